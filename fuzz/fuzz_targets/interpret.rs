@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+/// Enough statements to get into interesting states without letting loops stall the fuzzer.
+const FUEL: usize = 10_000;
+
+fuzz_target!(|source: &str| {
+    slow_lox::fuzz::fuzz_interpret(source, FUEL);
+});
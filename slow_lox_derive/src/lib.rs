@@ -0,0 +1,157 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitInt};
+
+/// Implements `slow_lox::host::LoxClass` for a struct with named fields.
+///
+/// Every field becomes a getter `name()` and a setter `set_name(value)` unless marked
+/// `#[lox(skip)]` (neither) or `#[lox(readonly)]` (getter only). Methods listed in
+/// `#[lox(methods(name = arity, ...))]` are forwarded to
+/// `fn name(&mut self, args: Vec<Value>) -> Result<Value, InterpretError>`.
+#[proc_macro_derive(LoxClass, attributes(lox))]
+pub fn derive_lox_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let class_name = name.to_string();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    name,
+                    "LoxClass can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "LoxClass can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut members = Vec::new();
+    for field in fields {
+        let mut skip = false;
+        let mut readonly = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("lox"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("readonly") {
+                    readonly = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `skip` or `readonly`"))
+                }
+            })?;
+        }
+        if skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let getter = ident.to_string();
+        members.push(quote! {
+            {
+                let this = ::std::rc::Rc::clone(this);
+                ::slow_lox::primitive::NativeFunction::new(
+                    #getter,
+                    0,
+                    ::std::rc::Rc::new(move |_, _| {
+                        Ok(::slow_lox::expr::Value::from(this.borrow().#ident.clone()))
+                    }),
+                )
+            }
+        });
+        if !readonly {
+            let setter = format!("set_{}", ident);
+            members.push(quote! {
+                {
+                    let this = ::std::rc::Rc::clone(this);
+                    ::slow_lox::primitive::NativeFunction::new(
+                        #setter,
+                        1,
+                        ::std::rc::Rc::new(move |_, mut args: Vec<::slow_lox::expr::Value>| {
+                            let value = <#ty as ::std::convert::TryFrom<::slow_lox::expr::Value>>::try_from(
+                                args.remove(0),
+                            )?;
+                            this.borrow_mut().#ident = value;
+                            Ok(::slow_lox::expr::Value::from(()))
+                        }),
+                    )
+                }
+            });
+        }
+    }
+
+    for (method, arity) in methods(&input)? {
+        let method_name = method.to_string();
+        members.push(quote! {
+            {
+                let this = ::std::rc::Rc::clone(this);
+                ::slow_lox::primitive::NativeFunction::new(
+                    #method_name,
+                    #arity,
+                    ::std::rc::Rc::new(move |_, args| this.borrow_mut().#method(args)),
+                )
+            }
+        });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::slow_lox::host::LoxClass for #name #ty_generics #where_clause {
+            fn class_name() -> &'static str {
+                #class_name
+            }
+
+            fn members(
+                this: &::std::rc::Rc<::std::cell::RefCell<Self>>,
+            ) -> Vec<::slow_lox::primitive::NativeFunction> {
+                vec![#(#members),*]
+            }
+        }
+    })
+}
+
+fn methods(input: &DeriveInput) -> Result<Vec<(Ident, usize)>, Error> {
+    let mut methods = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("lox"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("methods") {
+                return Err(meta.error("expected `methods(...)`"));
+            }
+            meta.parse_nested_meta(|method| {
+                let name = method
+                    .path
+                    .get_ident()
+                    .cloned()
+                    .ok_or_else(|| method.error("expected a method name"))?;
+                let arity: LitInt = method.value()?.parse()?;
+                methods.push((name, arity.base10_parse()?));
+                Ok(())
+            })
+        })?;
+    }
+    Ok(methods)
+}
@@ -0,0 +1,87 @@
+use slow_lox::{
+    expr::Value,
+    interpreter::{environment::Environment, InterpretError, Interpreter},
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    LoxClass,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(LoxClass)]
+#[lox(methods(area = 0, scale = 1))]
+struct Rect {
+    width: f64,
+    height: f64,
+    #[lox(readonly)]
+    label: String,
+    #[lox(skip)]
+    #[allow(dead_code)]
+    secret: Vec<u8>,
+}
+
+impl Rect {
+    fn area(&mut self, _args: Vec<Value>) -> Result<Value, InterpretError> {
+        Ok((self.width * self.height).into())
+    }
+
+    fn scale(&mut self, args: Vec<Value>) -> Result<Value, InterpretError> {
+        let factor = f64::try_from(args[0].clone())?;
+        self.width *= factor;
+        self.height *= factor;
+        Ok(().into())
+    }
+}
+
+fn run(interpreter: &mut Interpreter, source: &str) {
+    let tokens = Scanner::new(source.to_string()).scan_tokens();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    Resolver::new(interpreter).resolve(stmts.clone()).unwrap();
+    for stmt in stmts {
+        interpreter.interpret(stmt).unwrap();
+    }
+}
+
+#[test]
+fn test_members_bridge_to_the_rust_struct() {
+    let stdout = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter =
+        Interpreter::new(Rc::new(RefCell::new(Environment::global()))).with_stdout(stdout.clone());
+    let (value, rect) = Rect {
+        width: 2.0,
+        height: 3.0,
+        label: String::from("box"),
+        secret: Vec::new(),
+    }
+    .into_lox();
+    interpreter.define(String::from("rect"), value);
+
+    run(
+        &mut interpreter,
+        "print rect; print rect.label(); print rect.area(); rect.scale(2); print rect.area(); rect.set_width(1); print rect.area();",
+    );
+
+    assert_eq!(
+        String::from_utf8(stdout.borrow().clone()).unwrap(),
+        "Rect instance\n\"box\"\n6\n24\n6\n"
+    );
+    assert_eq!(rect.borrow().width, 1.0);
+    assert_eq!(rect.borrow().height, 6.0);
+}
+
+#[test]
+fn test_setter_type_errors() {
+    let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())));
+    let (value, _) = Rect {
+        width: 2.0,
+        height: 3.0,
+        label: String::new(),
+        secret: Vec::new(),
+    }
+    .into_lox();
+    interpreter.define(String::from("rect"), value);
+    let tokens = Scanner::new("rect.set_width(\"wide\");".to_string()).scan_tokens();
+    let stmt = Parser::new(tokens).parse().unwrap().remove(0);
+    let err = interpreter.interpret(stmt).unwrap_err();
+    assert_eq!(err.message, "Expected number, got \"wide\"");
+}
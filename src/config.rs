@@ -0,0 +1,173 @@
+//! User settings read from `~/.loxrc`, a TOML file such as:
+//!
+//! ```toml
+//! prompt = "lox> "
+//! color = "never"
+//! continuation_prompt = "...  "
+//! plugins = ["~/lox/libmath.so"]
+//! preload = ["~/lox/prelude.lox"]
+//! precision = 6
+//! ```
+//!
+//! Plugins load before any given with `--plugin`; preloaded files run at the start of every
+//! REPL session. `precision` rounds printed numbers to that many decimal places.
+
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use toml::{Table, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and `NO_COLOR` isn't set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+pub struct Config {
+    pub prompt: String,
+    pub continuation_prompt: String,
+    pub color: ColorChoice,
+    pub plugins: Vec<String>,
+    pub preload: Vec<String>,
+    pub precision: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prompt: String::from("> "),
+            continuation_prompt: String::from(".. "),
+            color: ColorChoice::Auto,
+            plugins: Vec::new(),
+            preload: Vec::new(),
+            precision: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path`, or `~/.loxrc` when none is given. A missing default file just means
+    /// defaults; any other problem is reported and the defaults are used instead.
+    pub fn load(path: Option<&Path>) -> Config {
+        let Some(path) = path.map(PathBuf::from).or_else(default_path) else {
+            return Config::default();
+        };
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Config::default(),
+            Err(e) => {
+                eprintln!("Could not read '{}': {}", path.display(), e);
+                return Config::default();
+            }
+        };
+        Config::parse(&source).unwrap_or_else(|e| {
+            eprintln!("Ignoring '{}': {}", path.display(), e);
+            Config::default()
+        })
+    }
+
+    fn parse(source: &str) -> Result<Config, String> {
+        let table = source
+            .parse::<Table>()
+            .map_err(|e| e.message().to_string())?;
+        let mut config = Config::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "prompt" => config.prompt = string(&key, value)?,
+                "continuation_prompt" => config.continuation_prompt = string(&key, value)?,
+                "color" => {
+                    config.color = match string(&key, value)?.as_str() {
+                        "auto" => ColorChoice::Auto,
+                        "always" => ColorChoice::Always,
+                        "never" => ColorChoice::Never,
+                        _ => return Err(String::from("'color' must be auto, always or never")),
+                    }
+                }
+                "plugins" => config.plugins = paths(&key, value)?,
+                "preload" => config.preload = paths(&key, value)?,
+                "precision" => {
+                    let digits = value.as_integer().and_then(|n| usize::try_from(n).ok());
+                    match digits {
+                        Some(digits) => config.precision = Some(digits),
+                        None => return Err(String::from("'precision' must be a whole number")),
+                    }
+                }
+                _ => return Err(format!("unknown setting '{}'", key)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn string(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(format!("'{}' must be a string", key)),
+    }
+}
+
+fn paths(key: &str, value: Value) -> Result<Vec<String>, String> {
+    let Value::Array(values) = value else {
+        return Err(format!("'{}' must be a list of paths", key));
+    };
+    values
+        .into_iter()
+        .map(|value| string(key, value).map(|path| expand_home(&path)))
+        .collect()
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".loxrc"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return dirs::home_dir();
+    #[cfg(target_arch = "wasm32")]
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse("prompt = \"lox> \"\npreload = [\"a.lox\"]").unwrap();
+        assert_eq!(config.prompt, "lox> ");
+        assert_eq!(config.continuation_prompt, ".. ");
+        assert_eq!(config.preload, vec!["a.lox"]);
+        assert!(Config::parse("prompt = 1").is_err());
+        assert_eq!(
+            Config::parse("color = \"never\"").unwrap().color,
+            ColorChoice::Never
+        );
+        assert!(Config::parse("color = \"blue\"").is_err());
+        assert_eq!(Config::parse("precision = 3").unwrap().precision, Some(3));
+        assert!(Config::parse("precision = -1").is_err());
+        assert!(Config::parse("colour = true").is_err());
+    }
+}
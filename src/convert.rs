@@ -0,0 +1,228 @@
+use crate::{
+    expr::Value,
+    interpreter::{ErrorKind, InterpretError},
+    primitive::Primitive,
+    token::{Token, TokenType},
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+// Values created on the Rust side have no source location, so they get a token
+// on line 0, the same way the interpreter fabricates `nil` for bare returns.
+fn host_value(primitive: Primitive) -> Value {
+    let (token_type, lexeme) = match &primitive {
+        Primitive::Number(number) => (TokenType::NUMBER, number.to_string()),
+        Primitive::Boolean(true) => (TokenType::TRUE, String::from("true")),
+        Primitive::Boolean(false) => (TokenType::FALSE, String::from("false")),
+        Primitive::String(string) => (TokenType::STRING, string.clone()),
+        Primitive::Nil => (TokenType::NIL, String::from("nil")),
+        _ => (TokenType::IDENTIFIER, primitive.to_string()),
+    };
+    Value {
+        primitive,
+        token: Token::new(token_type, lexeme, 0),
+    }
+}
+
+fn type_error(expected: &str, value: Value) -> InterpretError {
+    InterpretError::new(
+        ErrorKind::TypeError,
+        format!("Expected {}, got {}", expected, value.primitive),
+        value.token,
+    )
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        host_value(Primitive::Number(number))
+    }
+}
+
+impl From<i64> for Value {
+    fn from(number: i64) -> Self {
+        host_value(Primitive::Number(number as f64))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(boolean: bool) -> Self {
+        host_value(Primitive::Boolean(boolean))
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        host_value(Primitive::String(string))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        host_value(Primitive::String(string.to_string()))
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        host_value(Primitive::Nil)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Self {
+        match option {
+            Some(value) => value.into(),
+            None => host_value(Primitive::Nil),
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(list: Vec<T>) -> Self {
+        let list = list.into_iter().map(Into::into).collect();
+        host_value(Primitive::List(Rc::new(RefCell::new(list))))
+    }
+}
+
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(map: HashMap<String, T>) -> Self {
+        let map = map
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect();
+        host_value(Primitive::Map(Rc::new(RefCell::new(map))))
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = InterpretError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.primitive {
+            Primitive::Number(number) => Ok(number),
+            _ => Err(type_error("number", value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = InterpretError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.primitive {
+            Primitive::Number(number)
+                if number.fract() == 0.0
+                    && number >= i64::MIN as f64
+                    && number <= i64::MAX as f64 =>
+            {
+                Ok(number as i64)
+            }
+            _ => Err(type_error("integer", value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = InterpretError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.primitive {
+            Primitive::Boolean(boolean) => Ok(boolean),
+            _ => Err(type_error("boolean", value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = InterpretError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.primitive {
+            Primitive::String(string) => Ok(string),
+            _ => Err(type_error("string", value)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = InterpretError>> TryFrom<Value> for Option<T> {
+    type Error = InterpretError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.primitive {
+            Primitive::Nil => Ok(None),
+            _ => Ok(Some(T::try_from(value)?)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = InterpretError>> TryFrom<Value> for Vec<T> {
+    type Error = InterpretError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match &value.primitive {
+            Primitive::List(list) => list.borrow().iter().cloned().map(T::try_from).collect(),
+            _ => Err(type_error("list", value)),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = InterpretError>> TryFrom<Value> for HashMap<String, T> {
+    type Error = InterpretError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match &value.primitive {
+            Primitive::Map(map) => map
+                .borrow()
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), T::try_from(value.clone())?)))
+                .collect(),
+            _ => Err(type_error("map", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_scalars() {
+        assert_eq!(f64::try_from(Value::from(1.5)).unwrap(), 1.5);
+        assert_eq!(i64::try_from(Value::from(42i64)).unwrap(), 42);
+        assert!(bool::try_from(Value::from(true)).unwrap());
+        assert_eq!(String::try_from(Value::from("lox")).unwrap(), "lox");
+    }
+
+    #[test]
+    fn test_integer_rejects_fractions() {
+        let err = i64::try_from(Value::from(1.5)).unwrap_err();
+        assert_eq!(err.message, "Expected integer, got 1.5");
+    }
+
+    #[test]
+    fn test_option_maps_to_nil() {
+        assert_eq!(Value::from(None::<f64>).primitive, Primitive::Nil);
+        assert_eq!(Option::<f64>::try_from(Value::from(())).unwrap(), None);
+        assert_eq!(
+            Option::<f64>::try_from(Value::from(2.0)).unwrap(),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn test_collections() {
+        let list = Value::from(vec![1.0, 2.0, 3.0]);
+        assert_eq!(list.primitive.to_string(), "[1, 2, 3]");
+        assert_eq!(Vec::<f64>::try_from(list).unwrap(), vec![1.0, 2.0, 3.0]);
+
+        let map = Value::from(HashMap::from([(String::from("a"), true)]));
+        assert_eq!(map.primitive.to_string(), "{\"a\": true}");
+        let map = HashMap::<String, bool>::try_from(map).unwrap();
+        assert_eq!(map.get("a"), Some(&true));
+    }
+
+    #[test]
+    fn test_mismatched_element_type() {
+        let list = Value::from(vec!["a"]);
+        assert!(Vec::<f64>::try_from(list).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! Line coverage: which statements' lines ran, and how often.
+
+use crate::stmt::{self, Stmt};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write},
+};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Coverage {
+    /// Hit counts for every line that has a statement on it.
+    lines: BTreeMap<usize, usize>,
+}
+
+impl Coverage {
+    /// Registers the lines of `stmts`, including those in function and method bodies, as
+    /// coverable.
+    pub fn add_program(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if let Some(line) = stmt::line(stmt) {
+                self.lines.entry(line).or_insert(0);
+            }
+            match stmt {
+                Stmt::Block(stmts)
+                | Stmt::Function(_, _, stmts, _)
+                | Stmt::Class(_, _, stmts, _)
+                | Stmt::With(_, _, _, stmts) => self.add_program(stmts),
+                Stmt::If(_, then_branch, else_branch) => {
+                    self.add_program(std::slice::from_ref(then_branch));
+                    if let Some(else_branch) = else_branch {
+                        self.add_program(std::slice::from_ref(else_branch));
+                    }
+                }
+                Stmt::While(_, body, _) => self.add_program(std::slice::from_ref(body)),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn hit(&mut self, line: usize) {
+        *self.lines.entry(line).or_insert(0) += 1;
+    }
+
+    /// How many coverable lines ran at least once, out of how many there are.
+    pub fn summary(&self) -> (usize, usize) {
+        let hit = self.lines.values().filter(|hits| **hits > 0).count();
+        (hit, self.lines.len())
+    }
+
+    /// The lines that never ran.
+    pub fn missed(&self) -> Vec<usize> {
+        self.lines
+            .iter()
+            .filter(|(_, hits)| **hits == 0)
+            .map(|(line, _)| *line)
+            .collect()
+    }
+
+    /// An lcov record for `path`, to be written to a tracefile on its own or along with the
+    /// records of other files.
+    pub fn lcov(&self, path: &str) -> String {
+        let mut record = format!("TN:\nSF:{}\n", path);
+        for (line, hits) in &self.lines {
+            let _ = writeln!(record, "DA:{},{}", line, hits);
+        }
+        let (hit, total) = self.summary();
+        let _ = write!(record, "LF:{}\nLH:{}\nend_of_record\n", total, hit);
+        record
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (hit, total) = self.summary();
+        let percent = if total == 0 {
+            100.0
+        } else {
+            hit as f64 * 100.0 / total as f64
+        };
+        write!(f, "{} of {} lines covered ({:.1}%)", hit, total, percent)
+    }
+}
@@ -0,0 +1,286 @@
+//! Rendering of errors and warnings, with the offending source line and a caret underline
+//! when the source is known.
+
+use crate::{
+    expr::json_string,
+    token::{Token, TokenType},
+};
+use std::{
+    cell::{Cell, RefCell},
+    io::Write,
+    ops::Range,
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Where a diagnostic points. `column` is 1-based, with 0 meaning the position within the line
+/// isn't known; `text` is what should appear at that column and gets underlined. `span`, the
+/// byte range of a whole expression around `text`, is underlined instead when it's given and
+/// fits on the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub span: Option<Range<usize>>,
+}
+
+impl Location {
+    pub fn new(line: usize, column: usize, text: String) -> Self {
+        Self {
+            line,
+            column,
+            text,
+            span: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Option<Range<usize>>) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl From<&Token> for Location {
+    fn from(token: &Token) -> Self {
+        let text = match token.token_type {
+            // The lexeme of a string literal is its contents, without the quotes.
+            TokenType::STRING => format!("\"{}\"", token.lexeme),
+            _ => token.lexeme.clone(),
+        };
+        Location::new(token.line, token.column, text)
+    }
+}
+
+thread_local! {
+    static COLOR: Cell<bool> = const { Cell::new(false) };
+    static SOURCE: RefCell<Option<Rc<str>>> = const { RefCell::new(None) };
+}
+
+/// Turns ANSI colors in diagnostics on or off for this thread. Off by default.
+pub fn set_color(enabled: bool) {
+    COLOR.with(|color| color.set(enabled));
+}
+
+/// Whether diagnostics on this thread are colored.
+pub fn color() -> bool {
+    COLOR.with(Cell::get)
+}
+
+/// Runs `f` with `source` available for quoting in any diagnostics it reports.
+pub fn with_source<R>(source: &str, f: impl FnOnce() -> R) -> R {
+    let previous = SOURCE.with(|current| current.replace(Some(Rc::from(source))));
+    let result = f();
+    SOURCE.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Writes a diagnostic to `out`. `code` is an error code such as `E102`, shown after the label.
+pub fn report(
+    out: &mut dyn Write,
+    severity: Severity,
+    code: Option<&str>,
+    location: &Location,
+    message: &str,
+) {
+    let style = Style { color: color() };
+    let (label, color) = match severity {
+        Severity::Error => ("error", "1;31"),
+        Severity::Warning => ("warning", "1;33"),
+    };
+    let label = match code {
+        Some(code) => format!("{}[{}]", label, code),
+        None => label.to_string(),
+    };
+    let _ = writeln!(
+        out,
+        "{}{}",
+        style.paint(color, &label),
+        style.paint("1", &format!(": {}", message))
+    );
+    let arrow = style.paint("1;34", "-->");
+    match snippet(location) {
+        Some((line, column, width)) => {
+            let number = location.line.to_string();
+            let gutter = " ".repeat(number.len());
+            let bar = style.paint("1;34", "|");
+            let _ = writeln!(
+                out,
+                "{} {} line {}:{}",
+                gutter, arrow, location.line, location.column
+            );
+            let _ = writeln!(out, "{} {}", gutter, bar);
+            let _ = writeln!(out, "{} {} {}", style.paint("1;34", &number), bar, line);
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                gutter,
+                bar,
+                " ".repeat(column - 1),
+                style.paint(color, &"^".repeat(width))
+            );
+        }
+        None => {
+            let _ = writeln!(out, " {} line {}", arrow, location.line);
+        }
+    }
+}
+
+/// Writes a diagnostic to `out` as a single line of JSON, for tools to read. `file` is the
+/// script it came from, if there is one.
+pub fn report_json(
+    out: &mut dyn Write,
+    file: Option<&str>,
+    severity: Severity,
+    code: Option<&str>,
+    location: &Location,
+    message: &str,
+) {
+    let severity = match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    let _ = writeln!(
+        out,
+        r#"{{"file":{},"severity":"{}","code":{},"line":{},"column":{},"message":{}}}"#,
+        file.map_or(String::from("null"), json_string),
+        severity,
+        code.map_or(String::from("null"), json_string),
+        location.line,
+        location.column,
+        json_string(message)
+    );
+}
+
+/// The quoted source line and the column and width of the underline, if the location can be
+/// found in the current source. Errors from code run in an earlier REPL entry point at lines
+/// that aren't in it.
+fn snippet(location: &Location) -> Option<(String, usize, usize)> {
+    if location.column == 0 {
+        return None;
+    }
+    let source = SOURCE.with(|source| source.borrow().clone())?;
+    let start = source
+        .split_inclusive('\n')
+        .take(location.line.checked_sub(1)?)
+        .map(str::len)
+        .sum::<usize>();
+    let line = source.get(start..)?.lines().next()?;
+    if let Some(span) = span_columns(location, start..start + line.len(), &source) {
+        return Some((line.to_string(), span.start, span.len()));
+    }
+    let rest = line.chars().skip(location.column - 1).collect::<String>();
+    if !rest.starts_with(location.text.lines().next().unwrap_or("")) {
+        return None;
+    }
+    let width = location
+        .text
+        .lines()
+        .next()
+        .map_or(0, |text| text.chars().count());
+    Some((line.to_string(), location.column, width.max(1)))
+}
+
+/// The columns the location's span covers, if it lies within the line at `line` and contains
+/// the location's own column.
+fn span_columns(location: &Location, line: Range<usize>, source: &str) -> Option<Range<usize>> {
+    let span = location.span.clone()?;
+    if span.start < line.start || span.end > line.end || span.is_empty() {
+        return None;
+    }
+    let column = source.get(line.start..span.start)?.chars().count() + 1;
+    let columns = column..column + source.get(span)?.chars().count();
+    columns.contains(&location.column).then_some(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, location: Location) -> String {
+        let mut out = Vec::new();
+        with_source(source, || {
+            report(&mut out, Severity::Error, None, &location, "Oops.");
+        });
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_snippet() {
+        let location = Location::new(2, 7, String::from("nope"));
+        assert_eq!(
+            render("var a;\nprint nope;", location),
+            "error: Oops.\n  --> line 2:7\n  |\n2 | print nope;\n  |       ^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_span() {
+        let source = "var a;\nprint a + nope;";
+        let location = Location::new(2, 9, String::from("+")).with_span(Some(13..21));
+        assert_eq!(
+            render(source, location),
+            "error: Oops.\n  --> line 2:9\n  |\n2 | print a + nope;\n  |       ^^^^^^^^\n"
+        );
+        // A span that doesn't cover the location falls back to the token.
+        let location = Location::new(2, 9, String::from("+")).with_span(Some(0..3));
+        assert_eq!(
+            render(source, location),
+            "error: Oops.\n  --> line 2:9\n  |\n2 | print a + nope;\n  |         ^\n"
+        );
+    }
+
+    #[test]
+    fn test_no_snippet_for_other_source() {
+        let location = Location::new(2, 7, String::from("nope"));
+        assert_eq!(render("print 1;", location), "error: Oops.\n --> line 2\n");
+    }
+
+    #[test]
+    fn test_code() {
+        let mut out = Vec::new();
+        let location = Location::new(1, 0, String::new());
+        report(&mut out, Severity::Error, Some("E102"), &location, "Oops.");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "error[E102]: Oops.\n --> line 1\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let mut out = Vec::new();
+        let location = Location::new(2, 7, String::from("nope"));
+        report_json(
+            &mut out,
+            Some("a.lox"),
+            Severity::Warning,
+            None,
+            &location,
+            "\"Oops\".",
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"file\":\"a.lox\",\"severity\":\"warning\",\"code\":null,\"line\":2,\"column\":7,\"message\":\"\\\"Oops\\\".\"}\n"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Running Lox from another Rust program. Unlike `run`, which prints what goes wrong and
+//! carries on, a `Lox` stops at the first error and hands it back:
+//!
+//! ```
+//! use slow_lox::{Lox, LoxError};
+//!
+//! let mut lox = Lox::new();
+//! lox.run("fun square(n) { return n * n; }").unwrap();
+//! let nine = lox.interpreter().call_function("square", &[3.0.into()]).unwrap();
+//! assert_eq!(nine.primitive.to_string(), "9");
+//! assert!(matches!(lox.run("print nope;"), Err(LoxError::Runtime(_))));
+//! ```
+
+use crate::{
+    diagnostic::with_source,
+    interpreter::{environment::Environment, InterpretError, Interpreter},
+};
+use std::{cell::RefCell, fmt::Display, io::Write, rc::Rc};
+
+/// An interpreter whose globals last from one `run` to the next.
+pub struct Lox {
+    interpreter: Interpreter,
+    /// Where the interpreter writes diagnostics, to be handed back with compile errors.
+    diagnostics: Rc<RefCell<Vec<u8>>>,
+}
+
+#[derive(Debug)]
+pub enum LoxError {
+    /// The source didn't scan, parse or resolve, so none of it ran. Holds the diagnostics as
+    /// they'd be printed.
+    Compile(String),
+    /// A statement failed; the ones before it ran and the ones after it didn't.
+    Runtime(InterpretError),
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Compile(diagnostics) => write!(f, "{}", diagnostics.trim_end()),
+            LoxError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        let diagnostics = Rc::new(RefCell::new(Vec::new()));
+        let interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stderr(diagnostics.clone());
+        Self {
+            interpreter,
+            diagnostics,
+        }
+    }
+
+    /// Sends what scripts print to `stdout` instead of the process's standard output.
+    pub fn with_stdout(mut self, stdout: Rc<RefCell<dyn Write>>) -> Self {
+        self.interpreter = self.interpreter.with_stdout(stdout);
+        self
+    }
+
+    /// The interpreter underneath, for defining natives or calling Lox functions.
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    /// Compiles `source` and runs it, stopping at the first statement that fails.
+    pub fn run(&mut self, source: &str) -> Result<(), LoxError> {
+        self.diagnostics.borrow_mut().clear();
+        with_source(source, || {
+            let Some(stmts) = crate::compile(source.to_string(), &mut self.interpreter, false)
+            else {
+                let diagnostics = String::from_utf8_lossy(&self.diagnostics.borrow()).into_owned();
+                return Err(LoxError::Compile(diagnostics));
+            };
+            self.interpreter.clear_interrupt();
+            for stmt in stmts {
+                if let Err(e) = self.interpreter.interpret(stmt) {
+                    // Counted like any other error, and seen by `on_error` hooks.
+                    self.interpreter.report_error(&e);
+                    return Err(LoxError::Runtime(e));
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::ErrorKind;
+
+    #[test]
+    fn test_lox() {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let mut lox = Lox::new().with_stdout(stdout.clone());
+        lox.run("var x = 2;").unwrap();
+        lox.run("print x * 3;").unwrap();
+
+        let error = lox.run("print x; print (;").unwrap_err();
+        let LoxError::Compile(diagnostics) = &error else {
+            panic!("expected a compile error, got {:?}", error);
+        };
+        assert!(diagnostics.contains("Expect expression."));
+
+        let error = lox.run("print 1; print nope; print 2;").unwrap_err();
+        let LoxError::Runtime(runtime) = &error else {
+            panic!("expected a runtime error, got {:?}", error);
+        };
+        assert_eq!(runtime.kind, ErrorKind::NameError);
+        assert_eq!(error.to_string(), "Undefined variable 'nope'.");
+        assert_eq!(
+            String::from_utf8(stdout.borrow().clone()).unwrap(),
+            "6\n1\n"
+        );
+    }
+
+    #[test]
+    fn test_unbounded_recursion() {
+        // Runs on the test thread, whose stack is far smaller than the CLI's.
+        let mut lox = Lox::new();
+        let error = lox.run("fun f() { f(); } f();").unwrap_err();
+        let LoxError::Runtime(runtime) = &error else {
+            panic!("expected a runtime error, got {:?}", error);
+        };
+        assert_eq!(runtime.message, "Stack overflow.");
+    }
+}
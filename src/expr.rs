@@ -1,8 +1,9 @@
 use crate::primitive::Primitive;
 use crate::token::Token;
-use std::fmt::Debug;
+use std::{fmt::Debug, ops::Range};
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Binary(Binary),
     Grouping(Grouping),
@@ -15,10 +16,13 @@ pub enum Expr {
     Call(Call),
     Get(GetExpr),
     Set(SetExpr),
+    This(This),
+    Super(Super),
 }
 
 // 1 + 2, 3 * 4, etc.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Binary {
     pub left: Box<Expr>,
     pub operator: Token,
@@ -27,18 +31,23 @@ pub struct Binary {
 
 // (expression)
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Grouping {
     pub expression: Box<Expr>,
+    /// The byte offsets of the `(` and just past the `)`.
+    pub span: Range<usize>,
 }
 
 // true, false, nil, 1, 2, 3, etc.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Literal {
     pub value: Token,
 }
 
 // -1, !true, etc.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Unary {
     pub operator: Token,
     pub right: Box<Expr>,
@@ -46,6 +55,7 @@ pub struct Unary {
 
 // condition ? then_branch : else_branch
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ternary {
     pub condition: Box<Expr>,
     pub then_branch: Box<Expr>,
@@ -53,17 +63,20 @@ pub struct Ternary {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variable {
     pub name: Token,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Assignment {
     pub name: Token,
     pub value: Box<Expr>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Logical {
     pub left: Box<Expr>,
     pub operator: Token,
@@ -71,6 +84,7 @@ pub struct Logical {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Call {
     pub callee: Box<Expr>,
     pub paren: Token,
@@ -84,19 +98,80 @@ pub struct Value {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GetExpr {
     pub expr: Box<Expr>,
     pub name: Token,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SetExpr {
     pub expr: Box<Expr>,
     pub name: Token,
     pub value: Box<Expr>,
 }
 
-#[allow(dead_code)]
+// this, inside a method
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct This {
+    pub keyword: Token,
+}
+
+// super.method, inside a subclass's method
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Super {
+    pub keyword: Token,
+    pub method: Token,
+}
+
+/// The line an expression starts on.
+pub fn line(expr: &Expr) -> usize {
+    first_token(expr).line
+}
+
+/// The first token of an expression that the parser kept. A grouping's `(` isn't kept, so
+/// for those it's the first token inside.
+pub fn first_token(expr: &Expr) -> &Token {
+    match expr {
+        Expr::Binary(binary) => first_token(&binary.left),
+        Expr::Grouping(grouping) => first_token(&grouping.expression),
+        Expr::Literal(literal) => &literal.value,
+        Expr::Unary(unary) => &unary.operator,
+        Expr::Logical(logical) => first_token(&logical.left),
+        Expr::Ternary(ternary) => first_token(&ternary.condition),
+        Expr::Variable(variable) => &variable.name,
+        Expr::Assign(assignment) => &assignment.name,
+        Expr::Call(call) => first_token(&call.callee),
+        Expr::Get(get) => first_token(&get.expr),
+        Expr::Set(set) => first_token(&set.expr),
+        Expr::This(this) => &this.keyword,
+        Expr::Super(super_expr) => &super_expr.keyword,
+    }
+}
+
+/// The byte range of the source an expression was parsed from, from its first token to its
+/// last.
+pub fn span(expr: &Expr) -> Range<usize> {
+    match expr {
+        Expr::Binary(binary) => span(&binary.left).start..span(&binary.right).end,
+        Expr::Grouping(grouping) => grouping.span.clone(),
+        Expr::Literal(literal) => literal.value.start..literal.value.end,
+        Expr::Unary(unary) => unary.operator.start..span(&unary.right).end,
+        Expr::Logical(logical) => span(&logical.left).start..span(&logical.right).end,
+        Expr::Ternary(ternary) => span(&ternary.condition).start..span(&ternary.else_branch).end,
+        Expr::Variable(variable) => variable.name.start..variable.name.end,
+        Expr::Assign(assignment) => assignment.name.start..span(&assignment.value).end,
+        Expr::Call(call) => span(&call.callee).start..call.paren.end,
+        Expr::Get(get) => span(&get.expr).start..get.name.end,
+        Expr::Set(set) => span(&set.expr).start..span(&set.value).end,
+        Expr::This(this) => this.keyword.start..this.keyword.end,
+        Expr::Super(super_expr) => super_expr.keyword.start..super_expr.method.end,
+    }
+}
+
 pub fn print(expr: Expr) -> String {
     match expr {
         Expr::Binary(binary) => {
@@ -141,18 +216,13 @@ pub fn print(expr: Expr) -> String {
         Expr::Call(call) => {
             let mut args = String::new();
             for arg in call.arguments {
+                args.push(' ');
                 args.push_str(&print(arg));
-                args.push_str(", ");
             }
-            format!(
-                "(call {} {} {})",
-                print(*call.callee),
-                call.paren.lexeme,
-                args
-            )
+            format!("(call {}{})", print(*call.callee), args)
         }
         Expr::Get(get_expr) => {
-            format!("(get {})", print(*get_expr.expr))
+            format!("(get {} {})", get_expr.name.lexeme, print(*get_expr.expr))
         }
         Expr::Set(set_expr) => {
             format!(
@@ -162,5 +232,95 @@ pub fn print(expr: Expr) -> String {
                 print(*set_expr.value)
             )
         }
+        Expr::This(this) => this.keyword.lexeme,
+        Expr::Super(super_expr) => format!("(super {})", super_expr.method.lexeme),
+    }
+}
+
+pub fn to_json(expr: Expr) -> String {
+    match expr {
+        Expr::Binary(binary) => format!(
+            r#"{{"type":"Binary","operator":{},"left":{},"right":{}}}"#,
+            json_string(&binary.operator.lexeme),
+            to_json(*binary.left),
+            to_json(*binary.right)
+        ),
+        Expr::Grouping(grouping) => format!(
+            r#"{{"type":"Grouping","expression":{}}}"#,
+            to_json(*grouping.expression)
+        ),
+        Expr::Literal(literal) => format!(
+            r#"{{"type":"Literal","kind":{},"value":{}}}"#,
+            json_string(&format!("{:?}", literal.value.token_type)),
+            json_string(&literal.value.lexeme)
+        ),
+        Expr::Unary(unary) => format!(
+            r#"{{"type":"Unary","operator":{},"right":{}}}"#,
+            json_string(&unary.operator.lexeme),
+            to_json(*unary.right)
+        ),
+        Expr::Logical(logical) => format!(
+            r#"{{"type":"Logical","operator":{},"left":{},"right":{}}}"#,
+            json_string(&logical.operator.lexeme),
+            to_json(*logical.left),
+            to_json(*logical.right)
+        ),
+        Expr::Ternary(ternary) => format!(
+            r#"{{"type":"Ternary","condition":{},"then":{},"else":{}}}"#,
+            to_json(*ternary.condition),
+            to_json(*ternary.then_branch),
+            to_json(*ternary.else_branch)
+        ),
+        Expr::Variable(variable) => format!(
+            r#"{{"type":"Variable","name":{}}}"#,
+            json_string(&variable.name.lexeme)
+        ),
+        Expr::Assign(assignment) => format!(
+            r#"{{"type":"Assign","name":{},"value":{}}}"#,
+            json_string(&assignment.name.lexeme),
+            to_json(*assignment.value)
+        ),
+        Expr::Call(call) => format!(
+            r#"{{"type":"Call","callee":{},"arguments":[{}]}}"#,
+            to_json(*call.callee),
+            call.arguments
+                .into_iter()
+                .map(to_json)
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+        Expr::Get(get_expr) => format!(
+            r#"{{"type":"Get","object":{},"name":{}}}"#,
+            to_json(*get_expr.expr),
+            json_string(&get_expr.name.lexeme)
+        ),
+        Expr::Set(set_expr) => format!(
+            r#"{{"type":"Set","object":{},"name":{},"value":{}}}"#,
+            to_json(*set_expr.expr),
+            json_string(&set_expr.name.lexeme),
+            to_json(*set_expr.value)
+        ),
+        Expr::This(_) => String::from(r#"{"type":"This"}"#),
+        Expr::Super(super_expr) => format!(
+            r#"{{"type":"Super","method":{}}}"#,
+            json_string(&super_expr.method.lexeme)
+        ),
+    }
+}
+
+pub fn json_string(string: &str) -> String {
+    let mut json = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
     }
+    json.push('"');
+    json
 }
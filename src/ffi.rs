@@ -0,0 +1,300 @@
+//! C ABI for embedding the interpreter in non-Rust hosts.
+//!
+//! ```c
+//! LoxVm *vm = lox_new();
+//! if (lox_run(vm, "print 1 + 2;") != 0) {
+//!     fprintf(stderr, "%s", lox_last_error(vm));
+//! }
+//! lox_free(vm);
+//! ```
+
+use crate::{
+    expr::Value,
+    interpreter::{environment::Environment, ErrorKind, InterpretError, Interpreter},
+    primitive::Primitive,
+    token::{Token, TokenType},
+    Status,
+};
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    ptr,
+    rc::Rc,
+};
+
+pub struct LoxVm {
+    interpreter: Interpreter,
+    diagnostics: Rc<RefCell<Vec<u8>>>,
+    last_error: Option<CString>,
+    /// What a run that succeeded warned about.
+    last_warnings: Option<CString>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoxValueTag {
+    Nil,
+    Boolean,
+    Number,
+    String,
+}
+
+/// A Lox value as seen from C. `string` is only meaningful for `LoxValueTag::String`; strings
+/// passed to a native stay valid for the duration of the call, and strings returned from one
+/// are copied before the native returns control to the interpreter.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LoxValue {
+    pub tag: LoxValueTag,
+    pub boolean: bool,
+    pub number: f64,
+    pub string: *const c_char,
+}
+
+impl LoxValue {
+    fn nil() -> Self {
+        Self {
+            tag: LoxValueTag::Nil,
+            boolean: false,
+            number: 0.0,
+            string: ptr::null(),
+        }
+    }
+}
+
+/// Signature of natives registered from C. Returns `false` to signal an error, in which case
+/// the interpreter raises a runtime error with the native's name.
+pub type LoxNativeFn = extern "C" fn(
+    user_data: *mut c_void,
+    args: *const LoxValue,
+    argc: usize,
+    result: *mut LoxValue,
+) -> bool;
+
+#[no_mangle]
+pub extern "C" fn lox_new() -> *mut LoxVm {
+    let diagnostics = Rc::new(RefCell::new(Vec::new()));
+    let interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+        .with_stderr(diagnostics.clone());
+    Box::into_raw(Box::new(LoxVm {
+        interpreter,
+        diagnostics,
+        last_error: None,
+        last_warnings: None,
+    }))
+}
+
+/// # Safety
+/// `vm` must come from `lox_new` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn lox_free(vm: *mut LoxVm) {
+    if !vm.is_null() {
+        drop(Box::from_raw(vm));
+    }
+}
+
+/// Runs a NUL-terminated program. Returns 0 on success, 65 for compile errors and 70 for
+/// runtime errors, matching the CLI's exit codes.
+///
+/// # Safety
+/// `vm` must come from `lox_new` and `source` must be a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_run(vm: *mut LoxVm, source: *const c_char) -> c_int {
+    let Some(vm) = vm.as_mut() else {
+        return 64;
+    };
+    if source.is_null() {
+        return 64;
+    }
+    let source = CStr::from_ptr(source).to_string_lossy().into_owned();
+    vm.diagnostics.borrow_mut().clear();
+    let status = crate::run(source, &mut vm.interpreter);
+    let diagnostics = std::mem::take(&mut *vm.diagnostics.borrow_mut());
+    let diagnostics = if diagnostics.is_empty() {
+        None
+    } else {
+        CString::new(
+            diagnostics
+                .into_iter()
+                .filter(|b| *b != 0)
+                .collect::<Vec<u8>>(),
+        )
+        .ok()
+    };
+    // Warnings alone don't make a run fail.
+    if status == Status::Ok {
+        vm.last_error = None;
+        vm.last_warnings = diagnostics;
+    } else {
+        vm.last_error = diagnostics;
+        vm.last_warnings = None;
+    }
+    match status {
+        Status::Ok => 0,
+        Status::CompileError => 65,
+        Status::RuntimeError => 70,
+    }
+}
+
+/// Returns the diagnostics from the last `lox_run`, or NULL if it succeeded. The string is
+/// owned by the VM and stays valid until the next `lox_run` or `lox_free`.
+///
+/// # Safety
+/// `vm` must come from `lox_new`.
+#[no_mangle]
+pub unsafe extern "C" fn lox_last_error(vm: *const LoxVm) -> *const c_char {
+    match vm.as_ref().and_then(|vm| vm.last_error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Returns the warnings from the last `lox_run` if it succeeded, or NULL if there were none.
+/// The string is owned by the VM and stays valid until the next `lox_run` or `lox_free`.
+///
+/// # Safety
+/// `vm` must come from `lox_new`.
+#[no_mangle]
+pub unsafe extern "C" fn lox_last_warnings(vm: *const LoxVm) -> *const c_char {
+    match vm.as_ref().and_then(|vm| vm.last_warnings.as_ref()) {
+        Some(warnings) => warnings.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Defines a global native function backed by a C callback.
+///
+/// # Safety
+/// `vm` must come from `lox_new`, `name` must be a valid C string, and `user_data` must stay
+/// valid for as long as the VM lives.
+#[no_mangle]
+pub unsafe extern "C" fn lox_register_native(
+    vm: *mut LoxVm,
+    name: *const c_char,
+    arity: usize,
+    function: LoxNativeFn,
+    user_data: *mut c_void,
+) -> bool {
+    let Some(vm) = vm.as_mut() else {
+        return false;
+    };
+    if name.is_null() {
+        return false;
+    }
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let native_name = name.clone();
+    vm.interpreter.define_native(&name, arity, move |_, args| {
+        let token = Token::new(TokenType::IDENTIFIER, native_name.clone(), 0);
+        // Keeps the argument strings alive until the callback returns.
+        let strings = args
+            .iter()
+            .map(|arg| match &arg.primitive {
+                Primitive::String(string) => CString::new(string.replace('\0', "")).ok(),
+                _ => None,
+            })
+            .collect::<Vec<Option<CString>>>();
+        let c_args = args
+            .iter()
+            .zip(&strings)
+            .map(|(arg, string)| to_c(arg, string))
+            .collect::<Vec<LoxValue>>();
+        let mut result = LoxValue::nil();
+        if !function(user_data, c_args.as_ptr(), c_args.len(), &mut result) {
+            return Err(InterpretError::new(
+                ErrorKind::NativeError,
+                format!("Native function '{}' failed.", native_name),
+                token,
+            ));
+        }
+        Ok(from_c(&result))
+    });
+    true
+}
+
+fn to_c(value: &Value, string: &Option<CString>) -> LoxValue {
+    let mut c_value = LoxValue::nil();
+    match &value.primitive {
+        Primitive::Boolean(boolean) => {
+            c_value.tag = LoxValueTag::Boolean;
+            c_value.boolean = *boolean;
+        }
+        Primitive::Number(number) => {
+            c_value.tag = LoxValueTag::Number;
+            c_value.number = *number;
+        }
+        Primitive::String(_) => {
+            c_value.tag = LoxValueTag::String;
+            c_value.string = string.as_ref().map_or(ptr::null(), |s| s.as_ptr());
+        }
+        // Functions, classes and collections have no C representation.
+        _ => {}
+    }
+    c_value
+}
+
+unsafe fn from_c(value: &LoxValue) -> Value {
+    match value.tag {
+        LoxValueTag::Nil => ().into(),
+        LoxValueTag::Boolean => value.boolean.into(),
+        LoxValueTag::Number => value.number.into(),
+        LoxValueTag::String if value.string.is_null() => ().into(),
+        LoxValueTag::String => CStr::from_ptr(value.string)
+            .to_string_lossy()
+            .into_owned()
+            .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn add(
+        user_data: *mut c_void,
+        args: *const LoxValue,
+        argc: usize,
+        result: *mut LoxValue,
+    ) -> bool {
+        let args = unsafe { std::slice::from_raw_parts(args, argc) };
+        if args.iter().any(|arg| arg.tag != LoxValueTag::Number) {
+            return false;
+        }
+        let calls = unsafe { &mut *(user_data as *mut usize) };
+        *calls += 1;
+        unsafe {
+            (*result).tag = LoxValueTag::Number;
+            (*result).number = args[0].number + args[1].number;
+        }
+        true
+    }
+
+    #[test]
+    fn test_run_and_natives() {
+        let mut calls = 0usize;
+        unsafe {
+            let vm = lox_new();
+            assert!(lox_register_native(
+                vm,
+                c"add".as_ptr(),
+                2,
+                add,
+                &mut calls as *mut usize as *mut c_void,
+            ));
+            assert_eq!(lox_run(vm, c"var x = add(1, 2);".as_ptr()), 0);
+            assert!(lox_last_error(vm).is_null());
+
+            assert_eq!(lox_run(vm, c"add(\"a\", 2);".as_ptr()), 70);
+            let error = CStr::from_ptr(lox_last_error(vm)).to_str().unwrap();
+            assert!(error.contains("Native function 'add' failed."));
+            assert!(lox_last_warnings(vm).is_null());
+
+            let source = c"fun f() { var unused = 1; } f();";
+            assert_eq!(lox_run(vm, source.as_ptr()), 0);
+            assert!(lox_last_error(vm).is_null());
+            let warnings = CStr::from_ptr(lox_last_warnings(vm)).to_str().unwrap();
+            assert!(warnings.contains("warning[unused-local]"));
+            lox_free(vm);
+        }
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,119 @@
+//! Constant folding: the value of an expression built only from literals, worked out without
+//! running anything.
+
+use crate::{
+    expr::Expr,
+    primitive::Primitive,
+    token::{Token, TokenType},
+};
+
+/// The value `expr` always has, or None if it depends on anything that isn't a literal or
+/// would fail at runtime.
+pub fn constant(expr: &Expr) -> Option<Primitive> {
+    match expr {
+        Expr::Literal(literal) => literal_value(&literal.value),
+        Expr::Grouping(grouping) => constant(&grouping.expression),
+        Expr::Unary(unary) => {
+            let right = constant(&unary.right)?;
+            match (unary.operator.token_type, right) {
+                (TokenType::BANG, right) => Some(Primitive::Boolean(!right.is_truthy())),
+                (TokenType::MINUS, Primitive::Number(right)) => Some(Primitive::Number(-right)),
+                _ => None,
+            }
+        }
+        Expr::Binary(binary) => {
+            let left = constant(&binary.left)?;
+            let right = constant(&binary.right)?;
+            binary_value(&binary.operator, left, right)
+        }
+        Expr::Logical(logical) => {
+            let left = constant(&logical.left)?;
+            match (logical.operator.token_type, left.is_truthy()) {
+                (TokenType::OR, true) | (TokenType::AND, false) => Some(left),
+                _ => constant(&logical.right),
+            }
+        }
+        Expr::Ternary(ternary) => {
+            if constant(&ternary.condition)?.is_truthy() {
+                constant(&ternary.then_branch)
+            } else {
+                constant(&ternary.else_branch)
+            }
+        }
+        Expr::Variable(_)
+        | Expr::Assign(_)
+        | Expr::Call(_)
+        | Expr::Get(_)
+        | Expr::Set(_)
+        | Expr::This(_)
+        | Expr::Super(_) => None,
+    }
+}
+
+fn literal_value(token: &Token) -> Option<Primitive> {
+    match token.token_type {
+        TokenType::NUMBER => token.lexeme.parse().ok().map(Primitive::Number),
+        TokenType::STRING => Some(Primitive::String(token.lexeme.clone())),
+        TokenType::TRUE => Some(Primitive::Boolean(true)),
+        TokenType::FALSE => Some(Primitive::Boolean(false)),
+        TokenType::NIL => Some(Primitive::Nil),
+        _ => None,
+    }
+}
+
+fn binary_value(operator: &Token, left: Primitive, right: Primitive) -> Option<Primitive> {
+    use Primitive::{Boolean, Number};
+    let value = match (operator.token_type, left, right) {
+        (TokenType::COMMA, _, right) => right,
+        (TokenType::EQUAL_EQUAL, left, right) => Boolean(left == right),
+        (TokenType::BANG_EQUAL, left, right) => Boolean(left != right),
+        (TokenType::PLUS, Primitive::String(left), Primitive::String(right)) => {
+            Primitive::String(left + &right)
+        }
+        (token_type, Number(left), Number(right)) => match token_type {
+            TokenType::PLUS => Number(left + right),
+            TokenType::MINUS => Number(left - right),
+            TokenType::STAR => Number(left * right),
+            TokenType::SLASH if right != 0.0 => Number(left / right),
+            TokenType::GREATER => Boolean(left > right),
+            TokenType::GREATER_EQUAL => Boolean(left >= right),
+            TokenType::LESS => Boolean(left < right),
+            TokenType::LESS_EQUAL => Boolean(left <= right),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner, stmt::Stmt};
+
+    fn fold(source: &str) -> Option<Primitive> {
+        let tokens = Scanner::new(format!("{};", source)).scan_tokens();
+        match Parser::new(tokens).parse().unwrap().pop() {
+            Some(Stmt::Expr(expr)) => constant(&expr),
+            stmt => panic!("expected an expression, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_constant() {
+        assert_eq!(fold("1 + 2 * 3"), Some(Primitive::Number(7.0)));
+        assert_eq!(
+            fold("\"a\" + \"b\""),
+            Some(Primitive::String(String::from("ab")))
+        );
+        assert_eq!(fold("!(1 < 2) or nil"), Some(Primitive::Nil));
+        assert_eq!(
+            fold("1 == 1 ? \"y\" : x"),
+            Some(Primitive::String(String::from("y")))
+        );
+        assert_eq!(fold("1 / 0"), None);
+        assert_eq!(fold("(1, 2), 3"), Some(Primitive::Number(3.0)));
+        assert_eq!(fold("x > 1"), None);
+        assert_eq!(fold("f()"), None);
+    }
+}
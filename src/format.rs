@@ -0,0 +1,455 @@
+//! The formatter behind `slow_lox fmt`: prints a parsed program back out as source with
+//! canonical indentation and spacing, and long calls wrapped one argument per line.
+//!
+//! It works from the syntax tree, so the `while` loops the parser turns `for` loops into are
+//! recognised and printed as `for` loops again; that needs an initializer and an increment,
+//! and a `for` missing either comes back as a `while`. Comments are put back before the
+//! statement that follows them, or at the end of the line they trailed.
+
+use crate::{
+    expr::Expr,
+    scanner::Comment,
+    stmt::{self, Stmt},
+    token::TokenType,
+};
+
+const INDENT: &str = "    ";
+/// Calls that would run past this column get their arguments on separate lines.
+const WIDTH: usize = 100;
+
+/// Formats the program parsed from `source`. The source is only consulted to keep the
+/// blank lines that separated statements.
+pub fn format(source: &str, stmts: &[Stmt], comments: &[Comment]) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        indent: 0,
+        comments,
+        lines: source.lines().collect(),
+    };
+    formatter.stmts(stmts, None);
+    formatter.comments_before(None);
+    formatter.out
+}
+
+struct Formatter<'a> {
+    out: String,
+    indent: usize,
+    /// Comments not yet written out.
+    comments: &'a [Comment],
+    lines: Vec<&'a str>,
+}
+
+impl Formatter<'_> {
+    /// Writes `stmts`, followed by any comments before `end`, the line of whatever comes
+    /// after them.
+    fn stmts(&mut self, stmts: &[Stmt], end: Option<usize>) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            if i > 0 && (is_declaration(stmt) || is_declaration(&stmts[i - 1])) {
+                self.blank_line();
+            }
+            if let Some(line) = first_line(stmt) {
+                self.comments_before(Some(line));
+                self.blank_line_before(line);
+            }
+            let next = stmts[i + 1..].iter().find_map(first_line).or(end);
+            self.stmt(stmt, next);
+        }
+        self.comments_before(end);
+    }
+
+    /// Writes out the comments that start before `line`, or all of them if there's no line.
+    fn comments_before(&mut self, line: Option<usize>) {
+        while let Some((comment, rest)) = self.comments.split_first() {
+            if line.is_some_and(|line| comment.line >= line) {
+                break;
+            }
+            self.comments = rest;
+            if comment.trailing && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push_str("  ");
+                self.out.push_str(&comment.text);
+                self.out.push('\n');
+            } else {
+                self.blank_line_before(comment.line);
+                self.line(&comment.text);
+            }
+        }
+    }
+
+    /// Keeps a blank line the source had before `line`.
+    fn blank_line_before(&mut self, line: usize) {
+        let blank = line
+            .checked_sub(2)
+            .and_then(|previous| self.lines.get(previous))
+            .is_some_and(|previous| previous.trim().is_empty());
+        if blank {
+            self.blank_line();
+        }
+    }
+
+    /// Separates what comes next with a blank line, unless it starts the file or a block.
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&INDENT.repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, next: Option<usize>) {
+        if let Some(line) = stmt::line(stmt) {
+            self.comments_before(Some(line));
+        }
+        match stmt {
+            Stmt::Expr(expr) => {
+                let expr = self.expr(expr);
+                self.line(&format!("{};", expr));
+            }
+            Stmt::Print(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| self.expr(value))
+                    .collect::<Vec<_>>();
+                self.line(&format!("print {};", values.join(", ")));
+            }
+            Stmt::Var(name, None, annotation) => {
+                let name = stmt::annotated(&name.lexeme, annotation.as_ref());
+                self.line(&format!("var {};", name));
+            }
+            Stmt::Var(name, Some(initializer), annotation) => {
+                let name = stmt::annotated(&name.lexeme, annotation.as_ref());
+                let initializer = self.expr(initializer);
+                self.line(&format!("var {} = {};", name, initializer));
+            }
+            Stmt::Block(stmts) => match for_loop(stmts) {
+                Some((initializer, condition, body, increment)) => {
+                    let initializer = self.clause(initializer);
+                    self.for_loop(&initializer, condition, body, increment, next);
+                }
+                None => {
+                    self.line("{");
+                    self.block(stmts, next);
+                }
+            },
+            Stmt::With(_, resource, name, body) => {
+                let resource = self.expr(resource);
+                self.line(&format!("with {} as {} {{", resource, name.lexeme));
+                self.block(body, next);
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                let condition = self.expr(condition);
+                let then_next = else_branch.as_deref().and_then(first_line).or(next);
+                self.body(&format!("if ({})", condition), then_branch, then_next);
+                if let Some(else_branch) = else_branch {
+                    self.else_branch(else_branch, next);
+                }
+            }
+            Stmt::While(condition, body, Some(increment)) => {
+                self.for_loop(";", condition.as_ref(), body, increment, next);
+            }
+            Stmt::While(condition, body, None) => {
+                let condition = condition
+                    .as_ref()
+                    .map_or(String::from("true"), |condition| self.expr(condition));
+                self.body(&format!("while ({})", condition), body, next);
+            }
+            Stmt::Function(..) => self.function("fun ", stmt, next),
+            Stmt::Class(name, superclass, methods, _) => {
+                let heading = match superclass {
+                    Some(superclass) => {
+                        format!("class {} < {}", name.lexeme, superclass.name.lexeme)
+                    }
+                    None => format!("class {}", name.lexeme),
+                };
+                if methods.is_empty() {
+                    return self.line(&format!("{} {{}}", heading));
+                }
+                self.line(&format!("{} {{", heading));
+                self.indent += 1;
+                for (i, method) in methods.iter().enumerate() {
+                    if i > 0 {
+                        self.blank_line();
+                    }
+                    let following = methods[i + 1..].iter().find_map(first_line).or(next);
+                    self.function("", method, following);
+                }
+                self.comments_before(next);
+                self.indent -= 1;
+                self.line("}");
+            }
+            Stmt::Return(_, values) if values.is_empty() => self.line("return;"),
+            Stmt::Return(_, values) => {
+                let values = values
+                    .iter()
+                    .map(|value| self.expr(value))
+                    .collect::<Vec<_>>();
+                self.line(&format!("return {};", values.join(", ")));
+            }
+            Stmt::Unpack(keyword, names, values) => {
+                let names = names
+                    .iter()
+                    .map(|name| name.lexeme.as_str())
+                    .collect::<Vec<_>>();
+                let values = values
+                    .iter()
+                    .map(|value| self.expr(value))
+                    .collect::<Vec<_>>();
+                let keyword = if keyword.is_some() { "var " } else { "" };
+                let (names, values) = (names.join(", "), values.join(", "));
+                self.line(&format!("{}{} = {};", keyword, names, values));
+            }
+            Stmt::Break(_) => self.line("break;"),
+            Stmt::Continue(_) => self.line("continue;"),
+        }
+    }
+
+    fn function(&mut self, keyword: &str, stmt: &Stmt, next: Option<usize>) {
+        let Stmt::Function(name, params, body, signature) = stmt else {
+            return self.stmt(stmt, next);
+        };
+        self.comments_before(Some(name.line));
+        let params = params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| stmt::annotated(&param.lexeme, signature.parameter(i)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let returns = stmt::annotated("", signature.returns.as_ref());
+        let header = format!("{}{}({}){}", keyword, name.lexeme, params, returns);
+        if body.is_empty() {
+            return self.line(&format!("{} {{}}", header));
+        }
+        self.line(&format!("{} {{", header));
+        self.block(body, next);
+    }
+
+    /// A `for` loop initializer or increment, on one line.
+    /// Writes a `for` loop whose initializer clause, with its `;`, is already formatted.
+    fn for_loop(
+        &mut self,
+        initializer: &str,
+        condition: Option<&Expr>,
+        body: &Stmt,
+        increment: &Expr,
+        next: Option<usize>,
+    ) {
+        let condition = match condition {
+            Some(condition) => format!(" {}", self.expr(condition)),
+            None => String::new(),
+        };
+        let increment = self.expr(increment);
+        let header = format!("for ({}{}; {})", initializer, condition, increment);
+        self.body(&header, body, next);
+    }
+
+    fn clause(&self, stmt: &Stmt) -> String {
+        let mut formatter = Formatter {
+            out: String::new(),
+            indent: 0,
+            comments: &[],
+            lines: Vec::new(),
+        };
+        formatter.stmt(stmt, None);
+        formatter.out.trim_end().to_string()
+    }
+
+    /// Writes the indented statements of a block and its closing brace.
+    fn block(&mut self, stmts: &[Stmt], next: Option<usize>) {
+        self.indent += 1;
+        self.stmts(stmts, next);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// Writes a loop or branch body after `header`: a block opens on the same line, anything
+    /// else goes on the next line, indented.
+    fn body(&mut self, header: &str, body: &Stmt, next: Option<usize>) {
+        match body {
+            Stmt::Block(stmts) if stmts.is_empty() => self.line(&format!("{} {{}}", header)),
+            Stmt::Block(stmts) => {
+                self.line(&format!("{} {{", header));
+                self.block(stmts, next);
+            }
+            body => {
+                self.line(header);
+                self.indent += 1;
+                self.stmt(body, next);
+                self.indent -= 1;
+            }
+        }
+    }
+
+    /// Writes an `else` branch, starting on the line that closed the `if` body when it
+    /// ended with a brace.
+    fn else_branch(&mut self, else_branch: &Stmt, next: Option<usize>) {
+        let start = self.out.len();
+        match else_branch {
+            Stmt::If(..) => self.stmt(else_branch, next),
+            _ => self.body("else", else_branch, next),
+        }
+        let written = self.out.split_off(start);
+        if self.out.ends_with("}\n") {
+            self.out.pop();
+            self.out.push(' ');
+        } else {
+            self.out.push_str(&INDENT.repeat(self.indent));
+        }
+        if let Stmt::If(..) = else_branch {
+            self.out.push_str("else ");
+        }
+        self.out.push_str(written.trim_start());
+    }
+
+    fn expr(&self, expr: &Expr) -> String {
+        let column = self.indent * INDENT.len();
+        self.expr_at(expr, column)
+    }
+
+    /// Formats `expr` as it would appear starting at `column`, which decides whether a long
+    /// call gets wrapped.
+    fn expr_at(&self, expr: &Expr, column: usize) -> String {
+        match expr {
+            Expr::Binary(binary) if binary.operator.token_type == TokenType::COMMA => format!(
+                "{}, {}",
+                self.expr_at(&binary.left, column),
+                self.expr_at(&binary.right, column)
+            ),
+            Expr::Binary(binary) => format!(
+                "{} {} {}",
+                self.expr_at(&binary.left, column),
+                binary.operator.lexeme,
+                self.expr_at(&binary.right, column)
+            ),
+            Expr::Logical(logical) => format!(
+                "{} {} {}",
+                self.expr_at(&logical.left, column),
+                logical.operator.lexeme,
+                self.expr_at(&logical.right, column)
+            ),
+            Expr::Grouping(grouping) => format!("({})", self.expr_at(&grouping.expression, column)),
+            Expr::Literal(literal) => match literal.value.token_type {
+                TokenType::STRING => format!("\"{}\"", literal.value.lexeme),
+                _ => literal.value.lexeme.clone(),
+            },
+            Expr::Unary(unary) => format!(
+                "{}{}",
+                unary.operator.lexeme,
+                self.expr_at(&unary.right, column)
+            ),
+            Expr::Ternary(ternary) => format!(
+                "{} ? {} : {}",
+                self.expr_at(&ternary.condition, column),
+                self.expr_at(&ternary.then_branch, column),
+                self.expr_at(&ternary.else_branch, column)
+            ),
+            Expr::Variable(variable) => variable.name.lexeme.clone(),
+            Expr::Assign(assignment) => format!(
+                "{} = {}",
+                assignment.name.lexeme,
+                self.expr_at(&assignment.value, column)
+            ),
+            Expr::Get(get) => format!("{}.{}", self.expr_at(&get.expr, column), get.name.lexeme),
+            Expr::Set(set) => format!(
+                "{}.{} = {}",
+                self.expr_at(&set.expr, column),
+                set.name.lexeme,
+                self.expr_at(&set.value, column)
+            ),
+            Expr::This(this) => this.keyword.lexeme.clone(),
+            Expr::Super(super_expr) => format!("super.{}", super_expr.method.lexeme),
+            Expr::Call(call) => {
+                let callee = self.expr_at(&call.callee, column);
+                let arguments = call
+                    .arguments
+                    .iter()
+                    .map(|argument| self.expr_at(argument, column))
+                    .collect::<Vec<_>>();
+                let flat = format!("{}({})", callee, arguments.join(", "));
+                if column + flat.len() <= WIDTH || arguments.is_empty() {
+                    return flat;
+                }
+                let inner = column + INDENT.len();
+                let mut wrapped = format!("{}(\n", callee);
+                for argument in &call.arguments {
+                    wrapped.push_str(&" ".repeat(inner));
+                    wrapped.push_str(&self.expr_at(argument, inner));
+                    wrapped.push_str(",\n");
+                }
+                // Lox doesn't allow a trailing comma.
+                wrapped.truncate(wrapped.len() - 2);
+                wrapped.push('\n');
+                wrapped.push_str(&" ".repeat(column));
+                wrapped.push(')');
+                wrapped
+            }
+        }
+    }
+}
+
+/// The parts of a desugared `for` loop: `{ initializer; while (condition) body increment }`.
+fn for_loop(stmts: &[Stmt]) -> Option<(&Stmt, Option<&Expr>, &Stmt, &Expr)> {
+    match stmts {
+        [initializer @ (Stmt::Var(..) | Stmt::Unpack(..) | Stmt::Expr(_)), Stmt::While(condition, body, Some(increment))] => {
+            Some((initializer, condition.as_ref(), body, increment))
+        }
+        _ => None,
+    }
+}
+
+/// The line of the first statement in `stmt`, looking inside blocks, which have none.
+fn first_line(stmt: &Stmt) -> Option<usize> {
+    match stmt {
+        Stmt::Block(stmts) => stmts.iter().find_map(first_line),
+        stmt => stmt::line(stmt),
+    }
+}
+
+fn is_declaration(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Function(..) | Stmt::Class(..))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn format_source(source: &str) -> String {
+        let mut scanner = Scanner::new(source.to_string());
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        format(source, &stmts, scanner.comments())
+    }
+
+    #[test]
+    fn test_format() {
+        let source = "// Adds.\nfun add(a,b){return a+b;}\nvar x=add(1,2); // three\n\n\nif (x>2) print \"big\"; else {\n// small\nprint x;}\nclass A{m(){}}";
+        let formatted = format_source(source);
+        assert_eq!(
+            formatted,
+            "// Adds.\nfun add(a, b) {\n    return a + b;\n}\n\nvar x = add(1, 2);  // three\n\nif (x > 2)\n    print \"big\";\nelse {\n    // small\n    print x;\n}\n\nclass A {\n    m() {}\n}\n"
+        );
+        assert_eq!(format_source(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let formatted = format_source("for (var i=0;i<3;i=i+1) print i;\nfor(x=0;;x=x+1){}");
+        assert_eq!(
+            formatted,
+            "for (var i = 0; i < 3; i = i + 1)\n    print i;\nfor (x = 0;; x = x + 1) {}\n"
+        );
+        assert_eq!(format_source(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_wrap_long_calls() {
+        let arguments = (0..30).map(|i| format!("a{}", i)).collect::<Vec<_>>();
+        let formatted = format_source(&format!("f({});", arguments.join(", ")));
+        assert!(formatted.starts_with("f(\n    a0,\n    a1,\n"));
+        assert!(formatted.ends_with("    a29\n);\n"));
+        assert_eq!(format_source(&formatted), formatted);
+    }
+}
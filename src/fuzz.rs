@@ -0,0 +1,59 @@
+//! Entry points for fuzzing. Each takes any input at all and has to turn whatever is wrong with
+//! it into diagnostics rather than panic; the cargo-fuzz targets under `fuzz/` drive them.
+
+use crate::{
+    interpreter::{environment::Environment, Interpreter},
+    parser::{ParseError, Parser},
+    scanner::Scanner,
+    stmt::Stmt,
+    token::Token,
+    Status,
+};
+use std::{cell::RefCell, rc::Rc};
+
+pub fn fuzz_scan(source: &str) -> Vec<Token> {
+    Scanner::new(source.to_string()).scan_tokens()
+}
+
+pub fn fuzz_parse(source: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    Parser::new(fuzz_scan(source)).parse()
+}
+
+/// Runs `source` in a fresh, seeded interpreter that can't read stdin and stops after `fuel`
+/// statements, discarding everything it prints.
+pub fn fuzz_interpret(source: &str, fuel: usize) -> Status {
+    let sink = Rc::new(RefCell::new(std::io::sink()));
+    let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+        .with_stdout(sink.clone())
+        .with_stderr(sink)
+        .with_stdin(Rc::new(RefCell::new(std::io::empty())));
+    interpreter.set_fuel(Some(fuel));
+    interpreter.set_seed(0);
+    crate::run(source.to_string(), &mut interpreter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_panics() {
+        let inputs = [
+            "print \"héllo\"; var ü = 1; print ü;",
+            "«»",
+            "\"unterminated",
+            "/* unterminated",
+            "while (true) {}",
+            "fun f() { f(); } f();",
+            "break",
+            "1 +",
+            "print 1.;",
+        ];
+        for input in inputs {
+            fuzz_scan(input);
+            let _ = fuzz_parse(input);
+            fuzz_interpret(input, 1000);
+        }
+        assert_eq!(fuzz_interpret("while (true) {}", 100), Status::RuntimeError);
+    }
+}
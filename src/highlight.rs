@@ -0,0 +1,253 @@
+//! Syntax highlighting: classifies the source text behind the scanner's tokens, then renders it
+//! with ANSI colors or as HTML.
+
+use crate::scanner::Scanner;
+use crate::token::TokenType;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Keyword,
+    /// `true`, `false` and `nil`.
+    Constant,
+    Number,
+    String,
+    Comment,
+    Identifier,
+    Punctuation,
+}
+
+impl Class {
+    /// The name used for the class in HTML, e.g. `lox-keyword`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Keyword => "keyword",
+            Class::Constant => "constant",
+            Class::Number => "number",
+            Class::String => "string",
+            Class::Comment => "comment",
+            Class::Identifier => "identifier",
+            Class::Punctuation => "punctuation",
+        }
+    }
+
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            Class::Keyword => Some("1;35"),
+            Class::Constant | Class::Number => Some("36"),
+            Class::String => Some("32"),
+            Class::Comment => Some("90"),
+            Class::Identifier | Class::Punctuation => None,
+        }
+    }
+}
+
+fn class(token_type: TokenType) -> Class {
+    use TokenType::*;
+    match token_type {
+        AND | BREAK | CLASS | CONTINUE | ELSE | FOR | FUN | IF | IN | OR | PRINT | RETURN
+        | SUPER | THIS | VAR | WHILE | WITH => Class::Keyword,
+        TRUE | FALSE | NIL => Class::Constant,
+        NUMBER => Class::Number,
+        STRING => Class::String,
+        IDENTIFIER => Class::Identifier,
+        COMMENT => Class::Comment,
+        _ => Class::Punctuation,
+    }
+}
+
+/// The byte ranges of everything in `source` worth coloring, in order. Whitespace and
+/// characters the scanner rejected are left out; an unterminated string or comment runs to
+/// the end.
+pub fn classify(source: &str) -> Vec<(Range<usize>, Class)> {
+    let mut scanner = Scanner::new(source.to_string()).with_comments();
+    let tokens = scanner.scan_tokens();
+    let mut spans = tokens
+        .iter()
+        .filter(|token| token.token_type != TokenType::EOF)
+        .map(|token| (token.start..token.end, class(token.token_type)))
+        .collect::<Vec<_>>();
+    if scanner.unterminated() {
+        // Only the last thing in the source can run off the end of it.
+        let end = spans.last().map_or(0, |(range, _)| range.end);
+        let start = source[end..]
+            .find(|c: char| !c.is_whitespace())
+            .map_or(source.len(), |start| end + start);
+        let rest = &source[start..];
+        if rest.starts_with('"') {
+            spans.push((start..source.len(), Class::String));
+        } else if rest.starts_with("/*") {
+            spans.push((start..source.len(), Class::Comment));
+        }
+    }
+    spans
+}
+
+/// `source` with ANSI color escapes around keywords, literals and comments.
+pub fn ansi(source: &str) -> String {
+    ansi_with_brackets(source, None)
+}
+
+/// Like `ansi`, with the bracket pair at `brackets` (see `matching_bracket`) picked out.
+pub fn ansi_with_brackets(source: &str, brackets: Option<(usize, usize)>) -> String {
+    let matched = |start| brackets.is_some_and(|(a, b)| start == a || start == b);
+    render(source, str::to_string, |range, class, text| {
+        let code = if matched(range.start) {
+            Some("1;34")
+        } else {
+            class.ansi()
+        };
+        match code {
+            Some(code) => format!("\x1b[{}m{}\x1b[0m", code, text),
+            None => text.to_string(),
+        }
+    })
+}
+
+/// The offsets of the bracket at `cursor`, or else just before it, and of the bracket that
+/// pairs with it, if there is one. Brackets in strings and comments don't count.
+pub fn matching_bracket(source: &str, cursor: usize) -> Option<(usize, usize)> {
+    use TokenType::{LEFT_BRACE, LEFT_PAREN, RIGHT_BRACE, RIGHT_PAREN};
+    let tokens = Scanner::new(source.to_string()).scan_tokens();
+    let is_bracket = |token_type| {
+        matches!(
+            token_type,
+            LEFT_PAREN | RIGHT_PAREN | LEFT_BRACE | RIGHT_BRACE
+        )
+    };
+    let at = tokens
+        .iter()
+        .position(|token| token.start == cursor && is_bracket(token.token_type))
+        .or_else(|| {
+            tokens
+                .iter()
+                .position(|token| token.end == cursor && is_bracket(token.token_type))
+        })?;
+    let (open, close) = match tokens[at].token_type {
+        LEFT_PAREN | RIGHT_PAREN => (LEFT_PAREN, RIGHT_PAREN),
+        _ => (LEFT_BRACE, RIGHT_BRACE),
+    };
+    let forward = tokens[at].token_type == open;
+    let mut candidates: Box<dyn Iterator<Item = _>> = if forward {
+        Box::new(tokens[at + 1..].iter())
+    } else {
+        Box::new(tokens[..at].iter().rev())
+    };
+    let mut depth = 0;
+    let partner = candidates.find(|token| {
+        let (inward, outward) = if forward {
+            (open, close)
+        } else {
+            (close, open)
+        };
+        if token.token_type == inward {
+            depth += 1;
+        } else if token.token_type == outward {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+    Some((tokens[at].start, partner.start))
+}
+
+/// `source` as an HTML `<pre>` block, with every classified span in a `lox-<class>` span.
+pub fn html(source: &str) -> String {
+    let body = render(source, escape_html, |_, class, text| {
+        format!(
+            "<span class=\"lox-{}\">{}</span>",
+            class.name(),
+            escape_html(text)
+        )
+    });
+    format!("<pre class=\"lox\"><code>{}</code></pre>\n", body)
+}
+
+/// Joins the spans of `source` rendered with `paint` and the text between them with `plain`.
+fn render(
+    source: &str,
+    plain: impl Fn(&str) -> String,
+    paint: impl Fn(Range<usize>, Class, &str) -> String,
+) -> String {
+    let mut out = String::new();
+    let mut end = 0;
+    for (range, class) in classify(source) {
+        out.push_str(&plain(&source[end..range.start]));
+        out.push_str(&paint(range.clone(), class, &source[range.clone()]));
+        end = range.end;
+    }
+    out.push_str(&plain(&source[end..]));
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let source = "var s = \"a b\"; // note\nif (x < 1.5) print nil; /* a /* b */ c */ /* end";
+        let classes = classify(source)
+            .into_iter()
+            .map(|(range, class)| (&source[range], class))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            classes,
+            vec![
+                ("var", Class::Keyword),
+                ("s", Class::Identifier),
+                ("=", Class::Punctuation),
+                ("\"a b\"", Class::String),
+                (";", Class::Punctuation),
+                ("// note", Class::Comment),
+                ("if", Class::Keyword),
+                ("(", Class::Punctuation),
+                ("x", Class::Identifier),
+                ("<", Class::Punctuation),
+                ("1.5", Class::Number),
+                (")", Class::Punctuation),
+                ("print", Class::Keyword),
+                ("nil", Class::Constant),
+                (";", Class::Punctuation),
+                ("/* a /* b */ c */", Class::Comment),
+                ("/* end", Class::Comment),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let source = "if (f(\")\")) { print 1; }";
+        assert_eq!(matching_bracket(source, 3), Some((3, 10)));
+        // Just past a bracket counts too.
+        assert_eq!(matching_bracket(source, 11), Some((10, 3)));
+        assert_eq!(matching_bracket(source, 5), Some((5, 9)));
+        assert_eq!(matching_bracket(source, 12), Some((12, 23)));
+        assert_eq!(matching_bracket(source, 7), None);
+        assert_eq!(matching_bracket("fun f() {", 8), None);
+        assert_eq!(
+            ansi_with_brackets("(1)", Some((0, 2))),
+            "\x1b[1;34m(\x1b[0m\x1b[36m1\x1b[0m\x1b[1;34m)\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            html("print a<b; @"),
+            "<pre class=\"lox\"><code><span class=\"lox-keyword\">print</span> \
+             <span class=\"lox-identifier\">a</span><span class=\"lox-punctuation\">&lt;</span>\
+             <span class=\"lox-identifier\">b</span><span class=\"lox-punctuation\">;</span> @\
+             </code></pre>\n"
+        );
+    }
+}
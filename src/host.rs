@@ -0,0 +1,39 @@
+use crate::{
+    expr::Value,
+    primitive::{Class, Instance, NativeFunction, Primitive},
+    token::{Token, TokenType},
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// A Rust type that can be handed to scripts as an instance of a Lox class.
+///
+/// Usually implemented with `#[derive(LoxClass)]`, which exposes each field as a getter
+/// (`point.x()`) and setter (`point.set_x(3)`) and any methods listed in
+/// `#[lox(methods(name = arity, ...))]`. Every member reads and writes the shared
+/// `Rc<RefCell<Self>>`, so changes made by the script are visible to the host and vice versa.
+pub trait LoxClass: Sized + 'static {
+    fn class_name() -> &'static str;
+
+    fn members(this: &Rc<RefCell<Self>>) -> Vec<NativeFunction>;
+
+    fn into_lox(self) -> (Value, Rc<RefCell<Self>>) {
+        let this = Rc::new(RefCell::new(self));
+        let name = Token::new(TokenType::IDENTIFIER, Self::class_name().to_string(), 0);
+        let instance = Instance::new(Class::new(name.clone(), Vec::new()));
+        for member in Self::members(&this) {
+            let token = Token::new(TokenType::IDENTIFIER, member.name.clone(), 0);
+            instance.fields.borrow_mut().insert(
+                member.name.clone(),
+                Value {
+                    primitive: Primitive::Native(member),
+                    token,
+                },
+            );
+        }
+        let value = Value {
+            primitive: Primitive::Instance(instance),
+            token: name,
+        };
+        (value, this)
+    }
+}
@@ -1,42 +1,196 @@
-use crate::expr::{Expr, Value};
-use crate::primitive::{Callable, Class, Instance, LoxCallable, Primitive};
-use crate::stmt::Stmt;
+use crate::coverage::Coverage;
+use crate::expr::{self, Expr, Value};
+use crate::metrics::Metrics;
+use crate::primitive::{format_number, Callable, Class, Instance, LoxCallable, Primitive};
+use crate::session::Session;
+use crate::stmt::{self, Stmt};
 use crate::token::{Token, TokenType};
 use core::fmt::Display;
 use environment::Environment;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::io::{BufRead, Write};
+use std::ops::Range;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How many Lox calls may be active at once before the program is stopped, by default.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_CALL_DEPTH: usize = 1000;
+/// wasm32 has a fixed stack of its own that the interpreter can't add to.
+#[cfg(target_arch = "wasm32")]
+const MAX_CALL_DEPTH: usize = 100;
+/// When less stack than this is left, the interpreter carries on in a new segment of
+/// `STACK_SEGMENT` bytes rather than overflow whatever thread it was called on. A Lox call can
+/// take a few hundred kilobytes of stack in a debug build.
+#[cfg(not(target_arch = "wasm32"))]
+const STACK_RED_ZONE: usize = 1024 * 1024;
+#[cfg(not(target_arch = "wasm32"))]
+const STACK_SEGMENT: usize = 16 * 1024 * 1024;
 
 pub mod environment;
+pub mod hooks;
+pub mod native;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;
 
 pub struct Interpreter {
     pub environment: Rc<RefCell<Environment>>,
     pub locals: HashMap<Expr, usize>,
+    pub stdout: Rc<RefCell<dyn Write>>,
+    /// Where errors are reported and counted.
+    pub session: Session,
+    pub stdin: Rc<RefCell<dyn BufRead>>,
+    interrupted: Arc<AtomicBool>,
+    trace: Trace,
+    trace_output: Rc<RefCell<dyn Write>>,
+    /// How many Lox function calls deep execution is, for indenting the trace.
+    depth: usize,
+    max_call_depth: usize,
+    /// Lines run so far, when coverage is being collected.
+    pub coverage: Option<Coverage>,
+    /// Look up variables the resolver didn't place through the current scope chain rather
+    /// than only among globals.
+    dynamic_scope: bool,
+    /// How many more statements may run, when limited.
+    fuel: Option<usize>,
+    /// When the program has to be done by, and the limit that was set, when limited.
+    deadline: Option<(Instant, Duration)>,
+    timed_out: bool,
+    division_by_zero: DivisionByZero,
+    /// How many decimal places printed numbers are rounded to, if any.
+    precision: Option<usize>,
+    hooks: hooks::Hooks,
+    rng: native::Rng,
+    /// Whether a seed was set, making `random` and `clock` repeat from run to run.
+    seeded: bool,
+    pub(crate) metrics: Metrics,
+    /// The Lox calls running right now, outermost first.
+    frames: Vec<Frame>,
+}
+
+/// What dividing by zero does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DivisionByZero {
+    /// Stop the program with an error.
+    Error,
+    /// Give infinity or NaN, as floating point division does and as reference Lox does.
+    #[default]
+    Ieee,
+}
+
+/// How much of a program's execution to log as it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trace {
+    Off,
+    /// Each statement before it runs, plus calls and what they return.
+    Statements,
+    /// Statements, and every expression along with its value once evaluated.
+    Expressions,
+}
+
+/// What went wrong, broadly. Each kind has a stable code that's shown with the message, so
+/// callers can match on the category rather than the wording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    RuntimeError,
+    TypeError,
+    NameError,
+    ArityError,
+    DivisionByZero,
+    PropertyError,
+    IndexError,
+    IoError,
+    NativeError,
+    Interrupted,
+    /// Found by the resolver before anything ran.
+    ResolveError,
+    /// Not an error: a `break` on its way out to the loop it ends.
+    Break,
+    /// Not an error: a `continue` on its way out to the loop it moves on.
+    Continue,
+}
+
+impl ErrorKind {
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::RuntimeError => "E100",
+            ErrorKind::TypeError => "E101",
+            ErrorKind::NameError => "E102",
+            ErrorKind::ArityError => "E103",
+            ErrorKind::DivisionByZero => "E104",
+            ErrorKind::PropertyError => "E105",
+            ErrorKind::IndexError => "E106",
+            ErrorKind::IoError => "E107",
+            ErrorKind::NativeError => "E108",
+            ErrorKind::Interrupted => "E109",
+            ErrorKind::ResolveError => "E200",
+            ErrorKind::Break => "E300",
+            ErrorKind::Continue => "E301",
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct InterpretError {
+    pub kind: ErrorKind,
     pub message: String,
-    pub token: Token,
-    pub value: Option<Value>,
+    pub token: Box<Token>,
+    pub value: Option<Box<Value>>,
+    /// The calls the error unwound through, innermost first.
+    pub trace: Vec<Frame>,
+    /// The byte range of the whole expression at fault, when there's more of it than the token.
+    pub span: Option<Range<usize>>,
+}
+
+/// A call the error passed through: the function that was running and the line it was
+/// called from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub line: usize,
 }
 
 impl InterpretError {
-    pub fn new(message: String, token: Token) -> Self {
+    pub fn new(kind: ErrorKind, message: String, token: Token) -> Self {
         Self {
+            kind,
             message,
-            token,
+            token: Box::new(token),
             value: None,
+            trace: Vec::new(),
+            span: None,
+        }
+    }
+
+    fn called_from(mut self, function: String, paren: &Token) -> Self {
+        // Returns travel as errors too, but they don't leave the function they return from.
+        if self.value.is_none() {
+            self.trace.push(Frame {
+                function,
+                line: paren.line,
+            });
         }
+        self
+    }
+
+    /// Points the error at `span`, unless an inner expression already claimed it.
+    pub(crate) fn within(mut self, span: Range<usize>) -> Self {
+        self.span.get_or_insert(span);
+        self
     }
 
     fn with_value(message: String, token: Token, value: Value) -> Self {
         Self {
+            kind: ErrorKind::RuntimeError,
             message,
-            token,
-            value: Some(value),
+            token: Box::new(token),
+            value: Some(Box::new(value)),
+            trace: Vec::new(),
+            span: None,
         }
     }
 }
@@ -49,42 +203,227 @@ impl Display for InterpretError {
 
 impl Interpreter {
     pub fn new(environment: Rc<RefCell<Environment>>) -> Self {
-        Self {
+        let mut interpreter = Self {
             environment,
             locals: HashMap::new(),
+            stdout: Rc::new(RefCell::new(std::io::stdout())),
+            session: Session::default(),
+            stdin: Rc::new(RefCell::new(std::io::BufReader::new(std::io::stdin()))),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            trace: Trace::Off,
+            trace_output: Rc::new(RefCell::new(std::io::stderr())),
+            depth: 0,
+            max_call_depth: MAX_CALL_DEPTH,
+            dynamic_scope: false,
+            coverage: None,
+            fuel: None,
+            deadline: None,
+            timed_out: false,
+            division_by_zero: DivisionByZero::default(),
+            precision: None,
+            hooks: hooks::Hooks::default(),
+            rng: native::Rng::from_entropy(),
+            seeded: false,
+            metrics: Metrics::default(),
+            frames: Vec::new(),
+        };
+        native::define_natives(&mut interpreter);
+        interpreter
+    }
+
+    /// Sends `print` output to `stdout` instead of the process's standard output.
+    pub fn with_stdout(mut self, stdout: Rc<RefCell<dyn Write>>) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    /// Sends diagnostics to `stderr` instead of the process's standard error.
+    pub fn with_stderr(mut self, stderr: Rc<RefCell<dyn Write>>) -> Self {
+        self.session = Session::new(stderr);
+        self
+    }
+
+    /// Reads `input()` and REPL lines from `stdin` instead of the process's standard input.
+    pub fn with_stdin(mut self, stdin: Rc<RefCell<dyn BufRead>>) -> Self {
+        self.stdin = stdin;
+        self
+    }
+
+    /// What the program has made the interpreter do so far.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Logs execution to `output` as it happens, indented by call depth.
+    pub fn set_trace(&mut self, trace: Trace, output: Rc<RefCell<dyn Write>>) {
+        self.trace = trace;
+        self.trace_output = output;
+    }
+
+    fn trace(&self, line: &str) {
+        let _ = writeln!(
+            self.trace_output.borrow_mut(),
+            "{}{}",
+            "  ".repeat(self.depth),
+            line
+        );
+    }
+
+    /// Runs `f` with the variables of the current scope visible to code compiled at the top
+    /// level, as at a `breakpoint()` prompt.
+    pub fn with_dynamic_scope<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = std::mem::replace(&mut self.dynamic_scope, true);
+        let result = f(self);
+        self.dynamic_scope = previous;
+        result
+    }
+
+    /// A flag that, once set from another thread or a signal handler, stops the running
+    /// program at its next loop iteration or call.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.interrupted.clone()
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    pub fn clear_interrupt(&self) {
+        self.interrupted.store(false, Ordering::SeqCst);
+    }
+
+    fn check_interrupt(&mut self, token: &Token) -> Result<(), InterpretError> {
+        if let Some((deadline, limit)) = self.deadline.filter(|_| !self.timed_out) {
+            if Instant::now() >= deadline {
+                // Interrupting too keeps the rest of the program from running.
+                self.interrupted.store(true, Ordering::SeqCst);
+                self.timed_out = true;
+                return Err(InterpretError::new(
+                    ErrorKind::Interrupted,
+                    format!("Execution timed out after {:?}.", limit),
+                    token.clone(),
+                ));
+            }
+        }
+        if self.is_interrupted() {
+            return Err(InterpretError::new(
+                ErrorKind::Interrupted,
+                String::from("Interrupted."),
+                token.clone(),
+            ));
         }
+        Ok(())
     }
 
-    pub fn new_with_locals(
-        environment: Rc<RefCell<Environment>>,
-        locals: HashMap<Expr, usize>,
-    ) -> Self {
-        Self {
-            environment,
-            locals,
+    /// Stops the program with an error once it has run `fuel` more statements, so that input
+    /// that loops forever still finishes. `None` lifts the limit.
+    pub fn set_fuel(&mut self, fuel: Option<usize>) {
+        self.fuel = fuel;
+    }
+
+    /// Stops the program with an error at its next loop iteration or call once `timeout` has
+    /// passed from now. `None` lifts the limit. Not available on wasm32, which has no clock.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+        self.timed_out = false;
+    }
+
+    /// Whether the program was stopped for running past its timeout.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Makes the program deterministic: `random` gives the same numbers for the same seed,
+    /// and `clock` starts at 0 and advances a microsecond per statement run rather than reading
+    /// the system time.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = native::Rng::new(seed);
+        self.seeded = true;
+    }
+
+    /// Stops the program with a stack overflow error once `depth` Lox calls are active.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    pub fn set_division_by_zero(&mut self, division_by_zero: DivisionByZero) {
+        self.division_by_zero = division_by_zero;
+    }
+
+    /// Rounds numbers that `print`, `str()` and joining to a string show to `digits` decimal
+    /// places, or lifts the limit.
+    pub fn set_precision(&mut self, digits: Option<usize>) {
+        self.precision = digits;
+    }
+
+    pub fn precision(&self) -> Option<usize> {
+        self.precision
+    }
+
+    /// Fails if `divisor` is zero and dividing by zero is an error.
+    fn check_divisor(&self, divisor: f64, operator: &Token) -> Result<(), InterpretError> {
+        if divisor == 0.0 && self.division_by_zero == DivisionByZero::Error {
+            return Err(InterpretError::new(
+                ErrorKind::DivisionByZero,
+                "Division by zero.".to_string(),
+                operator.clone(),
+            ));
         }
+        Ok(())
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
-        self.environment.borrow_mut().define(name, value);
+    fn burn_fuel(&mut self, token: &Token) -> Result<(), InterpretError> {
+        match &mut self.fuel {
+            Some(0) => Err(InterpretError::new(
+                ErrorKind::Interrupted,
+                String::from("Ran out of fuel."),
+                token.clone(),
+            )),
+            Some(fuel) => {
+                *fuel -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
     }
 
-    pub fn get_local(&mut self, expr: &Expr) -> Option<usize> {
-        let distance = self.locals.get(expr);
-        if let Some(distance) = distance {
-            return Some(*distance);
-        } else {
-            None
+    /// Reports a runtime error along with the calls it unwound through.
+    pub fn report_error(&mut self, error: &InterpretError) {
+        for hook in &mut self.hooks.error {
+            hook(error);
+        }
+        self.session.report_error(error);
+    }
+
+    pub fn report(&mut self, token: &Token, message: &str) {
+        self.session.error(&token.into(), message);
+    }
+
+    /// Forgets everything the program defined, keeping natives (including plugins and
+    /// script arguments) and the I/O streams.
+    pub fn reset(&mut self) {
+        let mut globals = Environment::global();
+        for (name, value) in self.environment.borrow().globals() {
+            if let Primitive::Native(_) = value.primitive {
+                globals.define(name, value);
+            }
         }
+        self.environment = Rc::new(RefCell::new(globals));
+        self.locals.clear();
     }
 
-    fn assign(&mut self, token: Token, value: Value) -> Result<(), InterpretError> {
-        self.environment.borrow_mut().assign(token.lexeme, value)
+    pub fn define(&mut self, name: String, value: Value) {
+        self.environment.borrow_mut().define(name, value);
+    }
+
+    pub fn get_local(&mut self, expr: &Expr) -> Option<usize> {
+        self.locals.get(expr).copied()
     }
 
     pub fn new_environment(&mut self) {
         let previous = self.environment.clone();
         self.environment = Rc::new(RefCell::new(Environment::new(previous)));
+        self.metrics.environments += 1;
     }
 
     pub fn resolve(&mut self, expr: Expr, depth: usize) {
@@ -97,25 +436,20 @@ impl Interpreter {
             self.environment
                 .borrow()
                 .get(*distance, name.lexeme.as_str())
+        } else if self.dynamic_scope {
+            self.environment.borrow().lookup(name.lexeme.as_str())
         } else {
             self.environment.borrow().get_global(name.lexeme.as_str())
         }
         .ok_or_else(|| {
             InterpretError::new(
+                ErrorKind::NameError,
                 format!("Undefined variable '{}'.", name.lexeme),
                 name.clone(),
             )
         })
     }
 
-    fn is_truthy(&self, value: &Value) -> bool {
-        match value.primitive {
-            Primitive::Nil => false,
-            Primitive::Boolean(bool) => bool,
-            _ => true,
-        }
-    }
-
     fn to_number(&self, value: Value) -> Result<f64, InterpretError> {
         match value.primitive {
             Primitive::Number(number) => Ok(number),
@@ -124,7 +458,11 @@ impl Interpreter {
             | Primitive::Nil
             | Primitive::Boolean(_)
             | Primitive::Instance(_)
-            | Primitive::Class(_) => Err(InterpretError::new(
+            | Primitive::Class(_)
+            | Primitive::Native(_)
+            | Primitive::List(_)
+            | Primitive::Map(_) => Err(InterpretError::new(
+                ErrorKind::TypeError,
                 format!("Expected number, got {}", value.primitive),
                 value.token,
             )),
@@ -132,68 +470,297 @@ impl Interpreter {
     }
 
     fn is_equal(&self, left: Value, right: Value) -> bool {
-        match (left.primitive, right.primitive) {
-            (Primitive::Nil, Primitive::Nil) => true,
-            (Primitive::Boolean(left), Primitive::Boolean(right)) => left == right,
-            (Primitive::Number(left), Primitive::Number(right)) => left == right,
-            (Primitive::String(left), Primitive::String(right)) => left == right,
-            _ => false,
+        left.primitive.equals(&right.primitive)
+    }
+
+    /// Whether `item in container`: a substring of a string, an element of a list or a key of
+    /// a map.
+    fn contains(
+        &self,
+        container: &Value,
+        item: &Value,
+        operator: &Token,
+    ) -> Result<bool, InterpretError> {
+        match (&container.primitive, &item.primitive) {
+            (Primitive::String(string), Primitive::String(item)) => Ok(string.contains(item)),
+            (Primitive::List(list), item) => Ok(list
+                .borrow()
+                .iter()
+                .any(|element| element.primitive.equals(item))),
+            (Primitive::Map(map), Primitive::String(key)) => Ok(map.borrow().contains_key(key)),
+            (Primitive::String(_) | Primitive::Map(_), _) => Err(InterpretError::new(
+                ErrorKind::TypeError,
+                format!("Expected a string before 'in', got {}.", item.primitive),
+                operator.clone(),
+            )),
+            _ => Err(InterpretError::new(
+                ErrorKind::TypeError,
+                format!(
+                    "Can only look in a string, list or map, not {}.",
+                    container.primitive
+                ),
+                operator.clone(),
+            )),
         }
     }
 }
 
 impl Interpreter {
-    pub fn interpret(&mut self, stmt: Stmt) -> Result<(), InterpretError> {
-        match stmt {
-            Stmt::Return(token, expr) => {
-                if let Some(expr) = expr {
-                    let value = self.interpret_expr(expr)?;
-                    return Err(InterpretError::with_value(
-                        "Successful return".to_string(),
-                        token,
-                        value,
+    /// Calls the global function or class `name` with already evaluated arguments, so
+    /// host applications can use scripts as hooks rather than only running them.
+    pub fn call_function(&mut self, name: &str, args: &[Value]) -> Result<Value, InterpretError> {
+        let token = Token::new(TokenType::IDENTIFIER, name.to_string(), 0);
+        let callee = self.environment.borrow().get_global(name).ok_or_else(|| {
+            InterpretError::new(
+                ErrorKind::NameError,
+                format!("Undefined variable '{}'.", name),
+                token.clone(),
+            )
+        })?;
+        self.call_value(callee, args.to_vec(), token)
+    }
+
+    /// Typed wrapper around `call_function` that converts the result into a Rust value.
+    pub fn call_function_as<R>(&mut self, name: &str, args: &[Value]) -> Result<R, InterpretError>
+    where
+        R: TryFrom<Value, Error = InterpretError>,
+    {
+        R::try_from(self.call_function(name, args)?)
+    }
+
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        paren: Token,
+    ) -> Result<Value, InterpretError> {
+        self.check_interrupt(&paren)?;
+        match callee.primitive {
+            Primitive::Callable(callable) => {
+                if arguments.len() != callable.arity {
+                    return Err(InterpretError::new(
+                        ErrorKind::ArityError,
+                        format!(
+                            "Expected {} arguments but got {}.",
+                            callable.arity,
+                            arguments.len()
+                        ),
+                        paren,
                     ));
                 }
-                Err(InterpretError::with_value(
-                    "Successful return".to_string(),
-                    token.clone(),
-                    Value {
+                if self.depth >= self.max_call_depth {
+                    return Err(InterpretError::new(
+                        ErrorKind::RuntimeError,
+                        String::from("Stack overflow."),
+                        paren,
+                    ));
+                }
+                let function = callable.name.lexeme.clone();
+                log::trace!("call {} at depth {}", function, self.depth + 1);
+                if self.trace >= Trace::Statements {
+                    let arguments = arguments
+                        .iter()
+                        .map(|argument| argument.primitive.to_string())
+                        .collect::<Vec<_>>();
+                    self.trace(&format!("call {}({})", function, arguments.join(", ")));
+                }
+                self.frames.push(Frame {
+                    function: function.clone(),
+                    line: paren.line,
+                });
+                let span = (paren.end > 0).then_some(paren.start..paren.end);
+                for hook in &mut self.hooks.call {
+                    let site = hooks::Site {
+                        line: paren.line,
+                        span: span.clone(),
+                        frames: &self.frames,
+                    };
+                    hook(&function, &arguments, &site);
+                }
+                self.depth += 1;
+                self.metrics.calls += 1;
+                self.metrics.max_depth = self.metrics.max_depth.max(self.depth);
+                let result = callable.call(self, arguments);
+                self.depth -= 1;
+                if let Ok(value) = &result {
+                    if self.trace >= Trace::Statements {
+                        self.trace(&format!("{} returned {}", function, value.primitive));
+                    }
+                    for hook in &mut self.hooks.returned {
+                        let site = hooks::Site {
+                            line: paren.line,
+                            span: span.clone(),
+                            frames: &self.frames,
+                        };
+                        hook(&function, value, &site);
+                    }
+                }
+                self.frames.pop();
+                result.map_err(|e| e.called_from(function, &paren))
+            }
+            Primitive::Native(native) => {
+                if arguments.len() != native.arity {
+                    return Err(InterpretError::new(
+                        ErrorKind::ArityError,
+                        format!(
+                            "Expected {} arguments but got {}.",
+                            native.arity,
+                            arguments.len()
+                        ),
+                        paren,
+                    ));
+                }
+                self.metrics.calls += 1;
+                // Natives have no source location of their own, so point at the call site.
+                native.call(self, arguments).map_err(|mut e| {
+                    if e.token.line == 0 {
+                        *e.token = paren.clone();
+                        e
+                    } else {
+                        e.called_from(native.name.clone(), &paren)
+                    }
+                })
+            }
+            Primitive::Class(class) => {
+                if arguments.len() != class.arity() {
+                    return Err(InterpretError::new(
+                        ErrorKind::ArityError,
+                        format!(
+                            "Expected {} arguments but got {}.",
+                            class.arity(),
+                            arguments.len()
+                        ),
+                        paren,
+                    ));
+                }
+                self.metrics.allocations += 1;
+                let instance = Instance::new(class.clone());
+                // `init` runs like any other call, so it shows up in traces and hooks.
+                if let Some(init) = class.find_method("init") {
+                    let init = Value {
+                        primitive: Primitive::Callable(init.bind(instance.clone())),
+                        token: paren.clone(),
+                    };
+                    self.call_value(init, arguments, paren.clone())?;
+                }
+                Ok(Value {
+                    primitive: Primitive::Instance(instance),
+                    token: paren,
+                })
+            }
+            _ => Err(InterpretError::new(
+                ErrorKind::TypeError,
+                "Can only call functions and classes.".to_string(),
+                paren,
+            )),
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn interpret(&mut self, stmt: Stmt) -> Result<(), InterpretError> {
+        with_stack(|| self.execute(stmt))
+    }
+
+    fn execute(&mut self, stmt: Stmt) -> Result<(), InterpretError> {
+        self.metrics.statements += 1;
+        if let (Some(coverage), Some(line)) = (&mut self.coverage, stmt::line(&stmt)) {
+            coverage.hit(line);
+        }
+        if self.fuel.is_some() {
+            let line = stmt::line(&stmt).unwrap_or_default();
+            self.burn_fuel(&Token::new(TokenType::EOF, String::new(), line))?;
+        }
+        // Stop between statements too, not just at calls and loop iterations.
+        if self.is_interrupted() {
+            let line = stmt::line(&stmt).unwrap_or_default();
+            let token = Token::new(TokenType::EOF, String::new(), line);
+            let span = stmt::span(&stmt);
+            return self.check_interrupt(&token).map_err(|e| match span {
+                Some(span) => e.within(span),
+                None => e,
+            });
+        }
+        if self.trace >= Trace::Statements {
+            if let Some(line) = trace_stmt(&stmt) {
+                self.trace(&line);
+            }
+        }
+        if !self.hooks.statement.is_empty() {
+            let site = hooks::Site {
+                line: stmt::line(&stmt).unwrap_or_default(),
+                span: stmt::span(&stmt),
+                frames: &self.frames,
+            };
+            for hook in &mut self.hooks.statement {
+                hook(&stmt, &site);
+            }
+        }
+        match stmt {
+            Stmt::Return(token, mut values) => {
+                let value = match values.len() {
+                    0 => Value {
                         primitive: Primitive::Nil,
-                        token: Token {
-                            token_type: TokenType::NIL,
-                            lexeme: "nil".to_string(),
-                            line: token.line,
-                        },
+                        token: Token::new(TokenType::NIL, "nil".to_string(), token.line),
                     },
+                    1 => self.interpret_expr(values.remove(0))?,
+                    // Several values go back as a list, for the caller to unpack.
+                    _ => {
+                        let values = values
+                            .into_iter()
+                            .map(|value| self.interpret_expr(value))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        self.metrics.allocations += 1;
+                        Value {
+                            primitive: Primitive::List(Rc::new(RefCell::new(values))),
+                            token: token.clone(),
+                        }
+                    }
+                };
+                Err(InterpretError::with_value(
+                    "Successful return".to_string(),
+                    token,
+                    value,
                 ))
             }
             Stmt::Expr(expr) => {
                 self.interpret_expr(expr)?;
                 Ok(())
             }
-            Stmt::Print(expr) => {
-                let value = self.interpret_expr(expr)?;
-                println!("{}", value.primitive);
-                Ok(())
+            Stmt::Print(values) => {
+                let line = values.first().map(expr::line).unwrap_or_default();
+                let mut printed = Vec::new();
+                for value in values {
+                    let value = self.interpret_expr(value)?;
+                    printed.push(value.primitive.display(self.precision).to_string());
+                }
+                writeln!(self.stdout.borrow_mut(), "{}", printed.join(" ")).map_err(|e| {
+                    let token = Token::new(TokenType::EOF, String::new(), line);
+                    InterpretError::new(ErrorKind::IoError, e.to_string(), token)
+                })
             }
-            Stmt::Var(token, initializer) => {
+            Stmt::Var(token, initializer, _) => {
                 let value = match initializer {
                     Some(expr) => self.interpret_expr(expr)?,
                     None => Value {
                         primitive: Primitive::Nil,
-                        token: Token {
-                            token_type: TokenType::NIL,
-                            lexeme: "nil".to_string(),
-                            line: token.line,
-                        },
+                        token: Token::new(TokenType::NIL, "nil".to_string(), token.line),
                     },
                 };
                 self.define(token.lexeme, value);
                 Ok(())
             }
-            Stmt::Assign(token, expr) => {
-                let value = self.interpret_expr(expr)?;
-                self.assign(token, value)
+            Stmt::Unpack(keyword, names, values) => {
+                let values = self.unpack(&names, values)?;
+                for (name, value) in names.into_iter().zip(values) {
+                    if keyword.is_some() {
+                        self.define(name.lexeme, value);
+                    } else {
+                        let key = Expr::Variable(expr::Variable { name: name.clone() });
+                        self.assign(name, &key, value)?;
+                    }
+                }
+                Ok(())
             }
             Stmt::Block(stmts) => {
                 let previous = self.environment.clone();
@@ -208,25 +775,56 @@ impl Interpreter {
                 self.environment = previous;
                 Ok(())
             }
+            Stmt::With(keyword, resource, name, body) => {
+                let resource = self.interpret_expr(resource)?;
+                let previous = self.environment.clone();
+                self.new_environment();
+                self.define(name.lexeme, resource.clone());
+                let result = self.interpret_block(body);
+                self.environment = previous;
+                // An error or return from the body still closes the resource, and wins over
+                // an error from closing it.
+                let closed = self.close(resource, keyword);
+                result.and(closed)
+            }
             Stmt::If(condition, then_branch, else_branch) => {
                 let condition = self.interpret_expr(condition)?;
-                if condition.primitive == Primitive::Boolean(true) {
+                if condition.primitive.is_truthy() {
                     self.interpret(*then_branch)?;
                 } else if let Some(else_branch) = else_branch {
                     self.interpret(*else_branch)?;
                 }
                 Ok(())
             }
-            Stmt::While(condition, body) => {
-                while self.interpret_expr(condition.clone())?.primitive == Primitive::Boolean(true)
-                {
-                    self.interpret(*body.clone())?;
+            Stmt::While(condition, body, increment) => loop {
+                match &condition {
+                    Some(condition) => {
+                        let value = self.interpret_expr(condition.clone())?;
+                        if !value.primitive.is_truthy() {
+                            return Ok(());
+                        }
+                        self.check_interrupt(&value.token)?;
+                    }
+                    None => {
+                        let line = stmt::line(&body).unwrap_or_default();
+                        self.check_interrupt(&Token::new(TokenType::EOF, String::new(), line))?;
+                    }
                 }
-                Ok(())
-            }
-            Stmt::Function(token, parameters, body) => {
+                match self.interpret(*body.clone()) {
+                    Err(e) if e.kind == ErrorKind::Break => return Ok(()),
+                    // The increment still runs, as it would at the end of the body.
+                    Err(e) if e.kind == ErrorKind::Continue => {}
+                    result => result?,
+                }
+                if let Some(increment) = &increment {
+                    self.interpret_expr(increment.clone())?;
+                }
+            },
+            Stmt::Function(token, parameters, body, signature) => {
                 let callable =
-                    Callable::new(token.clone(), parameters, body, self.environment.clone());
+                    Callable::new(token.clone(), parameters, body, self.environment.clone())
+                        .with_signature(signature);
+                self.metrics.allocations += 1;
                 let value = Value {
                     primitive: Primitive::Callable(callable),
                     token: token.clone(),
@@ -234,8 +832,52 @@ impl Interpreter {
                 self.define(token.lexeme, value);
                 Ok(())
             }
-            Stmt::Class(name, methods) => {
-                let class = Class::new(name.clone(), methods);
+            Stmt::Class(name, superclass, methods, doc) => {
+                let superclass = match superclass {
+                    Some(superclass) => {
+                        let expr = Expr::Variable(superclass.clone());
+                        match self.look_up_var(&superclass.name, &expr)?.primitive {
+                            Primitive::Class(class) => Some(class),
+                            _ => {
+                                return Err(InterpretError::new(
+                                    ErrorKind::TypeError,
+                                    String::from("Superclass must be a class."),
+                                    superclass.name,
+                                ))
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                // Methods find `super` in a scope of its own, as the resolver expects.
+                let previous = self.environment.clone();
+                if let Some(superclass) = &superclass {
+                    self.new_environment();
+                    let value = Value {
+                        primitive: Primitive::Class(superclass.clone()),
+                        token: name.clone(),
+                    };
+                    self.define(String::from("super"), value);
+                }
+                let methods = methods
+                    .into_iter()
+                    .filter_map(|method| match method {
+                        Stmt::Function(name, params, body, signature) => {
+                            let initializer = name.lexeme == "init";
+                            Some(
+                                Callable::new(name, params, body, self.environment.clone())
+                                    .with_signature(signature)
+                                    .with_initializer(initializer),
+                            )
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                self.environment = previous;
+                let class = Class::new(name.clone(), methods)
+                    .with_superclass(superclass)
+                    .with_doc(doc);
+                self.metrics.allocations += 1;
                 let value = Value {
                     primitive: Primitive::Class(class.clone()),
                     token: name,
@@ -243,7 +885,17 @@ impl Interpreter {
                 self.define(class.name.lexeme, value);
                 Ok(())
             }
-            Stmt::Break => todo!(),
+            // Travels out like an error until the loop catches it.
+            Stmt::Break(keyword) => Err(InterpretError::new(
+                ErrorKind::Break,
+                String::from("Can't use 'break' outside of a loop."),
+                keyword,
+            )),
+            Stmt::Continue(keyword) => Err(InterpretError::new(
+                ErrorKind::Continue,
+                String::from("Can't use 'continue' outside of a loop."),
+                keyword,
+            )),
         }
     }
 
@@ -260,13 +912,28 @@ impl Interpreter {
     }
 
     pub fn interpret_expr(&mut self, expr: Expr) -> Result<Value, InterpretError> {
+        with_stack(|| self.trace_expr(expr))
+    }
+
+    fn trace_expr(&mut self, expr: Expr) -> Result<Value, InterpretError> {
+        if self.trace < Trace::Expressions {
+            return self.evaluate(expr);
+        }
+        let printed = expr::print(expr.clone());
+        let value = self.evaluate(expr)?;
+        self.trace(&format!("{} => {}", printed, value.primitive));
+        Ok(value)
+    }
+
+    fn evaluate(&mut self, expr: Expr) -> Result<Value, InterpretError> {
         match expr.clone() {
             Expr::Get(get_expr) => {
                 let object = self.interpret_expr(*get_expr.expr)?;
-                println!("Object we're getting: {:?}", object);
+                log::trace!("get {} on {}", get_expr.name.lexeme, object.primitive);
                 match object.primitive {
                     Primitive::Instance(instance) => instance.get(get_expr.name.clone()),
                     _ => Err(InterpretError::new(
+                        ErrorKind::PropertyError,
                         "Only instances have properties.".to_string(),
                         get_expr.name,
                     )),
@@ -275,14 +942,14 @@ impl Interpreter {
             Expr::Set(set_expr) => {
                 let object = self.interpret_expr(*set_expr.expr)?;
                 match object.primitive {
-                    Primitive::Instance(mut instance) => {
+                    Primitive::Instance(instance) => {
                         let value = self.interpret_expr(*set_expr.value)?;
-                        println!("Instace fields before: {:?}", instance.fields);
+                        log::trace!("set {} to {}", set_expr.name.lexeme, value.primitive);
                         instance.set(set_expr.name.clone(), value.clone());
-                        println!("Instance fields after: {:?}", instance.fields);
-                        return Ok(value);
+                        Ok(value)
                     }
                     _ => Err(InterpretError::new(
+                        ErrorKind::PropertyError,
                         "Only instances have fields.".to_string(),
                         set_expr.name,
                     )),
@@ -294,168 +961,21 @@ impl Interpreter {
                 for argument in call.arguments {
                     arguments.push(self.interpret_expr(argument)?);
                 }
-                match callee.primitive {
-                    Primitive::Callable(callable) => {
-                        if arguments.len() != callable.arity {
-                            return Err(InterpretError::new(
-                                format!(
-                                    "Expected {} arguments but got {}.",
-                                    callable.arity,
-                                    arguments.len()
-                                ),
-                                call.paren,
-                            ));
-                        }
-                        callable.call(arguments, self.locals.clone())
-                    }
-                    Primitive::Class(class) => {
-                        if arguments.len() != 0 {
-                            return Err(InterpretError::new(
-                                format!("Expected 0 arguments but got {}.", arguments.len()),
-                                call.paren,
-                            ));
+                // Only errors about the call itself cover it, not ones from inside the callee.
+                self.call_value(callee, arguments, call.paren.clone())
+                    .map_err(|e| {
+                        if e.trace.is_empty() && *e.token == call.paren {
+                            e.within(expr::span(&expr))
+                        } else {
+                            e
                         }
-                        Ok(Value {
-                            primitive: Primitive::Instance(Instance::new(class)),
-                            token: call.paren,
-                        })
-                    }
-                    _ => Err(InterpretError::new(
-                        "Can only call functions and classes.".to_string(),
-                        call.paren,
-                    )),
-                }
+                    })
             }
             Expr::Binary(binary) => {
                 let left = self.interpret_expr(*binary.left)?;
                 let right = self.interpret_expr(*binary.right)?;
-                match binary.operator.lexeme.as_str() {
-                    "-" => {
-                        if let (Primitive::Number(left), Primitive::Number(right)) =
-                            (&left.primitive, &right.primitive)
-                        {
-                            Ok(Value {
-                                primitive: Primitive::Number(left - right),
-                                token: binary.operator,
-                            })
-                        } else {
-                            Err(InterpretError::new(
-                                format!(
-                                    "Operands must be two numbers: {} - {}",
-                                    left.token.lexeme, right.token.lexeme
-                                ),
-                                binary.operator,
-                            ))
-                        }
-                    }
-                    "*" => {
-                        if let (Primitive::Number(left), Primitive::Number(right)) =
-                            (&left.primitive, &right.primitive)
-                        {
-                            Ok(Value {
-                                primitive: Primitive::Number(left * right),
-                                token: binary.operator,
-                            })
-                        } else {
-                            Err(InterpretError::new(
-                                format!(
-                                    "Operands must be two numbers: {} * {}",
-                                    left.token.lexeme, right.token.lexeme
-                                ),
-                                binary.operator,
-                            ))
-                        }
-                    }
-                    "/" => {
-                        if let (Primitive::Number(left), Primitive::Number(right)) =
-                            (&left.primitive, &right.primitive)
-                        {
-                            if right == &0.0 {
-                                Err(InterpretError::new(
-                                    "Division by zero.".to_string(),
-                                    binary.operator,
-                                ))
-                            } else {
-                                Ok(Value {
-                                    primitive: Primitive::Number(left / right),
-                                    token: binary.operator,
-                                })
-                            }
-                        } else {
-                            Err(InterpretError::new(
-                                format!(
-                                    "Operands must be two numbers: {} / {}",
-                                    left.token.lexeme, right.token.lexeme
-                                ),
-                                binary.operator,
-                            ))
-                        }
-                    }
-                    "+" => match (&left.primitive, &right.primitive) {
-                        (Primitive::Number(left), Primitive::Number(right)) => Ok(Value {
-                            primitive: Primitive::Number(left + right),
-                            token: binary.operator,
-                        }),
-                        (Primitive::String(left), Primitive::String(right)) => Ok(Value {
-                            primitive: Primitive::String(format!("{}{}", left, right)),
-                            token: binary.operator,
-                        }),
-                        (Primitive::String(left), Primitive::Number(right)) => Ok(Value {
-                            primitive: Primitive::String(format!("{}{}", left, right)),
-                            token: binary.operator,
-                        }),
-                        (Primitive::Number(left), Primitive::String(right)) => Ok(Value {
-                            primitive: Primitive::String(format!("{}{}", left, right)),
-                            token: binary.operator,
-                        }),
-                        _ => Err(InterpretError::new(
-                            format!(
-                                "Operands must be two numbers or two strings: {} + {}",
-                                left.token.lexeme, right.token.lexeme
-                            ),
-                            binary.operator,
-                        )),
-                    },
-                    ">" => Ok(Value {
-                        primitive: Primitive::Boolean(
-                            self.to_number(left)? > self.to_number(right)?,
-                        ),
-                        token: binary.operator,
-                    }),
-                    ">=" => Ok(Value {
-                        primitive: Primitive::Boolean(
-                            self.to_number(left)? >= self.to_number(right)?,
-                        ),
-                        token: binary.operator,
-                    }),
-                    "<" => Ok(Value {
-                        primitive: Primitive::Boolean(
-                            self.to_number(left)? < self.to_number(right)?,
-                        ),
-                        token: binary.operator,
-                    }),
-                    "<=" => Ok(Value {
-                        primitive: Primitive::Boolean(
-                            self.to_number(left)? <= self.to_number(right)?,
-                        ),
-                        token: binary.operator,
-                    }),
-                    "!=" => Ok(Value {
-                        primitive: Primitive::Boolean(!self.is_equal(left, right)),
-                        token: binary.operator,
-                    }),
-                    "==" => Ok(Value {
-                        primitive: Primitive::Boolean(self.is_equal(left, right)),
-                        token: binary.operator,
-                    }),
-                    _ => Err(InterpretError::new(
-                        format!(
-                            "Operands must be two numbers or two strings: {} + {}",
-                            left.token.lexeme, right.token.lexeme
-                        ),
-                        binary.operator,
-                    )),
-                }
+                self.binary(binary.operator, left, right)
+                    .map_err(|e| e.within(expr::span(&expr)))
             }
             Expr::Grouping(grouping) => Ok(self.interpret_expr(*grouping.expression)?),
             Expr::Literal(literal) => match literal.value.token_type {
@@ -471,15 +991,23 @@ impl Interpreter {
                     primitive: Primitive::Nil,
                     token: literal.value,
                 }),
-                TokenType::NUMBER => Ok(Value {
-                    primitive: Primitive::Number(literal.value.lexeme.parse().unwrap()),
-                    token: literal.value,
-                }),
+                TokenType::NUMBER => match literal.value.lexeme.parse() {
+                    Ok(number) => Ok(Value {
+                        primitive: Primitive::Number(number),
+                        token: literal.value,
+                    }),
+                    Err(_) => Err(InterpretError::new(
+                        ErrorKind::RuntimeError,
+                        format!("Invalid number literal: {}", literal.value.lexeme),
+                        literal.value,
+                    )),
+                },
                 TokenType::STRING => Ok(Value {
                     primitive: Primitive::String(literal.value.lexeme.clone()),
                     token: literal.value,
                 }),
                 _ => Err(InterpretError::new(
+                    ErrorKind::RuntimeError,
                     format!("Unknown literal: {}", literal.value.lexeme),
                     literal.value,
                 )),
@@ -488,14 +1016,19 @@ impl Interpreter {
                 let right = self.interpret_expr(*unary.right)?;
                 match unary.operator.lexeme.as_str() {
                     "!" => Ok(Value {
-                        primitive: Primitive::Boolean(!self.is_truthy(&right)),
+                        primitive: Primitive::Boolean(!right.primitive.is_truthy()),
                         token: unary.operator,
                     }),
                     "-" => Ok(Value {
-                        primitive: Primitive::Number(-self.to_number(right)?),
+                        primitive: Primitive::Number(
+                            -self
+                                .to_number(right)
+                                .map_err(|e| e.within(expr::span(&expr)))?,
+                        ),
                         token: unary.operator,
                     }),
                     _ => Err(InterpretError::new(
+                        ErrorKind::RuntimeError,
                         format!("Unknown unary operator: {}", unary.operator.lexeme),
                         unary.operator,
                     )),
@@ -503,38 +1036,56 @@ impl Interpreter {
             }
             Expr::Ternary(ternary) => {
                 let condition = self.interpret_expr(*ternary.condition)?;
-                if self.is_truthy(&condition) {
+                if condition.primitive.is_truthy() {
                     Ok(self.interpret_expr(*ternary.then_branch)?)
                 } else {
                     Ok(self.interpret_expr(*ternary.else_branch)?)
                 }
             }
             Expr::Variable(variable) => Ok(self.look_up_var(&variable.name, &expr)?),
-            Expr::Assign(assign) => {
-                let distance = self.get_local(&expr);
-                if let Some(distance) = distance.clone() {
-                    let expr = self.interpret_expr(*assign.value.clone())?;
-                    self.environment.borrow_mut().assign_at(
-                        distance,
-                        assign.name.lexeme.clone(),
-                        expr,
-                    );
-                } else {
-                    let expr = self.interpret_expr(*assign.value.clone())?;
-                    self.environment
-                        .borrow_mut()
-                        .assign_global(assign.name.lexeme.clone(), expr);
+            Expr::This(this) => self.look_up_var(&this.keyword, &expr),
+            Expr::Super(super_expr) => {
+                let superclass = self.look_up_var(&super_expr.keyword, &expr)?;
+                // `this` is in the scope just inside the one holding `super`.
+                let distance = self.locals.get(&expr).copied().unwrap_or_default();
+                let this = self
+                    .environment
+                    .borrow()
+                    .get(distance.saturating_sub(1), "this");
+                let (Primitive::Class(superclass), Some(Primitive::Instance(instance))) =
+                    (superclass.primitive, this.map(|this| this.primitive))
+                else {
+                    return Err(InterpretError::new(
+                        ErrorKind::RuntimeError,
+                        String::from("Can't use 'super' outside of a method."),
+                        super_expr.keyword,
+                    ));
+                };
+                match superclass.find_method(&super_expr.method.lexeme) {
+                    Some(method) => Ok(Value {
+                        primitive: Primitive::Callable(method.bind(instance)),
+                        token: super_expr.method,
+                    }),
+                    None => Err(InterpretError::new(
+                        ErrorKind::PropertyError,
+                        format!("Undefined property '{}'.", super_expr.method.lexeme),
+                        super_expr.method,
+                    )),
                 }
-                Ok(self.interpret_expr(*assign.value)?)
+            }
+            Expr::Assign(assign) => {
+                let value = self.interpret_expr(*assign.value.clone())?;
+                self.assign(assign.name, &expr, value.clone())?;
+                Ok(value)
             }
             Expr::Logical(logical) => {
                 let left = self.interpret_expr(*logical.left)?;
                 if logical.operator.token_type == TokenType::OR {
-                    if self.is_truthy(&left) {
+                    if left.primitive.is_truthy() {
                         return Ok(left);
                     }
                 } else {
-                    if !self.is_truthy(&left) {
+                    if !left.primitive.is_truthy() {
                         return Ok(left);
                     }
                 }
@@ -542,4 +1093,886 @@ impl Interpreter {
             }
         }
     }
+
+    /// Calls the `close()` method of a `with` statement's resource.
+    fn close(&mut self, resource: Value, keyword: Token) -> Result<(), InterpretError> {
+        let Primitive::Instance(instance) = &resource.primitive else {
+            return Err(InterpretError::new(
+                ErrorKind::PropertyError,
+                format!("Only instances can be closed, not {}.", resource.primitive),
+                keyword,
+            ));
+        };
+        let close = Token::new(TokenType::IDENTIFIER, String::from("close"), keyword.line);
+        let close = instance.get(close).map_err(|_| {
+            InterpretError::new(
+                ErrorKind::PropertyError,
+                format!("{} has no close() method.", resource.primitive),
+                keyword.clone(),
+            )
+        })?;
+        self.call_value(close, Vec::new(), keyword).map(|_| ())
+    }
+
+    /// Stores `value` in the variable `name`, where the resolver found it for `expr`.
+    fn assign(&mut self, name: Token, expr: &Expr, value: Value) -> Result<(), InterpretError> {
+        if let Some(distance) = self.get_local(expr) {
+            self.environment
+                .borrow_mut()
+                .assign_at(distance, name.lexeme, value);
+            Ok(())
+        } else if self.dynamic_scope {
+            self.environment.borrow_mut().assign(name.lexeme, value)
+        } else {
+            self.environment.borrow_mut().assign_global(&name, value)
+        }
+    }
+
+    /// The values of `value` for each of `names`: the ones a function gave back with
+    /// `return a, b;`, as many as there are names.
+    /// The values for `names` to take: those of a list when there's one value, or else each
+    /// value, all worked out before any name is assigned.
+    fn unpack(&mut self, names: &[Token], values: Vec<Expr>) -> Result<Vec<Value>, InterpretError> {
+        let token = expr::first_token(&values[0]).clone();
+        let span = expr::span(&values[0]).start..expr::span(&values[values.len() - 1]).end;
+        let values = if values.len() == 1 {
+            let value = self.interpret_expr(values.into_iter().next().unwrap())?;
+            match &value.primitive {
+                Primitive::List(list) => list.borrow().clone(),
+                _ => vec![value],
+            }
+        } else {
+            values
+                .into_iter()
+                .map(|value| self.interpret_expr(value))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        if values.len() != names.len() {
+            return Err(InterpretError::new(
+                ErrorKind::TypeError,
+                format!(
+                    "Expected {} values to unpack but got {}.",
+                    names.len(),
+                    values.len()
+                ),
+                token,
+            )
+            .within(span));
+        }
+        Ok(values)
+    }
+
+    fn binary(
+        &mut self,
+        operator: Token,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, InterpretError> {
+        match operator.lexeme.as_str() {
+            "," => Ok(right),
+            "-" => {
+                if let (Primitive::Number(left), Primitive::Number(right)) =
+                    (&left.primitive, &right.primitive)
+                {
+                    Ok(Value {
+                        primitive: Primitive::Number(left - right),
+                        token: operator,
+                    })
+                } else {
+                    Err(InterpretError::new(
+                        ErrorKind::TypeError,
+                        format!(
+                            "Operands must be two numbers: {} - {}",
+                            left.token.lexeme, right.token.lexeme
+                        ),
+                        operator,
+                    ))
+                }
+            }
+            "*" => {
+                if let (Primitive::Number(left), Primitive::Number(right)) =
+                    (&left.primitive, &right.primitive)
+                {
+                    Ok(Value {
+                        primitive: Primitive::Number(left * right),
+                        token: operator,
+                    })
+                } else {
+                    Err(InterpretError::new(
+                        ErrorKind::TypeError,
+                        format!(
+                            "Operands must be two numbers: {} * {}",
+                            left.token.lexeme, right.token.lexeme
+                        ),
+                        operator,
+                    ))
+                }
+            }
+            "/" => {
+                if let (Primitive::Number(left), Primitive::Number(right)) =
+                    (&left.primitive, &right.primitive)
+                {
+                    self.check_divisor(*right, &operator)?;
+                    Ok(Value {
+                        primitive: Primitive::Number(left / right),
+                        token: operator,
+                    })
+                } else {
+                    Err(InterpretError::new(
+                        ErrorKind::TypeError,
+                        format!(
+                            "Operands must be two numbers: {} / {}",
+                            left.token.lexeme, right.token.lexeme
+                        ),
+                        operator,
+                    ))
+                }
+            }
+            "+" => match (&left.primitive, &right.primitive) {
+                (Primitive::Number(left), Primitive::Number(right)) => Ok(Value {
+                    primitive: Primitive::Number(left + right),
+                    token: operator,
+                }),
+                (Primitive::String(left), Primitive::String(right)) => Ok(Value {
+                    primitive: Primitive::String(format!("{}{}", left, right)),
+                    token: operator,
+                }),
+                (Primitive::String(left), Primitive::Number(right)) => Ok(Value {
+                    primitive: Primitive::String(format!(
+                        "{}{}",
+                        left,
+                        format_number(*right, self.precision)
+                    )),
+                    token: operator,
+                }),
+                (Primitive::Number(left), Primitive::String(right)) => Ok(Value {
+                    primitive: Primitive::String(format!(
+                        "{}{}",
+                        format_number(*left, self.precision),
+                        right
+                    )),
+                    token: operator,
+                }),
+                _ => Err(InterpretError::new(
+                    ErrorKind::TypeError,
+                    format!(
+                        "Operands must be two numbers or two strings: {} + {}",
+                        left.token.lexeme, right.token.lexeme
+                    ),
+                    operator,
+                )),
+            },
+            ">" => Ok(Value {
+                primitive: Primitive::Boolean(self.to_number(left)? > self.to_number(right)?),
+                token: operator,
+            }),
+            ">=" => Ok(Value {
+                primitive: Primitive::Boolean(self.to_number(left)? >= self.to_number(right)?),
+                token: operator,
+            }),
+            "<" => Ok(Value {
+                primitive: Primitive::Boolean(self.to_number(left)? < self.to_number(right)?),
+                token: operator,
+            }),
+            "<=" => Ok(Value {
+                primitive: Primitive::Boolean(self.to_number(left)? <= self.to_number(right)?),
+                token: operator,
+            }),
+            "!=" => Ok(Value {
+                primitive: Primitive::Boolean(!self.is_equal(left, right)),
+                token: operator,
+            }),
+            "==" => Ok(Value {
+                primitive: Primitive::Boolean(self.is_equal(left, right)),
+                token: operator,
+            }),
+            "in" => Ok(Value {
+                primitive: Primitive::Boolean(self.contains(&right, &left, &operator)?),
+                token: operator,
+            }),
+            _ => Err(InterpretError::new(
+                ErrorKind::TypeError,
+                format!(
+                    "Operands must be two numbers or two strings: {} + {}",
+                    left.token.lexeme, right.token.lexeme
+                ),
+                operator,
+            )),
+        }
+    }
+}
+
+/// Runs `f`, first moving to a fresh stack segment if the current one is running low, so a
+/// deeply recursive program ends at the call depth limit on any thread.
+#[cfg(not(target_arch = "wasm32"))]
+fn with_stack<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, f)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn with_stack<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+/// How a statement shows up in the trace. Blocks are left out since their statements are
+/// traced individually, and compound statements only show their header.
+fn trace_stmt(stmt: &Stmt) -> Option<String> {
+    match stmt {
+        Stmt::Block(_) => None,
+        Stmt::If(condition, _, _) => Some(format!("(if {})", expr::print(condition.clone()))),
+        Stmt::While(Some(condition), _, _) => {
+            Some(format!("(while {})", expr::print(condition.clone())))
+        }
+        Stmt::While(None, _, _) => Some(String::from("(while true)")),
+        Stmt::Function(name, ..) => Some(format!("(fun {})", name.lexeme)),
+        Stmt::Class(name, ..) => Some(format!("(class {})", name.lexeme)),
+        Stmt::With(_, resource, name, _) => Some(format!(
+            "(with {} {})",
+            expr::print(resource.clone()),
+            name.lexeme
+        )),
+        stmt => Some(stmt::print(stmt.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    fn run(interpreter: &mut Interpreter, source: &str) {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        Resolver::new(interpreter).resolve(stmts.clone()).unwrap();
+        for stmt in stmts {
+            interpreter.interpret(stmt).unwrap();
+        }
+    }
+
+    fn interpreter_for(source: &str) -> Interpreter {
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())));
+        run(&mut interpreter, source);
+        interpreter
+    }
+
+    /// Runs `source` in a fresh interpreter and returns everything it printed.
+    fn output_of(source: &str) -> String {
+        output_with(source, |_| {})
+    }
+
+    /// Like `output_of`, with `prepare` setting up the interpreter before `source` runs.
+    fn output_with(source: &str, prepare: impl FnOnce(&mut Interpreter)) -> String {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(stdout.clone())
+            .with_stderr(Rc::new(RefCell::new(std::io::sink())));
+        prepare(&mut interpreter);
+        run(&mut interpreter, source);
+        let output = String::from_utf8(stdout.borrow().clone()).unwrap();
+        output
+    }
+
+    /// Runs `source`, which has to fail, in a fresh interpreter and returns the diagnostics
+    /// it reported.
+    fn errors_of(source: &str) -> String {
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(Rc::new(RefCell::new(std::io::sink())))
+            .with_stderr(stderr.clone());
+        assert_ne!(
+            crate::run(source.to_string(), &mut interpreter),
+            crate::Status::Ok
+        );
+        let errors = String::from_utf8(stderr.borrow().clone()).unwrap();
+        errors
+    }
+
+    #[test]
+    fn test_injected_streams() {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let stdin = Rc::new(RefCell::new(std::io::Cursor::new(
+            b"first\nsecond".to_vec(),
+        )));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(stdout.clone())
+            .with_stdin(stdin);
+        run(
+            &mut interpreter,
+            "fun show(x) { print x; } show(input()); show(input()); show(input());",
+        );
+        assert_eq!(
+            String::from_utf8(stdout.borrow().clone()).unwrap(),
+            "\"first\"\n\"second\"\nnil\n"
+        );
+    }
+
+    #[test]
+    fn test_script_args() {
+        let output = output_with(
+            "var file, flag = args(); print flag; print file;",
+            |interpreter| {
+                interpreter.set_script_args(vec![String::from("a.txt"), String::from("--fast")])
+            },
+        );
+        assert_eq!(output, "\"--fast\"\n\"a.txt\"\n");
+    }
+
+    #[test]
+    fn test_assignment_expressions() {
+        assert_eq!(
+            output_of(
+                "var a; var b; a = b = 2; print a + b;\n\
+                 { var n = 3; var c; while ((n = n - 1) > 0) c = n; print c; }\n\
+                 fun f() { var x; if (x = \"set\") print x; } f();"
+            ),
+            "4\n1\n\"set\"\n"
+        );
+        assert!(errors_of("nope = 1;").starts_with("error[E102]: Undefined variable 'nope'."));
+    }
+
+    #[test]
+    fn test_with_closes() {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(stdout.clone())
+            .with_stderr(Rc::new(RefCell::new(std::io::sink())));
+        interpreter.define_native("open", 0, |_, _| {
+            let name = Token::new(TokenType::IDENTIFIER, String::from("File"), 0);
+            let close = crate::primitive::NativeFunction::new(
+                "close",
+                0,
+                Rc::new(|interpreter: &mut Interpreter, _| {
+                    writeln!(interpreter.stdout.borrow_mut(), "closed").unwrap();
+                    Ok(().into())
+                }),
+            );
+            let file = Instance::new(Class::new(name.clone(), Vec::new()));
+            file.set(
+                Token::new(TokenType::IDENTIFIER, String::from("close"), 0),
+                Value {
+                    primitive: Primitive::Native(close),
+                    token: name.clone(),
+                },
+            );
+            Ok(Value {
+                primitive: Primitive::Instance(file),
+                token: name,
+            })
+        });
+        run(
+            &mut interpreter,
+            "with open() as f { print 1; }\n\
+             fun read() { with open() as f { return 2; } }\n\
+             print read();",
+        );
+        assert_eq!(
+            crate::run(
+                String::from("with open() as f { nope(); }"),
+                &mut interpreter
+            ),
+            crate::Status::RuntimeError
+        );
+        assert_eq!(
+            crate::run(String::from("with 1 as f {}"), &mut interpreter),
+            crate::Status::RuntimeError
+        );
+        assert_eq!(
+            String::from_utf8(stdout.borrow().clone()).unwrap(),
+            "1\nclosed\nclosed\n2\nclosed\n"
+        );
+    }
+
+    #[test]
+    fn test_methods() {
+        assert_eq!(
+            output_of(
+                "class Animal { speak() { return \"...\"; } legs() { return 4; } }\n\
+                 class Dog < Animal { speak() { return \"woof\"; } }\n\
+                 var dog = Dog(); var speak = dog.speak;\n\
+                 print dog.speak(); print speak(); print dog.legs();\n\
+                 class Lock { close() { print \"unlocked\"; } }\n\
+                 with Lock() as lock { print 5; }"
+            ),
+            "\"woof\"\n\"woof\"\n4\n5\n\"unlocked\"\n"
+        );
+        let mut interpreter = interpreter_for("class Dog { speak() {} }");
+        let Primitive::Instance(dog) = interpreter.call_function("Dog", &[]).unwrap().primitive
+        else {
+            panic!("Dog() should make an instance");
+        };
+        let fly = Token::new(TokenType::IDENTIFIER, String::from("fly"), 1);
+        assert_eq!(
+            dog.get(fly).unwrap_err().message,
+            "Undefined property 'fly'."
+        );
+    }
+
+    #[test]
+    fn test_multiple_returns() {
+        assert_eq!(
+            output_of(
+                "fun divmod(a, b) { var q = 0; while (a >= b) { a = a - b; q = q + 1; } return q, a; }\n\
+                 var q, r = divmod(7, 2); print q; print r;\n\
+                 { var x; var y; x, y = divmod(9, 4); print x * 10 + y; }"
+            ),
+            "3\n1\n21\n"
+        );
+        assert_eq!(
+            output_of(
+                "var a = 1; var b = 2; a, b = b, a; print a, b;\n\
+                 var p, q = 1, 2; print p + q;"
+            ),
+            "2 1\n3\n"
+        );
+        assert!(errors_of("var a, b = 1, 2, 3;")
+            .starts_with("error[E101]: Expected 2 values to unpack but got 3."));
+        assert!(
+            errors_of("fun pair() { return 1, 2; } var a, b, c = pair();")
+                .starts_with("error[E101]: Expected 3 values to unpack but got 2.")
+        );
+    }
+
+    #[test]
+    fn test_late_bound_globals() {
+        assert_eq!(
+            output_of(
+                "fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }\n\
+                 fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }\n\
+                 print isEven(10); print isOdd(7);\n\
+                 fun answer() { return value() + offset; }\n\
+                 fun value() { return 1; }\n\
+                 var offset = 10;\n\
+                 print answer();\n\
+                 fun value() { return 2; }\n\
+                 offset = 20;\n\
+                 print answer();"
+            ),
+            "true\ntrue\n11\n22\n"
+        );
+    }
+
+    #[test]
+    fn test_equality() {
+        assert_eq!(
+            output_of(
+                "class A {} var a = A(); var b = a;\n\
+                 print a == b; print a == A(); print A == A;\n\
+                 fun f() {} fun g() {} print f == f; print f == g; print clock == clock;\n\
+                 fun make() { fun inner() {} return inner; } print make() == make();"
+            ),
+            "true\nfalse\ntrue\ntrue\nfalse\ntrue\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn test_truthiness() {
+        assert_eq!(
+            output_of(
+                "if (1) print 1; if (0) print 2; if (\"\") print 3; if (nil) print 4; else print 5;\n\
+                 if (false) print 6; fun f() {} if (f) print 7;\n\
+                 while (nil) print 8; while (false) print 9;\n\
+                 var n = 2; while (n) { print n; n = n > 1 ? n - 1 : nil; }\n\
+                 print !0, !\"\", !nil;"
+            ),
+            "1\n2\n3\n5\n7\n2\n1\nfalse false true\n"
+        );
+    }
+
+    #[test]
+    fn test_in() {
+        let output = output_with(
+            "fun pair() { return 1, \"a\"; } var list = pair();\n\
+             print \"ell\" in \"hello\"; print \"z\" in \"hello\";\n\
+             print 1 in list == true; print 2 in list; print \"key\" in map; print \"k\" in map;",
+            |interpreter| {
+                let map = HashMap::from([(String::from("key"), Value::from(1.0))]);
+                interpreter.define(String::from("map"), map.into());
+            },
+        );
+        assert_eq!(output, "true\nfalse\ntrue\nfalse\ntrue\nfalse\n");
+        assert!(errors_of("print 1 in 2;").starts_with(&format!(
+            "error[{}]: Can only look in a string, list or map, not 2.",
+            ErrorKind::TypeError.code()
+        )));
+    }
+
+    #[test]
+    fn test_break() {
+        assert_eq!(
+            output_of(
+                "for (var i = 0; i < 10; i = i + 1) {\n\
+                   for (var j = 0; j < 10; j = j + 1) { if (j == 2) break; print j; }\n\
+                   if (i == 1) { break; }\n\
+                   print i;\n\
+                 }\n\
+                 var n = 0; while (true) { n = n + 1; if (n > 2) break; } print n;\n\
+                 for (;;) { n = n - 1; if (n == 0) break; } print n;"
+            ),
+            "0\n1\n0\n0\n1\n3\n0\n"
+        );
+    }
+
+    #[test]
+    fn test_continue() {
+        assert_eq!(
+            output_of(
+                "for (var i = 0; i < 5; i = i + 1) { if (i == 1 or i == 3) continue; print i; }\n\
+                 var n = 0; while (n < 4) { n = n + 1; if (n == 2) { continue; } print n; }"
+            ),
+            "0\n2\n4\n1\n3\n4\n"
+        );
+    }
+
+    #[test]
+    fn test_this() {
+        assert_eq!(
+            output_of(
+                "class A {\n\
+                   me() { return this; }\n\
+                   later() { fun inner() { return this.name(); } return inner; }\n\
+                   name() { return 1; }\n\
+                 }\n\
+                 var a = A(); var me = a.me; var later = a.later();\n\
+                 print me() == a; print later(); print A().me() == a;"
+            ),
+            "true\n1\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn test_init() {
+        assert_eq!(
+            output_of(
+                "class P {\n\
+                   init(a, b) { print a + b; if (a > 5) return; print \"small\"; }\n\
+                 }\n\
+                 var p = P(1, 2); print p.init(10, 1) == p;"
+            ),
+            "3\n\"small\"\n11\ntrue\n"
+        );
+        assert!(errors_of("class P { init(a, b) {} } P(1);")
+            .starts_with("error[E103]: Expected 2 arguments but got 1."));
+    }
+
+    #[test]
+    fn test_super() {
+        assert_eq!(
+            output_of(
+                "class A { init(n) { print n; } hi() { return \"A \" + this.who(); } who() { return 1; } }\n\
+                 class B < A { init(n) { super.init(n + 1); } hi() { return super.hi() + \"B\"; } }\n\
+                 class C < B { who() { return \"C\"; } }\n\
+                 print C(1).hi();"
+            ),
+            "2\n\"A CB\"\n"
+        );
+        assert!(errors_of("var x = 1; class D < x {}")
+            .starts_with("error[E101]: Superclass must be a class."));
+    }
+
+    #[test]
+    fn test_shared_fields() {
+        assert_eq!(
+            output_of(
+                "class Counter { init() { this.n = 0; } bump() { this.n = this.n + 1; return this; } }\n\
+                 var a = Counter(); var b = a; a.x = 3; print a.x; print b.x;\n\
+                 fun bump(counter) { counter.bump(); } bump(a); b.bump().bump(); print a.n;\n\
+                 print a == b; print a == Counter();"
+            ),
+            "3\n3\n3\ntrue\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn test_print_values() {
+        assert_eq!(
+            output_of("var x = 1; var y = nil; print \"x =\", x, \"y =\", y; print (x, 2);"),
+            "\"x =\" 1 \"y =\" nil\n2\n"
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let new_interpreter = |precision| {
+            let stdout = Rc::new(RefCell::new(Vec::new()));
+            let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+                .with_stdout(stdout.clone());
+            interpreter.set_precision(precision);
+            (interpreter, stdout)
+        };
+        // Both on this thread at once, neither affecting the other.
+        let (mut rounded, rounded_output) = new_interpreter(Some(2));
+        let (mut exact, exact_output) = new_interpreter(None);
+        let source = "fun thirds() { return 1 / 3, 2 / 3; }\n\
+                      print 2 / 3, thirds(); print str(2 / 3) + \" \" + 1 / 3;";
+        run(&mut rounded, source);
+        run(&mut exact, source);
+        assert_eq!(
+            String::from_utf8(rounded_output.borrow().clone()).unwrap(),
+            "0.67 [0.33, 0.67]\n\"0.67 0.33\"\n"
+        );
+        assert_eq!(
+            String::from_utf8(exact_output.borrow().clone()).unwrap(),
+            "0.6666666666666666 [0.3333333333333333, 0.6666666666666666]\n\
+             \"0.6666666666666666 0.3333333333333333\"\n"
+        );
+    }
+
+    #[test]
+    fn test_call_function() {
+        let mut interpreter = interpreter_for("fun add(a, b) { return a + b; }");
+        let result = interpreter
+            .call_function("add", &[1.0.into(), 2.0.into()])
+            .unwrap();
+        assert_eq!(result.primitive, Primitive::Number(3.0));
+    }
+
+    #[test]
+    fn test_call_function_as() {
+        let mut interpreter = interpreter_for("fun greet(name) { return \"hi \" + name; }");
+        let greeting: String = interpreter
+            .call_function_as("greet", &["lox".into()])
+            .unwrap();
+        assert_eq!(greeting, "hi lox");
+    }
+
+    #[test]
+    fn test_call_function_errors() {
+        let mut interpreter = interpreter_for("fun one(a) { return a; } var x = 1;");
+        let err = interpreter.call_function("missing", &[]).unwrap_err();
+        assert_eq!(err.message, "Undefined variable 'missing'.");
+        let err = interpreter.call_function("one", &[]).unwrap_err();
+        assert_eq!(err.message, "Expected 1 arguments but got 0.");
+        let err = interpreter.call_function("x", &[]).unwrap_err();
+        assert_eq!(err.message, "Can only call functions and classes.");
+    }
+
+    #[test]
+    fn test_check_does_not_execute() {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(stdout.clone())
+            .with_stderr(stderr.clone());
+        assert_eq!(
+            crate::check(String::from("print 1;"), &mut interpreter),
+            crate::Status::Ok
+        );
+        assert!(stdout.borrow().is_empty());
+        assert_eq!(
+            crate::check(String::from("{ var a = a; }"), &mut interpreter),
+            crate::Status::CompileError
+        );
+        assert!(String::from_utf8(stderr.borrow().clone())
+            .unwrap()
+            .contains("Cannot read local variable in its own initializer."));
+    }
+
+    #[test]
+    fn test_reset_keeps_natives() {
+        let mut interpreter = interpreter_for("var x = 1;");
+        interpreter.set_script_args(vec![String::from("a")]);
+        interpreter.reset();
+        assert!(interpreter.environment.borrow().get_global("x").is_none());
+        let args: Vec<String> = interpreter.call_function_as("args", &[]).unwrap();
+        assert_eq!(args, vec![String::from("a")]);
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut interpreter =
+            interpreter_for("fun spin() { while (true) {} } fun forever() { for (;;) {} }");
+        interpreter.interrupt_flag().store(true, Ordering::SeqCst);
+        let err = interpreter.call_function("spin", &[]).unwrap_err();
+        assert_eq!(err.message, "Interrupted.");
+        let err = interpreter.call_function("forever", &[]).unwrap_err();
+        assert_eq!(err.message, "Interrupted.");
+
+        // Between statements, too.
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(stdout.clone())
+            .with_stderr(Rc::new(RefCell::new(std::io::sink())));
+        interpreter.define_native("stop", 0, |interpreter, _| {
+            interpreter.interrupt_flag().store(true, Ordering::SeqCst);
+            Ok(().into())
+        });
+        crate::run(
+            String::from("{ print 1; stop(); print 2; }"),
+            &mut interpreter,
+        );
+        assert_eq!(String::from_utf8(stdout.borrow().clone()).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        // On the test thread's own stack, which is far smaller than a thousand calls need.
+        let mut interpreter = interpreter_for("fun f() { f(); }");
+        let error = interpreter.call_function("f", &[]).unwrap_err();
+        assert_eq!(error.message, "Stack overflow.");
+        assert_eq!(interpreter.metrics().max_depth, MAX_CALL_DEPTH);
+
+        let mut interpreter = interpreter_for("fun f() { f(); }");
+        interpreter.set_max_call_depth(10);
+        assert!(interpreter.call_function("f", &[]).is_err());
+        assert_eq!(interpreter.metrics().max_depth, 10);
+    }
+
+    #[test]
+    fn test_stack_trace() {
+        let mut interpreter = interpreter_for(
+            "fun inner() {\n  return nope;\n}\nfun outer() {\n  return inner();\n}",
+        );
+        let err = interpreter.call_function("outer", &[]).unwrap_err();
+        assert_eq!(err.token.line, 2);
+        assert_eq!(
+            err.trace,
+            vec![
+                Frame {
+                    function: String::from("inner"),
+                    line: 5
+                },
+                Frame {
+                    function: String::from("outer"),
+                    line: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let mut interpreter =
+            interpreter_for("fun div(a, b) { return a / b; }\nfun double(a) { return a * 2; }");
+        let infinity = interpreter
+            .call_function_as::<f64>("div", &[Value::from(1.0), Value::from(0.0)])
+            .unwrap();
+        assert_eq!(infinity, f64::INFINITY);
+        interpreter.set_division_by_zero(DivisionByZero::Error);
+        let kind = |interpreter: &mut Interpreter, name: &str, args: &[Value]| {
+            interpreter.call_function(name, args).unwrap_err().kind
+        };
+        let number = |n: f64| Value::from(n);
+        assert_eq!(kind(&mut interpreter, "missing", &[]), ErrorKind::NameError);
+        assert_eq!(kind(&mut interpreter, "double", &[]), ErrorKind::ArityError);
+        assert_eq!(
+            kind(&mut interpreter, "div", &[number(1.0), number(0.0)]),
+            ErrorKind::DivisionByZero
+        );
+        assert_eq!(
+            kind(&mut interpreter, "double", &[Value::from(true)]),
+            ErrorKind::TypeError
+        );
+        assert_eq!(ErrorKind::NameError.code(), "E102");
+    }
+
+    #[test]
+    fn test_trace() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(Rc::new(RefCell::new(std::io::sink())));
+        interpreter.set_trace(Trace::Statements, output.clone());
+        run(
+            &mut interpreter,
+            "fun add(a, b) { return a + b; }\nprint add(1, 2);",
+        );
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "(fun add)\n(print (call add 1 2))\ncall add(1, 2)\n  (return (+ a b))\nadd returned 3\n"
+        );
+    }
+
+    #[test]
+    fn test_dynamic_scope() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stderr(Rc::new(RefCell::new(std::io::sink())));
+        let record = seen.clone();
+        interpreter.define_native("breakpoint", 0, move |interpreter, _| {
+            let secret = crate::evaluate(String::from("secret"), interpreter);
+            let scoped = interpreter.with_dynamic_scope(|interpreter| {
+                crate::evaluate(String::from("secret"), interpreter)
+            });
+            record
+                .borrow_mut()
+                .push((secret.is_some(), scoped.map(|v| v.primitive)));
+            Ok(().into())
+        });
+        run(
+            &mut interpreter,
+            "fun f() { var secret = 42; breakpoint(); } f();",
+        );
+        assert_eq!(*seen.borrow(), vec![(false, Some(Primitive::Number(42.0)))]);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(Rc::new(RefCell::new(std::io::sink())));
+        run(
+            &mut interpreter,
+            "fun f(n) { if (n > 0) { return f(n - 1); } return clock(); }\nclass A {}\nprint f(2);\nA();",
+        );
+        assert_eq!(
+            *interpreter.metrics(),
+            Metrics {
+                statements: 12,
+                calls: 4,
+                allocations: 3,
+                environments: 5,
+                max_depth: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0..2 {
+            let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+                .with_stdout(output.clone());
+            interpreter.set_seed(42);
+            run(
+                &mut interpreter,
+                "print random(); var start = clock(); print start;\n\
+                 var end = clock(); print (end - start) * 1000000;",
+            );
+        }
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..3], lines[3..]);
+        let random = lines[0].parse::<f64>().unwrap();
+        assert!((0.0..1.0).contains(&random));
+        // A microsecond for each statement up to the one calling `clock`.
+        assert_eq!(lines[1], "0.000002");
+        assert_eq!(lines[2].parse::<f64>().unwrap().round(), 2.0);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(Rc::new(RefCell::new(std::io::sink())))
+            .with_stderr(stderr.clone());
+        interpreter.set_timeout(Some(Duration::from_millis(10)));
+        let status = crate::run(String::from("while (true) {}\nprint 1;"), &mut interpreter);
+        assert_eq!(status, crate::Status::RuntimeError);
+        assert!(interpreter.timed_out());
+        let stderr = String::from_utf8(stderr.borrow().clone()).unwrap();
+        assert!(stderr.contains("Execution timed out after 10ms."));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(Rc::new(RefCell::new(std::io::sink())));
+        interpreter.coverage = Some(Coverage::default());
+        crate::run(
+            String::from("fun f(x) {\n  if (x)\n    print 1;\n  else\n    print 2;\n}\nf(true);"),
+            &mut interpreter,
+        );
+        let coverage = interpreter.coverage.unwrap();
+        assert_eq!(coverage.summary(), (4, 5));
+        assert_eq!(coverage.missed(), vec![5]);
+        assert!(coverage.lcov("f.lox").contains("SF:f.lox\nDA:1,1\n"));
+    }
 }
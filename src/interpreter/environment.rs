@@ -1,11 +1,11 @@
-use crate::interpreter::Value;
+use crate::{interpreter::Value, token::Token};
 use std::{
     cell::RefCell,
     collections::{hash_map, HashMap},
     rc::Rc,
 };
 
-use super::InterpretError;
+use super::{ErrorKind, InterpretError};
 
 #[derive(Clone, Debug)]
 pub struct Environment {
@@ -28,31 +28,69 @@ impl Environment {
         }
     }
 
+    /// Looks `name` up among the globals as they are now, so code can use globals declared
+    /// after it as long as they exist by the time it runs.
     pub fn get_global(&self, name: &str) -> Option<Value> {
-        let mut environment = self.clone();
-        while let Some(enclosing) = environment.enclosing {
-            environment = enclosing.as_ref().borrow().clone();
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_global(name),
+            None => self.values.get(name).cloned(),
         }
-        environment.get(0, name)
     }
 
+    /// Finds `name` in the nearest scope that defines it, walking out from this one.
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().lookup(name),
+        }
+    }
+
+    /// Every variable visible from this scope apart from globals, innermost first. A name
+    /// shadowed by an inner scope is only listed once.
+    pub fn locals(&self) -> Vec<(String, Value)> {
+        let mut locals: Vec<(String, Value)> = Vec::new();
+        let Some(enclosing) = &self.enclosing else {
+            return locals;
+        };
+        let mut names = self.values.iter().collect::<Vec<_>>();
+        names.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, value) in names {
+            locals.push((name.clone(), value.clone()));
+        }
+        for (name, value) in enclosing.borrow().locals() {
+            if !locals.iter().any(|(local, _)| *local == name) {
+                locals.push((name, value));
+            }
+        }
+        locals
+    }
+
+    /// Every variable defined in the outermost scope.
+    pub fn globals(&self) -> HashMap<String, Value> {
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().globals(),
+            None => self.values.clone(),
+        }
+    }
+
+    /// The value of `name` in the scope `distance` steps out from this one, as worked out by
+    /// the resolver.
     pub fn get(&self, distance: usize, name: &str) -> Option<Value> {
-        if self.values.contains_key(name) {
-            self.values.get(name).cloned()
-        } else {
-            self.ancestor(distance)
-                .as_ref()
-                .borrow()
-                .get(distance, name)
+        if distance == 0 {
+            return self.values.get(name).cloned();
         }
+        let ancestor = self.ancestor(distance)?;
+        let value = ancestor.borrow().values.get(name).cloned();
+        value
     }
 
-    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
-        let mut environment = self.enclosing.as_ref().unwrap().borrow().clone();
-        for _ in 0..distance {
-            environment = environment.enclosing.unwrap().as_ref().borrow().clone();
+    fn ancestor(&self, distance: usize) -> Option<Rc<RefCell<Environment>>> {
+        let mut environment = self.enclosing.clone()?;
+        for _ in 1..distance {
+            let enclosing = environment.borrow().enclosing.clone()?;
+            environment = enclosing;
         }
-        Rc::new(RefCell::new(environment))
+        Some(environment)
     }
 
     pub fn define(&mut self, name: String, value: Value) {
@@ -70,24 +108,35 @@ impl Environment {
         }
 
         Err(InterpretError::new(
-            String::from("Undefined variable '"),
+            ErrorKind::NameError,
+            format!("Undefined variable '{}'.", name),
             value.token,
         ))
     }
 
     pub fn assign_at(&mut self, distance: usize, name: String, value: Value) {
-        self.ancestor(distance)
-            .as_ref()
-            .borrow_mut()
-            .values
-            .insert(name, value);
+        if distance == 0 {
+            self.values.insert(name, value);
+        } else if let Some(ancestor) = self.ancestor(distance) {
+            ancestor.borrow_mut().values.insert(name, value);
+        }
     }
 
-    pub fn assign_global(&mut self, name: String, value: Value) {
-        let mut environment = self.clone();
-        while let Some(enclosing) = environment.enclosing {
-            environment = enclosing.as_ref().borrow().clone();
+    /// Assigns to `name` among the globals, which it must already be defined in.
+    pub fn assign_global(&mut self, name: &Token, value: Value) -> Result<(), InterpretError> {
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow_mut().assign_global(name, value);
+        }
+        match self.values.get_mut(&name.lexeme) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(InterpretError::new(
+                ErrorKind::NameError,
+                format!("Undefined variable '{}'.", name.lexeme),
+                name.clone(),
+            )),
         }
-        environment.values.insert(name, value);
     }
 }
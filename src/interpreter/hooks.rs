@@ -0,0 +1,110 @@
+//! Callbacks embedders register to follow a program as it runs, for profilers, debuggers and
+//! audit logs.
+
+use super::{Frame, InterpretError, Interpreter};
+use crate::{expr::Value, stmt::Stmt};
+use std::ops::Range;
+
+/// Where a hook is called from: the source it's about, and the Lox calls that led there,
+/// outermost first. At the top level of a script there are none.
+pub struct Site<'a> {
+    pub line: usize,
+    /// The statement's bytes in the source, or for calls and returns the call's closing
+    /// parenthesis. None for code that wasn't scanned from source.
+    pub span: Option<Range<usize>>,
+    pub frames: &'a [Frame],
+}
+
+type StatementHook = Box<dyn FnMut(&Stmt, &Site)>;
+type CallHook = Box<dyn FnMut(&str, &[Value], &Site)>;
+type ReturnHook = Box<dyn FnMut(&str, &Value, &Site)>;
+type ErrorHook = Box<dyn FnMut(&InterpretError)>;
+
+#[derive(Default)]
+pub(super) struct Hooks {
+    pub statement: Vec<StatementHook>,
+    pub call: Vec<CallHook>,
+    pub returned: Vec<ReturnHook>,
+    pub error: Vec<ErrorHook>,
+}
+
+impl Interpreter {
+    /// Calls `hook` with each statement just before it runs.
+    pub fn on_statement(&mut self, hook: impl FnMut(&Stmt, &Site) + 'static) {
+        self.hooks.statement.push(Box::new(hook));
+    }
+
+    /// Calls `hook` with the name and arguments of each Lox function as it's entered. The
+    /// site is the call's, and its last frame is the function's own.
+    pub fn on_call(&mut self, hook: impl FnMut(&str, &[Value], &Site) + 'static) {
+        self.hooks.call.push(Box::new(hook));
+    }
+
+    /// Calls `hook` with the name of each Lox function that returns and what it returned,
+    /// from the same site as its call.
+    pub fn on_return(&mut self, hook: impl FnMut(&str, &Value, &Site) + 'static) {
+        self.hooks.returned.push(Box::new(hook));
+    }
+
+    /// Calls `hook` with each error that's reported, at compile time or at runtime. Its token,
+    /// span and trace say where it happened.
+    pub fn on_error(&mut self, hook: impl FnMut(&InterpretError) + 'static) {
+        self.hooks.error.push(Box::new(hook));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::environment::Environment;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn test_hooks() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(Rc::new(RefCell::new(std::io::sink())))
+            .with_stderr(Rc::new(RefCell::new(std::io::sink())));
+        let log = events.clone();
+        interpreter.on_statement(move |stmt, site| {
+            let depth = site.frames.len();
+            log.borrow_mut().push(format!(
+                "{}:{} {}",
+                site.line,
+                depth,
+                crate::stmt::print(stmt.clone())
+            ));
+        });
+        let log = events.clone();
+        interpreter.on_call(move |name, args, site| {
+            log.borrow_mut()
+                .push(format!("call {}/{} from {}", name, args.len(), site.line));
+        });
+        let log = events.clone();
+        interpreter.on_return(move |name, value, site| {
+            let span = site.span.clone().unwrap();
+            log.borrow_mut().push(format!(
+                "{} returned {} to {:?}",
+                name, value.primitive, span
+            ));
+        });
+        let log = events.clone();
+        interpreter.on_error(move |error| log.borrow_mut().push(error.message.clone()));
+        crate::run(
+            String::from("fun twice(n) {\n  return n * 2;\n}\nprint twice(2);\nnope;"),
+            &mut interpreter,
+        );
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "1:0 (fun twice (n) (return (* n 2)))",
+                "4:0 (print (call twice 2))",
+                "call twice/1 from 4",
+                "2:1 (return (* n 2))",
+                "twice returned 4 to 46..47",
+                "5:0 (expr nope)",
+                "Undefined variable 'nope'.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use super::{ErrorKind, InterpretError, Interpreter};
+use crate::{
+    expr::Value,
+    primitive::{Class, Instance, NativeFunction, Primitive},
+    stmt,
+    token::{Token, TokenType},
+    LoxClass,
+};
+use std::rc::Rc;
+
+impl Interpreter {
+    /// Registers a Rust function as a global callable from Lox.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, InterpretError> + 'static,
+    {
+        let native = NativeFunction::new(name, arity, Rc::new(function));
+        let value = Value {
+            primitive: Primitive::Native(native),
+            token: native_token(name),
+        };
+        self.environment
+            .borrow_mut()
+            .define(name.to_string(), value);
+    }
+}
+
+pub fn define_natives(interpreter: &mut Interpreter) {
+    // `SystemTime::now` panics on wasm32-unknown-unknown, so browsers go without `clock`.
+    // Seeded runs get a clock that starts at 0 and ticks a microsecond per statement instead,
+    // so that they repeat exactly.
+    #[cfg(not(target_arch = "wasm32"))]
+    interpreter.define_native("clock", 0, |interpreter, _| {
+        if interpreter.seeded {
+            return Ok((interpreter.metrics.statements as f64 / 1e6).into());
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(now.as_secs_f64().into())
+    });
+
+    // Reads a line from the interpreter's input stream, or returns nil at end of input.
+    interpreter.define_native("input", 0, |interpreter, _| {
+        let mut line = String::new();
+        let read = interpreter
+            .stdin
+            .borrow_mut()
+            .read_line(&mut line)
+            .map_err(|e| {
+                InterpretError::new(ErrorKind::IoError, e.to_string(), native_token("input"))
+            })?;
+        if read == 0 {
+            return Ok(().into());
+        }
+        Ok(line.trim_end_matches(['\n', '\r']).into())
+    });
+
+    // Returns a pseudo-random number from 0 up to but not including 1.
+    interpreter.define_native("random", 0, |interpreter, _| {
+        Ok(interpreter.rng.next().into())
+    });
+
+    // Lists the Lox calls that led here, innermost first, as instances with the `function`
+    // that's running, the `caller` that called it ("script" at the top level) and the `line`
+    // it was called from.
+    interpreter.define_native("callerInfo", 0, |interpreter, _| {
+        let class = Class::new(native_token("CallerInfo"), Vec::new());
+        let mut callers = interpreter
+            .frames
+            .iter()
+            .scan("script", |caller, frame| {
+                let info = Instance::new(class.clone());
+                {
+                    let mut fields = info.fields.borrow_mut();
+                    fields.insert(String::from("function"), frame.function.as_str().into());
+                    fields.insert(String::from("caller"), (*caller).into());
+                    fields.insert(String::from("line"), (frame.line as f64).into());
+                }
+                *caller = &frame.function;
+                Some(Value {
+                    primitive: Primitive::Instance(info),
+                    token: native_token("callerInfo"),
+                })
+            })
+            .collect::<Vec<_>>();
+        callers.reverse();
+        Ok(callers.into())
+    });
+
+    interpreter.set_script_args(Vec::new());
+
+    // Pauses for inspection under the CLI, which replaces this; elsewhere it does nothing.
+    interpreter.define_native("breakpoint", 0, |_, _| Ok(().into()));
+
+    // Converts any value to the string `print` would show; strings come back unchanged.
+    interpreter.define_native("str", 1, |interpreter, args| {
+        Ok(text(&args[0], interpreter.precision()).into())
+    });
+
+    interpreter.define_native("StringBuilder", 0, |interpreter, _| {
+        let builder = StringBuilder {
+            text: String::new(),
+            precision: interpreter.precision(),
+        };
+        Ok(builder.into_lox().0)
+    });
+
+    // Joins the items of a list into one string, with `separator` between them.
+    interpreter.define_native("join", 2, |interpreter, args| {
+        let Primitive::List(list) = &args[0].primitive else {
+            return Err(InterpretError::new(
+                ErrorKind::TypeError,
+                format!("Expected list, got {}", args[0].primitive),
+                args[0].token.clone(),
+            ));
+        };
+        let Primitive::String(separator) = &args[1].primitive else {
+            return Err(InterpretError::new(
+                ErrorKind::TypeError,
+                format!("Expected string separator, got {}", args[1].primitive),
+                args[1].token.clone(),
+            ));
+        };
+        let precision = interpreter.precision();
+        let items = list
+            .borrow()
+            .iter()
+            .map(|item| text(item, precision))
+            .collect::<Vec<_>>();
+        Ok(items.join(separator).into())
+    });
+
+    // Writes a number with exactly `digits` decimal places.
+    interpreter.define_native("toFixed", 2, |_, args| {
+        let number = f64::try_from(args[0].clone())?;
+        Ok(fixed(number, decimal_places(&args[1])?, "").into())
+    });
+
+    // Like `toFixed`, with `separator` between each group of three digits before the point.
+    interpreter.define_native("formatNumber", 3, |_, args| {
+        let number = f64::try_from(args[0].clone())?;
+        let separator = String::try_from(args[2].clone())?;
+        Ok(fixed(number, decimal_places(&args[1])?, &separator).into())
+    });
+
+    // Prints how to call a function or class and the `///` comments above its declaration.
+    interpreter.define_native("help", 1, |interpreter, args| {
+        let text = help(&args[0])?;
+        write!(interpreter.stdout.borrow_mut(), "{}", text).map_err(|e| {
+            InterpretError::new(ErrorKind::IoError, e.to_string(), native_token("help"))
+        })?;
+        Ok(().into())
+    });
+}
+
+/// The generator behind `random`: SplitMix64, which is small and good enough for scripts.
+pub(super) struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Seeded from the process's hashing keys, which differ from run to run.
+    pub fn from_entropy() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+        Self(
+            std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish(),
+        )
+    }
+
+    /// The next number in `[0, 1)`.
+    pub fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // The top 53 bits fill an f64's mantissa exactly.
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// How many decimal places `toFixed` and `formatNumber` may be asked for.
+const MAX_DECIMAL_PLACES: i64 = 20;
+
+fn decimal_places(value: &Value) -> Result<usize, InterpretError> {
+    let digits = i64::try_from(value.clone())?;
+    if !(0..=MAX_DECIMAL_PLACES).contains(&digits) {
+        return Err(InterpretError::new(
+            ErrorKind::TypeError,
+            format!(
+                "Expected between 0 and {} decimal places, got {}.",
+                MAX_DECIMAL_PLACES, digits
+            ),
+            value.token.clone(),
+        ));
+    }
+    Ok(digits as usize)
+}
+
+/// `number` rounded to `digits` decimal places, with `separator` between thousands.
+fn fixed(number: f64, digits: usize, separator: &str) -> String {
+    if !number.is_finite() {
+        return crate::primitive::format_number(number, None);
+    }
+    let formatted = format!("{:.*}", digits, number.abs());
+    let (whole, fraction) = formatted.split_at(formatted.find('.').unwrap_or(formatted.len()));
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    // Rounding away everything shouldn't leave a bare minus sign: -0.001 is "0.00".
+    let zero = formatted.chars().all(|c| c == '0' || c == '.');
+    let sign = if number < 0.0 && !zero { "-" } else { "" };
+    format!("{}{}{}", sign, grouped, fraction)
+}
+
+/// A value as text: strings as they are, anything else as `print` shows it.
+fn text(value: &Value, precision: Option<usize>) -> String {
+    match &value.primitive {
+        Primitive::String(string) => string.clone(),
+        primitive => primitive.display(precision).to_string(),
+    }
+}
+
+/// Collects text to turn into one string at the end, which stays fast where adding each part
+/// to a string would copy everything so far every time.
+#[derive(LoxClass)]
+#[lox(methods(append = 1, toString = 0))]
+struct StringBuilder {
+    #[lox(skip)]
+    text: String,
+    /// That of the interpreter that made the builder, for appending numbers.
+    #[lox(skip)]
+    precision: Option<usize>,
+}
+
+impl StringBuilder {
+    fn append(&mut self, args: Vec<Value>) -> Result<Value, InterpretError> {
+        self.text.push_str(&text(&args[0], self.precision));
+        Ok(().into())
+    }
+
+    #[allow(non_snake_case)]
+    fn toString(&mut self, _: Vec<Value>) -> Result<Value, InterpretError> {
+        Ok(self.text.as_str().into())
+    }
+}
+
+/// What `help` prints for `value`: a heading saying how to call it, then its documentation,
+/// then the same for a class's methods.
+fn help(value: &Value) -> Result<String, InterpretError> {
+    let mut text = String::new();
+    match &value.primitive {
+        Primitive::Callable(callable) => {
+            let signature = stmt::signature(&callable.name, &callable.params, &callable.signature);
+            help_entry(
+                &mut text,
+                &format!("fun {}", signature),
+                callable.signature.doc.as_deref(),
+                "",
+            );
+        }
+        Primitive::Class(class) => {
+            help_entry(
+                &mut text,
+                &format!("class {}", class.name.lexeme),
+                class.doc.as_deref(),
+                "",
+            );
+            for method in class.methods.iter() {
+                text.push('\n');
+                let heading = stmt::signature(&method.name, &method.params, &method.signature);
+                help_entry(&mut text, &heading, method.signature.doc.as_deref(), "    ");
+            }
+        }
+        Primitive::Native(native) => {
+            let plural = if native.arity == 1 { "" } else { "s" };
+            let doc = format!("Built in, taking {} argument{}.", native.arity, plural);
+            help_entry(
+                &mut text,
+                &format!("<native fn> {}", native.name),
+                Some(&doc),
+                "",
+            );
+        }
+        primitive => {
+            return Err(InterpretError::new(
+                ErrorKind::TypeError,
+                format!("No help for {}; pass a function or class.", primitive),
+                value.token.clone(),
+            ))
+        }
+    }
+    Ok(text)
+}
+
+fn help_entry(text: &mut String, heading: &str, doc: Option<&str>, indent: &str) {
+    text.push_str(&format!("{}{}\n", indent, heading));
+    for line in doc.iter().flat_map(|doc| doc.lines()) {
+        // Blank lines between paragraphs stay blank.
+        let line = format!("{}    {}", indent, line);
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+}
+
+impl Interpreter {
+    /// Makes `args` visible to the script through the `args()` native.
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.define_native("args", 0, move |_, _| Ok(args.clone().into()));
+    }
+}
+
+fn native_token(name: &str) -> Token {
+    Token::new(TokenType::IDENTIFIER, name.to_string(), 0)
+}
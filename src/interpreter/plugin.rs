@@ -0,0 +1,52 @@
+use super::{ErrorKind, InterpretError, Interpreter};
+use crate::token::{Token, TokenType};
+use std::path::Path;
+
+/// Symbol every plugin must export. It receives the interpreter and registers its natives
+/// with `Interpreter::define_native`. Plugins share Rust types with the interpreter, so they
+/// must be built with the same compiler and the same version of this crate.
+pub const PLUGIN_REGISTER_SYMBOL: &[u8] = b"lox_plugin_register";
+
+pub type PluginRegister = unsafe fn(&mut Interpreter);
+
+impl Interpreter {
+    /// Loads a shared library and runs its `lox_plugin_register` function.
+    pub fn load_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<(), InterpretError> {
+        let path = path.as_ref();
+        let token = Token::new(TokenType::IDENTIFIER, path.display().to_string(), 0);
+        let error = |e: libloading::Error| {
+            InterpretError::new(
+                ErrorKind::NativeError,
+                format!("Could not load plugin '{}': {}", path.display(), e),
+                token.clone(),
+            )
+        };
+        unsafe {
+            let library = libloading::Library::new(path).map_err(error)?;
+            let register = library
+                .get::<PluginRegister>(PLUGIN_REGISTER_SYMBOL)
+                .map_err(error)?;
+            register(self);
+            // Natives registered by the plugin point into the library's code, so it has to
+            // stay loaded for as long as any of them might be called.
+            std::mem::forget(library);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::environment::Environment;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn test_missing_plugin() {
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())));
+        let err = interpreter.load_plugin("does/not/exist.so").unwrap_err();
+        assert!(err
+            .message
+            .starts_with("Could not load plugin 'does/not/exist.so'"));
+    }
+}
@@ -0,0 +1,394 @@
+pub mod convert;
+pub mod coverage;
+pub mod diagnostic;
+pub mod embed;
+pub mod expr;
+pub mod ffi;
+pub mod fold;
+pub mod format;
+pub mod fuzz;
+pub mod highlight;
+pub mod host;
+pub mod interpreter;
+pub mod lint;
+pub mod metrics;
+pub mod parser;
+pub mod pretty;
+pub mod primitive;
+pub mod reload;
+pub mod resolver;
+pub mod scanner;
+pub mod session;
+pub mod stmt;
+pub mod token;
+pub mod transpile;
+pub mod typecheck;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// Lets `#[derive(LoxClass)]`, which names this crate, be used inside it too.
+extern crate self as slow_lox;
+
+pub use embed::{Lox, LoxError};
+pub use expr::Value;
+pub use host::LoxClass;
+pub use interpreter::{ErrorKind, InterpretError, Interpreter};
+pub use slow_lox_derive::LoxClass;
+
+use diagnostic::with_source;
+use expr::Expr;
+use parser::Parser;
+use primitive::Primitive;
+use resolver::Resolver;
+use scanner::Scanner;
+use stmt::Stmt;
+use token::{Token, TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    CompileError,
+    RuntimeError,
+}
+
+/// Scans, parses, resolves and executes `source` against `interpreter`, reporting any
+/// errors along the way. Runtime errors don't stop the remaining top-level statements.
+pub fn run(source: String, interpreter: &mut Interpreter) -> Status {
+    with_source(&source.clone(), || {
+        execute(compile(source, interpreter, false), interpreter, false)
+    })
+}
+
+/// Like `run`, but for a line typed at the REPL: a missing `;` at the very end is allowed, and
+/// the value of a bare expression is printed and bound to `_`.
+pub fn run_interactive(source: String, interpreter: &mut Interpreter) -> Status {
+    with_source(&source.clone(), || {
+        execute(compile(source, interpreter, true), interpreter, true)
+    })
+}
+
+/// Evaluates `source` as a single expression and returns its value, reporting any errors.
+pub fn evaluate(source: String, interpreter: &mut Interpreter) -> Option<Value> {
+    with_source(&source.clone(), || evaluate_expr(source, interpreter))
+}
+
+fn evaluate_expr(source: String, interpreter: &mut Interpreter) -> Option<Value> {
+    let mut stmts = compile(source, interpreter, true)?;
+    let expr = match (stmts.pop(), stmts.is_empty()) {
+        (Some(Stmt::Expr(expr)), true) => expr,
+        _ => {
+            let start = Token::new(TokenType::EOF, String::new(), 1);
+            interpreter.report(&start, "Expected a single expression.");
+            return None;
+        }
+    };
+    interpreter.clear_interrupt();
+    match interpreter.interpret_expr(expr) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            interpreter.report_error(&e);
+            None
+        }
+    }
+}
+
+fn execute(stmts: Option<Vec<Stmt>>, interpreter: &mut Interpreter, interactive: bool) -> Status {
+    let Some(stmts) = stmts else {
+        return Status::CompileError;
+    };
+    // A Ctrl-C that arrived while nothing was running shouldn't cancel this run.
+    interpreter.clear_interrupt();
+    if let Some(coverage) = &mut interpreter.coverage {
+        coverage.add_program(&stmts);
+    }
+    let mut status = Status::Ok;
+    for stmt in stmts {
+        let result = match stmt {
+            // Assignments, to variables and fields alike, already say what they set, so they
+            // aren't echoed.
+            Stmt::Expr(expr) if interactive && !matches!(expr, Expr::Assign(_) | Expr::Set(_)) => {
+                interpreter.interpret_expr(expr).map(|value| {
+                    if value.primitive != Primitive::Nil {
+                        let shown = pretty::pretty(&value.primitive, interpreter.precision());
+                        let _ = writeln!(interpreter.stdout.borrow_mut(), "{}", shown);
+                        interpreter.define(String::from("_"), value);
+                    }
+                })
+            }
+            stmt => interpreter.interpret(stmt),
+        };
+        if let Err(e) = result {
+            interpreter.report_error(&e);
+            status = Status::RuntimeError;
+            if interpreter.is_interrupted() {
+                break;
+            }
+        }
+    }
+    status
+}
+
+/// Scans, parses and resolves `source` without executing anything.
+pub fn check(source: String, interpreter: &mut Interpreter) -> Status {
+    with_source(&source.clone(), || {
+        match compile(source, interpreter, false) {
+            Some(_) => Status::Ok,
+            None => Status::CompileError,
+        }
+    })
+}
+
+/// Whether `source` reads like the start of a longer REPL entry: it ends inside a string or
+/// comment, leaves brackets open, stops partway through a statement or ends with a doc comment.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut scanner = Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens();
+    if scanner.unterminated() {
+        return true;
+    }
+    // A doc comment waits for the declaration it documents.
+    let last_line = tokens
+        .iter()
+        .rfind(|token| token.token_type != TokenType::EOF)
+        .map(|token| token.line);
+    let doc_line = scanner
+        .comments()
+        .last()
+        .filter(|comment| comment.text.starts_with("///"))
+        .map(|comment| comment.line);
+    if doc_line.is_some() && doc_line > last_line {
+        return true;
+    }
+    let mut depth = 0;
+    for token in &tokens {
+        match token.token_type {
+            TokenType::LEFT_PAREN | TokenType::LEFT_BRACE => depth += 1,
+            TokenType::RIGHT_PAREN | TokenType::RIGHT_BRACE => depth -= 1,
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+    matches!(
+        Parser::new(tokens).interactive().parse(),
+        Err(errors) if errors[0].token.token_type == TokenType::EOF
+    )
+}
+
+fn compile(source: String, interpreter: &mut Interpreter, interactive: bool) -> Option<Vec<Stmt>> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens).with_docs(scanner.comments());
+    if interactive {
+        parser = parser.interactive();
+    }
+    // Parse even after a lexical error so that all syntax errors get reported in one go.
+    let stmts = parser.parse();
+    for e in scanner.errors() {
+        interpreter.session.error(&e.location, &e.message);
+    }
+    let stmts = match stmts {
+        Ok(stmts) => stmts,
+        Err(errors) => {
+            for e in errors {
+                interpreter.report(&e.token, &e.message);
+            }
+            return None;
+        }
+    };
+    if !scanner.errors().is_empty() {
+        return None;
+    }
+    let errors = interpreter.session.errors();
+    if let Err(e) = Resolver::new(interpreter).resolve(stmts.clone()) {
+        interpreter.report_error(&e);
+        return None;
+    }
+    for e in typecheck::check(&stmts) {
+        match e.rule {
+            Some(rule) => interpreter
+                .session
+                .warning(rule, &(&e.token).into(), &e.message),
+            None => interpreter.report(&e.token, &e.message),
+        }
+    }
+    // Denied warnings and type errors are reported as errors but don't stop the resolver.
+    if interpreter.session.errors() > errors {
+        return None;
+    }
+    Some(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Runs `source` in a fresh interpreter that `prepare` has set up, and returns its status
+    /// along with what it printed and the diagnostics it reported.
+    fn run_with(source: &str, prepare: impl FnOnce(&mut Interpreter)) -> (Status, String, String) {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(
+            interpreter::environment::Environment::global(),
+        )))
+        .with_stdout(stdout.clone())
+        .with_stderr(stderr.clone());
+        prepare(&mut interpreter);
+        let status = run(source.to_string(), &mut interpreter);
+        let output = String::from_utf8(stdout.borrow().clone()).unwrap();
+        let errors = String::from_utf8(stderr.borrow().clone()).unwrap();
+        (status, output, errors)
+    }
+
+    /// What `source` prints when run in a fresh interpreter.
+    fn output_of(source: &str) -> String {
+        run_with(source, |_| {}).1
+    }
+
+    /// The diagnostics reported by `source`, which has to fail, in a fresh interpreter.
+    fn errors_of(source: &str) -> String {
+        let (status, _, errors) = run_with(source, |_| {});
+        assert_ne!(status, Status::Ok);
+        errors
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("fun f() {"));
+        assert!(is_incomplete("print (1 +"));
+        assert!(is_incomplete("print \"abc"));
+        assert!(is_incomplete("var x ="));
+        assert!(is_incomplete("/// Adds one."));
+        assert!(!is_incomplete("print 1"));
+        assert!(!is_incomplete("fun f() { return 1; }"));
+        assert!(!is_incomplete("print 1 print 2"));
+        assert!(!is_incomplete("}"));
+    }
+
+    #[test]
+    fn test_repl_echo() {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(
+            interpreter::environment::Environment::global(),
+        )))
+        .with_stdout(stdout.clone());
+        let lines = [
+            "1 + 2",
+            "_ * 10",
+            "_",
+            "nil",
+            "var x = 1",
+            "x = 2",
+            "class A {}",
+            "var a = A()",
+            "a.n = 3",
+            "a.n",
+        ];
+        for line in lines {
+            run_interactive(line.to_string(), &mut interpreter);
+        }
+        assert_eq!(
+            String::from_utf8(stdout.borrow().clone()).unwrap(),
+            "3\n30\n30\n3\n"
+        );
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(
+            output_of(
+                "/// Splits `a`.\n///\n/// Into two.\nfun split(a: number): number {}\n\
+                 /// A point.\nclass P {\n  /// Its length.\n  len() {}\n}\n\
+                 help(split); help(P); help(clock);"
+            ),
+            "fun split(a: number): number\n    Splits `a`.\n\n    Into two.\n\
+             class P\n    A point.\n\n    len()\n        Its length.\n\
+             <native fn> clock\n    Built in, taking 0 arguments.\n"
+        );
+    }
+
+    #[test]
+    fn test_string_builder() {
+        let (_, output, _) = run_with(
+            "var b = StringBuilder(); for (var i = 0; i < 3; i = i + 1) b.append(i);\n\
+             b.append(\"!\"); print b.toString(); print join(args(), \", \");",
+            |interpreter| interpreter.set_script_args(vec![String::from("a"), String::from("b")]),
+        );
+        assert_eq!(output, "\"012!\"\n\"a, b\"\n");
+    }
+
+    #[test]
+    fn test_number_formatting() {
+        assert_eq!(
+            output_of(
+                "print toFixed(2.5, 2); print toFixed(0 - 0.001, 2); print toFixed(1.5, 0);\n\
+                 print formatNumber(1234567.891, 2, \",\"); print formatNumber(0 - 999, 0, \",\");\n\
+                 print toFixed(1, 0.5);"
+            ),
+            "\"2.50\"\n\"0.00\"\n\"2\"\n\"1,234,567.89\"\n\"-999\"\n"
+        );
+    }
+
+    #[test]
+    fn test_caller_info() {
+        assert_eq!(
+            output_of(
+                "fun log() {\n  var here, outer = callerInfo();\n  \
+                 print here.caller + \":\" + str(here.line);\n  \
+                 print outer.caller + \":\" + str(outer.line);\n}\n\
+                 fun main() {\n  log();\n}\nmain();\nprint callerInfo();"
+            ),
+            "\"main:7\"\n\"script:9\"\n[]\n"
+        );
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(
+            interpreter::environment::Environment::global(),
+        )))
+        .with_stderr(Rc::new(RefCell::new(std::io::sink())));
+        run(String::from("class P {} fun f(a, b) {}"), &mut interpreter);
+        let describe = |source: &str, interpreter: &mut Interpreter| {
+            evaluate(source.to_string(), interpreter)
+                .map(|value| value.primitive.type_description())
+        };
+        assert_eq!(describe("1 + 2", &mut interpreter).unwrap(), "number");
+        assert_eq!(describe("f", &mut interpreter).unwrap(), "function/2");
+        assert_eq!(describe("P()", &mut interpreter).unwrap(), "instance of P");
+        assert!(describe("var x = 1;", &mut interpreter).is_none());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let source = "fun f() { return 1; print 2; }";
+        let (status, _, errors) = run_with(source, |_| {});
+        assert_eq!(status, Status::Ok);
+        assert!(
+            errors.starts_with("warning[unreachable-code]: Code after 'return' is unreachable.")
+        );
+        let (status, ..) = run_with(source, |interpreter| {
+            interpreter.session.deny_warnings = true
+        });
+        assert_eq!(status, Status::CompileError);
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let underlines = |errors: String| {
+            errors
+                .lines()
+                .filter(|line| line.contains('^'))
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        let errors = errors_of("var a = 1;\nvar b = \"x\";\nprint 2 * ((a) - b);");
+        assert!(errors.starts_with("error[E101]: Operands must be two numbers"));
+        assert_eq!(underlines(errors), vec!["  |            ^^^^^^^"]);
+        let errors = errors_of("fun f(x) {}\nprint f(1, (2));");
+        assert!(errors.starts_with("error[E200]: Expected 1 arguments but got 2."));
+        assert_eq!(underlines(errors), vec!["  |       ^^^^^^^^^"]);
+    }
+}
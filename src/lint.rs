@@ -0,0 +1,132 @@
+//! Lint rules: the resolver's and type checker's warnings, each named so that it can be turned off, left as a
+//! warning or made an error.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnreachableCode,
+    MixedEquality,
+    UnusedLocal,
+    Shadowing,
+    ConstantCondition,
+    TypeMismatch,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnreachableCode,
+        Rule::MixedEquality,
+        Rule::UnusedLocal,
+        Rule::Shadowing,
+        Rule::ConstantCondition,
+        Rule::TypeMismatch,
+    ];
+
+    /// The name used on the command line and in diagnostics, e.g. `unreachable-code`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::MixedEquality => "mixed-equality",
+            Rule::UnusedLocal => "unused-local",
+            Rule::Shadowing => "shadowing",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::TypeMismatch => "type-mismatch",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Rule::UnreachableCode => "statements after a return that can never run",
+            Rule::MixedEquality => "== or != between literals of different types",
+            Rule::UnusedLocal => "local variables, functions and classes that are never read",
+            Rule::Shadowing => "declarations that hide one from an enclosing scope",
+            Rule::ConstantCondition => "if, while and ?: conditions that are always the same",
+            Rule::TypeMismatch => {
+                "operations that can't work on the types values are known to have"
+            }
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == name)
+            .ok_or_else(|| format!("unknown lint rule '{}'", name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The level each rule is reported at. Rules that haven't been set warn.
+#[derive(Debug, Clone, Default)]
+pub struct Lints {
+    levels: HashMap<Rule, Level>,
+}
+
+impl Lints {
+    pub fn set(&mut self, rule: Rule, level: Level) {
+        self.levels.insert(rule, level);
+    }
+
+    pub fn level(&self, rule: Rule) -> Level {
+        self.levels.get(&rule).copied().unwrap_or(Level::Warn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interpreter::{environment::Environment, Interpreter},
+        Status,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn test_rule_names() {
+        for rule in Rule::ALL {
+            assert_eq!(rule.name().parse::<Rule>(), Ok(rule));
+        }
+        assert!("nope".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_mixed_equality() {
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stderr(stderr.clone());
+        let source = "print 1 == \"1\"; print nil != false; print 1 == 2; print a == \"a\";";
+        assert_eq!(
+            crate::check(source.to_string(), &mut interpreter),
+            Status::Ok
+        );
+        let stderr = String::from_utf8(stderr.borrow().clone()).unwrap();
+        let messages = stderr
+            .lines()
+            .filter(|line| line.starts_with("warning"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "warning[mixed-equality]: Comparing a number with a string using '==' is always false.",
+                "warning[mixed-equality]: Comparing a nil with a boolean using '!=' is always true.",
+            ]
+        );
+    }
+}
@@ -1,97 +1,814 @@
-use std::{cell::RefCell, io::Write, rc::Rc};
+use std::{
+    cell::RefCell,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
-use crate::resolver::Resolver;
-use interpreter::environment::Environment;
+use clap::{Args, Parser as _, Subcommand, ValueEnum};
+use config::{ColorChoice, Config};
+use slow_lox::{
+    coverage::Coverage,
+    highlight,
+    interpreter::{environment::Environment, DivisionByZero, Interpreter, Trace},
+    lint::{Level, Rule},
+    parser::Parser,
+    reload::HotReload,
+    resolver::Resolver,
+    scanner::{self, Scanner},
+    session::Session,
+    stmt::{self, Stmt},
+    transpile, Status,
+};
 
-mod expr;
-mod interpreter;
-mod parser;
-mod primitive;
-mod resolver;
-mod scanner;
-mod stmt;
-mod token;
+mod config;
+mod manifest;
+mod repl;
+mod testing;
 
-static mut HAD_ERROR: bool = false;
-static mut HAD_RUNTIME_ERROR: bool = false;
+/// A tree-walking interpreter for Lox.
+#[derive(clap::Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Native library to load before running anything; may be repeated.
+    #[arg(long, value_name = "LIBRARY", global = true)]
+    plugin: Vec<String>,
+
+    /// Lox file to run before the script or the REPL's first prompt, so its definitions are
+    /// there to use; may be repeated. Adds to the `preload` setting in ~/.loxrc, which only
+    /// applies to the REPL.
+    #[arg(long, value_name = "FILE", global = true)]
+    preload: Vec<String>,
+
+    /// Settings file to use instead of ~/.loxrc.
+    #[arg(long, value_name = "FILE", global = true, conflicts_with = "no_config")]
+    config: Option<PathBuf>,
+
+    /// When to color diagnostics; overrides the `color` setting in ~/.loxrc.
+    #[arg(long, value_enum, value_name = "WHEN", global = true)]
+    color: Option<ColorChoice>,
+
+    /// Ignore ~/.loxrc.
+    #[arg(long, global = true)]
+    no_config: bool,
+
+    /// Round printed numbers to this many decimal places; overrides the `precision` setting
+    /// in ~/.loxrc.
+    #[arg(long, value_name = "DIGITS", global = true)]
+    precision: Option<usize>,
+
+    /// What dividing by zero does.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = DivByZero::Ieee, global = true)]
+    div_by_zero: DivByZero,
+
+    /// Seed `random` and make `clock` start at 0 and advance a microsecond per statement run
+    /// instead of reading the time, so that runs repeat exactly.
+    #[arg(long, value_name = "N", global = true)]
+    seed: Option<u64>,
+
+    /// Treat warnings as errors.
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    /// Treat shadowing an enclosing scope's variable as an error.
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Log what the interpreter is doing to stderr; repeat for more detail. RUST_LOG works
+    /// too.
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a script, the project in the current directory, or the program from stdin.
+    Run(RunArgs),
+    /// Start an interactive session.
+    Repl {
+        /// Script saved with :save to run before the first prompt.
+        #[arg(long, value_name = "FILE")]
+        replay: Option<String>,
+    },
+    /// Parse and resolve a script without running it.
+    Check {
+        /// Script or project directory to check; `-` reads stdin, and so does no argument
+        /// outside a project.
+        script: Option<String>,
+    },
+    /// Run the `.lox` files under a directory and check their `// expect:` comments.
+    Test {
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Write an lcov report covering every test to FILE and print a summary.
+        #[arg(long, value_name = "FILE")]
+        coverage: Option<PathBuf>,
+        /// Also run each test with another Lox, such as "java -jar jlox.jar", and fail where
+        /// what it prints or exits with differs.
+        #[arg(long, value_name = "COMMAND")]
+        reference: Option<String>,
+    },
+    /// Reformat scripts in place, or stdin to stdout when none are given.
+    Fmt {
+        files: Vec<String>,
+        /// Don't write anything; list the files that would change and fail if there are any.
+        #[arg(long)]
+        check: bool,
+    },
+    /// Report suspicious code in scripts, or stdin when none are given, without running them.
+    Lint(LintArgs),
+    /// Print a script with its syntax highlighted, or stdin when none is given.
+    Highlight {
+        script: Option<String>,
+        #[arg(long, value_enum, default_value_t = HighlightFormat::Ansi)]
+        format: HighlightFormat,
+    },
+    /// Translate a script into another language, or stdin when none is given, and print it.
+    Transpile {
+        script: Option<String>,
+        #[arg(long, value_enum, default_value_t = Target::Js)]
+        target: Target,
+    },
+    /// Run a script repeatedly and report how long it took.
+    Bench {
+        script: String,
+        /// Number of timed runs.
+        #[arg(short = 'n', long, default_value_t = 10)]
+        iterations: u32,
+    },
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Scripts to run, in order and sharing their globals; `-` reads stdin. A directory runs
+    /// the project its lox.toml describes, or else its main.lox. With none, the current
+    /// directory's lox.toml says what to run, if it has one.
+    scripts: Vec<String>,
+
+    /// Run the script again whenever it changes, until interrupted.
+    #[arg(
+        long,
+        requires = "scripts",
+        conflicts_with_all = ["dump_tokens", "dump_ast", "dump_symbols"]
+    )]
+    watch: bool,
+
+    /// With --watch, redefine only the functions and classes that changed instead of running
+    /// the whole script again, keeping its globals.
+    #[arg(long, requires = "watch")]
+    hot: bool,
+
+    /// Write an lcov report of the lines that ran to FILE and print a summary.
+    #[arg(long, value_name = "FILE", conflicts_with = "watch")]
+    coverage: Option<PathBuf>,
+
+    /// Print counts of the statements, calls, allocations and scopes the script ran through,
+    /// and how deep its calls went, once it finishes.
+    #[arg(long, conflicts_with = "watch")]
+    stats: bool,
+
+    /// Stop the script with an error if it's still running after SECONDS, and exit with 124.
+    #[arg(long, value_name = "SECONDS", conflicts_with = "watch")]
+    timeout: Option<u64>,
+
+    /// Print the scanner's tokens instead of running.
+    #[arg(long, conflicts_with_all = ["dump_ast", "dump_symbols"])]
+    dump_tokens: bool,
+
+    /// Print the parsed syntax tree instead of running.
+    #[arg(long, conflicts_with = "dump_symbols")]
+    dump_ast: bool,
+
+    /// Print the resolver's scopes and the scope each variable resolved to instead of running.
+    #[arg(long)]
+    dump_symbols: bool,
+
+    /// Log execution to stderr: each statement, or with `expressions` every value as well.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "statements"
+    )]
+    trace: Option<TraceLevel>,
+
+    /// Output format for --dump-ast.
+    #[arg(long, value_enum, default_value_t = AstFormat::Sexpr, requires = "dump_ast")]
+    format: AstFormat,
+
+    /// Arguments passed to the script through args().
+    #[arg(last = true)]
+    args: Vec<String>,
+}
+
+#[derive(Args)]
+struct LintArgs {
+    files: Vec<String>,
+    /// Don't report RULE; may be repeated.
+    #[arg(long, value_name = "RULE")]
+    allow: Vec<Rule>,
+    /// Report RULE as a warning; may be repeated.
+    #[arg(long, value_name = "RULE")]
+    warn: Vec<Rule>,
+    /// Report RULE as an error; may be repeated.
+    #[arg(long, value_name = "RULE")]
+    deny: Vec<Rule>,
+    /// Print each diagnostic to stdout as a line of JSON.
+    #[arg(long)]
+    json: bool,
+    /// List the rules and exit.
+    #[arg(long)]
+    rules: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum TraceLevel {
+    Statements,
+    Expressions,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum DivByZero {
+    /// Stop with a runtime error.
+    Error,
+    /// Give infinity or NaN.
+    Ieee,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum HighlightFormat {
+    Ansi,
+    Html,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Target {
+    /// JavaScript, with a small runtime, for browsers and Node.
+    Js,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum AstFormat {
+    Sexpr,
+    Json,
+}
 
 fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    if args.len() >= 2 {
-        run_file(args[1].clone());
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        // --help and --version land here too and aren't usage errors.
+        std::process::exit(if e.use_stderr() { 64 } else { 0 });
+    });
+    init_logging(cli.verbose);
+    let mut config = if cli.no_config {
+        Config::default()
     } else {
-        run_prompt();
+        Config::load(cli.config.as_deref())
+    };
+    slow_lox::diagnostic::set_color(cli.color.unwrap_or(config.color).enabled());
+    let plugins = [config.plugins.clone(), cli.plugin].concat();
+    config.preload.extend(cli.preload.iter().cloned());
+    let precision = cli.precision.or(config.precision);
+    let new_interpreter = || {
+        let mut interpreter = new_interpreter(&plugins);
+        if let Some(seed) = cli.seed {
+            interpreter.set_seed(seed);
+        }
+        interpreter.set_precision(precision);
+        interpreter.set_division_by_zero(match cli.div_by_zero {
+            DivByZero::Error => DivisionByZero::Error,
+            DivByZero::Ieee => DivisionByZero::Ieee,
+        });
+        interpreter.session.deny_warnings = cli.deny_warnings;
+        if cli.strict {
+            interpreter.session.lints.set(Rule::Shadowing, Level::Deny);
+        }
+        interpreter
+    };
+    let mut interpreter = new_interpreter();
+    match cli.command {
+        Some(Command::Run(args)) => run_command(args, &cli.preload, &mut interpreter, &config),
+        Some(Command::Repl { replay }) => repl::run(&mut interpreter, &config, replay.as_deref()),
+        Some(Command::Check { script }) => match script.map(project_scripts) {
+            Some(scripts) => {
+                for script in scripts {
+                    let source = read_source(Some(&script), &mut interpreter);
+                    check(source, &mut interpreter);
+                }
+            }
+            None => {
+                let scripts = manifest_scripts(Path::new(".")).unwrap_or_default();
+                if scripts.is_empty() {
+                    check(read_stdin(&mut interpreter), &mut interpreter);
+                }
+                for script in scripts {
+                    check(read_file(&script), &mut interpreter);
+                }
+            }
+        },
+        Some(Command::Test {
+            dir,
+            coverage,
+            reference,
+        }) => {
+            let passed = testing::run(
+                &dir,
+                coverage.as_deref(),
+                reference.as_deref(),
+                new_interpreter,
+            );
+            if !passed {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Fmt { files, check }) => fmt(&files, check, &mut interpreter),
+        Some(Command::Lint(args)) => lint(args, &mut interpreter),
+        Some(Command::Highlight { script, format }) => {
+            let source = read_source(script.as_deref(), &mut interpreter);
+            match format {
+                HighlightFormat::Ansi => print!("{}", highlight::ansi(&source)),
+                HighlightFormat::Html => print!("{}", highlight::html(&source)),
+            }
+        }
+        Some(Command::Transpile { script, target }) => {
+            let source = read_source(script.as_deref(), &mut interpreter);
+            check(source.clone(), &mut interpreter);
+            let stmts = parse_or_exit(source, &mut interpreter);
+            match target {
+                Target::Js => print!("{}", transpile::javascript(&stmts)),
+            }
+        }
+        Some(Command::Bench { script, iterations }) => bench(&script, iterations, new_interpreter),
+        None => run_command(cli.run, &cli.preload, &mut interpreter, &config),
     }
 }
 
-fn run_file(file_path: String) {
-    println!("Running file: {}", file_path);
-    let source =
-        std::fs::read_to_string(&file_path).expect("Something went wrong reading the file");
-    run(source);
+/// Sends the interpreter's logs to stderr, filtered by RUST_LOG unless `verbose` asks for
+/// debug (once) or trace (twice) logs.
+fn init_logging(verbose: u8) {
+    let mut builder = env_logger::Builder::from_default_env();
+    match verbose {
+        0 => {}
+        1 => {
+            builder.filter_module("slow_lox", log::LevelFilter::Debug);
+        }
+        _ => {
+            builder.filter_module("slow_lox", log::LevelFilter::Trace);
+        }
+    }
+    builder.init();
+}
 
-    if unsafe { HAD_ERROR } {
-        std::process::exit(65);
+fn new_interpreter(plugins: &[String]) -> Interpreter {
+    let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())));
+    interpreter.define_native("breakpoint", 0, |interpreter, _| {
+        repl::breakpoint(interpreter);
+        Ok(().into())
+    });
+    for plugin in plugins {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = interpreter.load_plugin(plugin) {
+            eprintln!("{}", e);
+            std::process::exit(74);
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            eprintln!("Plugins are not supported on this platform: {}", plugin);
+            std::process::exit(64);
+        }
+    }
+    interpreter
+}
+
+/// Runs the scripts `args` names, after the files in `preload`, or starts the REPL if there
+/// are none.
+fn run_command(
+    mut args: RunArgs,
+    preload: &[String],
+    interpreter: &mut Interpreter,
+    config: &Config,
+) {
+    interpreter.set_script_args(args.args);
+    args.scripts = if args.scripts.is_empty() {
+        manifest_scripts(Path::new(".")).unwrap_or_default()
+    } else {
+        args.scripts.into_iter().flat_map(project_scripts).collect()
+    };
+    let dumping = args.dump_tokens || args.dump_ast || args.dump_symbols;
+    // Piped or redirected input is a program, not an interactive session.
+    if args.scripts.is_empty() && !dumping && std::io::stdin().is_terminal() {
+        return repl::run(interpreter, config, None);
+    }
+    if let Some(level) = args.trace {
+        let trace = match level {
+            TraceLevel::Statements => Trace::Statements,
+            TraceLevel::Expressions => Trace::Expressions,
+        };
+        interpreter.set_trace(trace, Rc::new(RefCell::new(std::io::stderr())));
+    }
+    if args.watch {
+        let [script] = args.scripts.as_slice() else {
+            eprintln!("Can only watch one script.");
+            std::process::exit(64);
+        };
+        return watch(script, preload, args.hot, interpreter);
+    }
+    let scripts = if args.scripts.is_empty() {
+        vec![None]
+    } else {
+        args.scripts
+            .iter()
+            .map(|script| Some(script.as_str()))
+            .collect()
+    };
+    // The dumps are of the scripts alone.
+    let preload = if dumping { &[] } else { preload };
+    let scripts = preload
+        .iter()
+        .map(|path| Some(path.as_str()))
+        .chain(scripts)
+        .collect::<Vec<_>>();
+    let mut lcov = String::new();
+    interpreter.set_timeout(args.timeout.map(Duration::from_secs));
+    // Ctrl-C stops the script where it is and reports where that was, rather than killing
+    // the process outright.
+    #[cfg(not(target_arch = "wasm32"))]
+    if !dumping {
+        let interrupted = interpreter.interrupt_flag();
+        let _ = ctrlc::set_handler(move || {
+            interrupted.store(true, std::sync::atomic::Ordering::SeqCst)
+        });
+    }
+    for script in &scripts {
+        if let Some(script) = script.filter(|script| *script != "-") {
+            log::debug!("running {}", script);
+        }
+        let source = read_source(*script, interpreter);
+        if args.dump_tokens {
+            dump_tokens(source, interpreter);
+            continue;
+        } else if args.dump_ast {
+            dump_ast(source, args.format, interpreter);
+            continue;
+        } else if args.dump_symbols {
+            dump_symbols(source, interpreter);
+            continue;
+        }
+        if args.coverage.is_some() {
+            interpreter.coverage = Some(Coverage::default());
+        }
+        let status = slow_lox::run(source, interpreter);
+        if let Some(coverage) = &interpreter.coverage {
+            let name = script.unwrap_or("-");
+            lcov.push_str(&coverage.lcov(name));
+            eprintln!("{}: {}", name, coverage);
+        }
+        // Later files build on earlier ones, so there's no point going on past a failure.
+        if status != Status::Ok {
+            if scripts.len() > 1 {
+                eprintln!("Stopped in '{}'.", script.unwrap_or("-"));
+            }
+            break;
+        }
+    }
+    if dumping {
+        return;
+    }
+    if let Some(path) = &args.coverage {
+        write_coverage(path, &lcov);
+    }
+    if args.stats {
+        eprintln!("{}", interpreter.metrics());
     }
-    if unsafe { HAD_RUNTIME_ERROR } {
-        std::process::exit(70);
+    if interpreter.timed_out() {
+        std::process::exit(124);
     }
+    if interpreter.is_interrupted() {
+        std::process::exit(130);
+    }
+    exit_with_status(interpreter);
 }
 
-fn run_prompt() {
-    println!("Welcome to the Lox REPL!");
-    println!("Press q to quit.");
+/// What to run for `script`: the script itself, or for a directory, what its lox.toml lists or
+/// else its main.lox.
+fn project_scripts(script: String) -> Vec<String> {
+    let dir = Path::new(&script);
+    if !dir.is_dir() {
+        return vec![script];
+    }
+    if let Some(scripts) = manifest_scripts(dir) {
+        return scripts;
+    }
+    let main = dir.join("main.lox");
+    if !main.is_file() {
+        eprintln!("No main.lox or {} in '{}'.", manifest::FILE_NAME, script);
+        std::process::exit(66);
+    }
+    vec![main.to_string_lossy().into_owned()]
+}
+
+/// The scripts of the project in `dir`, or None if it has no manifest.
+fn manifest_scripts(dir: &Path) -> Option<Vec<String>> {
+    let manifest = manifest::Manifest::find(dir)?;
+    match manifest.and_then(|manifest| manifest.scripts()) {
+        Ok(scripts) => Some(scripts),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(66);
+        }
+    }
+}
+
+fn write_coverage(path: &Path, report: &str) {
+    if let Err(e) = std::fs::write(path, report) {
+        eprintln!("Could not write '{}': {}", path.display(), e);
+        std::process::exit(73);
+    }
+}
+
+/// Runs `script`, then polls it and runs it again from a clean slate each time it's saved. The
+/// files in `preload` run first each time, or just once when reloading hot.
+fn watch(script: &str, preload: &[String], hot: bool, interpreter: &mut Interpreter) {
+    if script == "-" {
+        eprintln!("Can't watch standard input.");
+        std::process::exit(64);
+    }
+    let modified = || {
+        std::fs::metadata(script)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let run_preload = |interpreter: &mut Interpreter| {
+        for path in preload {
+            slow_lox::run(read_file(path), interpreter);
+        }
+    };
+    let mut last = None;
+    let mut reload = HotReload::new();
+    if hot {
+        run_preload(interpreter);
+    }
     loop {
-        let mut input = String::new();
-        print!("> ");
-        std::io::stdout().flush().unwrap();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-        if input.to_lowercase() == "q" {
-            break;
+        let current = modified();
+        if current.is_some() && current != last {
+            // Some editors replace the file on save; if it's missing it'll be back by the next
+            // poll.
+            if let Ok(source) = std::fs::read_to_string(script) {
+                last = current;
+                let status = if hot {
+                    reload.run(source, interpreter)
+                } else {
+                    // Clear the screen so only this run's output and diagnostics are showing.
+                    print!("\x1b[2J\x1b[H");
+                    interpreter.reset();
+                    run_preload(interpreter);
+                    slow_lox::run(source, interpreter)
+                };
+                let outcome = match status {
+                    Status::Ok => "finished",
+                    Status::CompileError => "failed to compile",
+                    Status::RuntimeError => "failed",
+                };
+                println!("\n[{} {}; watching for changes]", script, outcome);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+fn read_source(script: Option<&str>, interpreter: &mut Interpreter) -> String {
+    match script {
+        None | Some("-") => read_stdin(interpreter),
+        Some(path) => read_file(path),
+    }
+}
+
+fn read_file(file_path: &str) -> String {
+    std::fs::read_to_string(file_path).unwrap_or_else(|e| {
+        eprintln!("Could not read '{}': {}", file_path, e);
+        std::process::exit(66);
+    })
+}
+
+fn read_stdin(interpreter: &mut Interpreter) -> String {
+    let mut source = String::new();
+    if let Err(e) = interpreter.stdin.borrow_mut().read_to_string(&mut source) {
+        eprintln!("Could not read program from stdin: {}", e);
+        std::process::exit(74);
+    }
+    source
+}
+
+fn dump_tokens(source: String, interpreter: &mut Interpreter) {
+    let tokens = Scanner::new(source).scan_tokens();
+    let _ = write!(
+        interpreter.stdout.borrow_mut(),
+        "{}",
+        scanner::dump_tokens(&tokens)
+    );
+}
+
+fn dump_ast(source: String, format: AstFormat, interpreter: &mut Interpreter) {
+    let stmts = parse_or_exit(source, interpreter);
+    let stdout = interpreter.stdout.clone();
+    let _ = match format {
+        AstFormat::Sexpr => stmts
+            .into_iter()
+            .try_for_each(|stmt| writeln!(stdout.borrow_mut(), "{}", stmt::print(stmt))),
+        AstFormat::Json => writeln!(stdout.borrow_mut(), "{}", stmt::to_json_all(stmts)),
+    };
+}
+
+fn dump_symbols(source: String, interpreter: &mut Interpreter) {
+    let stmts = parse_or_exit(source, interpreter);
+    let stdout = interpreter.stdout.clone();
+    let mut resolver = Resolver::new(interpreter);
+    let resolved = resolver.resolve(stmts);
+    let _ = write!(stdout.borrow_mut(), "{}", resolver.symbols());
+    if let Err(e) = resolved {
+        interpreter.report_error(&e);
+        std::process::exit(65);
+    }
+}
+
+fn parse_or_exit(source: String, interpreter: &mut Interpreter) -> Vec<Stmt> {
+    let tokens = Scanner::new(source).scan_tokens();
+    Parser::new(tokens).parse().unwrap_or_else(|errors| {
+        for e in errors {
+            interpreter.report(&e.token, &e.message);
         }
-        run(input.to_string());
-        unsafe {
-            HAD_ERROR = false;
-            HAD_RUNTIME_ERROR = false;
+        std::process::exit(65);
+    })
+}
+
+fn fmt(files: &[String], check: bool, interpreter: &mut Interpreter) {
+    if files.is_empty() {
+        let source = read_stdin(interpreter);
+        let formatted = format_source(source.clone(), interpreter).unwrap_or_else(|| {
+            std::process::exit(65);
+        });
+        if check {
+            std::process::exit(if formatted == source { 0 } else { 1 });
         }
+        print!("{}", formatted);
+        return;
+    }
+    let mut failed = false;
+    let mut unformatted = false;
+    for file in files {
+        let source = read_file(file);
+        let Some(formatted) = format_source(source.clone(), interpreter) else {
+            failed = true;
+            continue;
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("Would reformat: {}", file);
+            unformatted = true;
+        } else if let Err(e) = std::fs::write(file, formatted) {
+            eprintln!("Could not write '{}': {}", file, e);
+            std::process::exit(73);
+        }
+    }
+    if failed {
+        std::process::exit(65);
+    }
+    if unformatted {
+        std::process::exit(1);
     }
-    println!("Bye!");
 }
 
-fn run(input: String) {
-    let mut scanner = scanner::Scanner::new(input);
+/// Formats `source`, or reports why it can't be parsed.
+fn format_source(source: String, interpreter: &mut Interpreter) -> Option<String> {
+    let mut scanner = Scanner::new(source.clone());
     let tokens = scanner.scan_tokens();
-    let mut parser = crate::parser::Parser::new(tokens);
-    if let Ok(stmts) = parser.parse() {
-        let mut interpreter =
-            interpreter::Interpreter::new(Rc::new(RefCell::new(Environment::global())));
-        let mut resolver = Resolver::new(&mut interpreter);
-        if let Err(e) = resolver.resolve(stmts.clone()) {
-            error(e.token.line, &e.message);
-            unsafe {
-                HAD_ERROR = true;
-            }
-            return;
-        }
-        for stmt in stmts.into_iter() {
-            match interpreter.interpret(stmt) {
-                Ok(_) => (),
-                Err(e) => {
-                    error(e.token.line, &e.message);
-                    unsafe {
-                        HAD_ERROR = true;
-                        HAD_RUNTIME_ERROR = true;
-                    }
-                }
+    for e in scanner.errors() {
+        interpreter.session.error(&e.location, &e.message);
+    }
+    let stmts = slow_lox::diagnostic::with_source(&source, || {
+        Parser::new(tokens).parse().map_err(|errors| {
+            for e in errors {
+                interpreter.report(&e.token, &e.message);
             }
+        })
+    });
+    match stmts {
+        Ok(stmts) if scanner.errors().is_empty() => Some(slow_lox::format::format(
+            &source,
+            &stmts,
+            scanner.comments(),
+        )),
+        _ => None,
+    }
+}
+
+fn check(source: String, interpreter: &mut Interpreter) {
+    if slow_lox::check(source, interpreter) != Status::Ok {
+        std::process::exit(65);
+    }
+}
+
+/// Checks each file with the rule levels from `args`, exiting with 65 if any have errors or 1 if
+/// any have warnings. Human-readable diagnostics go to stderr under the name of their file.
+fn lint(args: LintArgs, interpreter: &mut Interpreter) {
+    if args.rules {
+        for rule in Rule::ALL {
+            println!("{:<18} {}", rule.name(), rule.description());
+        }
+        return;
+    }
+    let mut lints = interpreter.session.lints.clone();
+    for (rules, level) in [
+        (&args.allow, Level::Allow),
+        (&args.warn, Level::Warn),
+        (&args.deny, Level::Deny),
+    ] {
+        for rule in rules {
+            lints.set(*rule, level);
+        }
+    }
+    let files = if args.files.is_empty() {
+        vec![None]
+    } else {
+        args.files.iter().map(Some).collect()
+    };
+    let (mut failed, mut warned) = (false, false);
+    for file in files {
+        let source = match file {
+            Some(file) => read_file(file),
+            None => read_stdin(interpreter),
+        };
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut session = Session::new(output.clone());
+        session.deny_warnings = interpreter.session.deny_warnings;
+        session.lints = lints.clone();
+        session.json = args.json;
+        session.file = file.cloned();
+        interpreter.session = session;
+        failed |= slow_lox::check(source, interpreter) != Status::Ok;
+        warned |= interpreter.session.warnings() > 0;
+        let output = String::from_utf8_lossy(&output.borrow()).into_owned();
+        if args.json {
+            print!("{}", output);
+        } else if !output.is_empty() {
+            eprint!(
+                "{}{}",
+                file.map_or(String::new(), |file| format!("{}:\n", file)),
+                output
+            );
+        }
+    }
+    if failed {
+        std::process::exit(65);
+    }
+    if warned {
+        std::process::exit(1);
+    }
+}
+
+fn bench(script: &str, iterations: u32, new_interpreter: impl Fn() -> Interpreter) {
+    let source = read_file(script);
+    let mut timings = Vec::new();
+    for _ in 0..iterations.max(1) {
+        // A fresh interpreter per run so globals from one run can't leak into the next.
+        let mut interpreter = new_interpreter().with_stdout(Rc::new(RefCell::new(std::io::sink())));
+        let start = Instant::now();
+        let status = slow_lox::run(source.clone(), &mut interpreter);
+        timings.push(start.elapsed());
+        if status != Status::Ok {
+            std::process::exit(if status == Status::CompileError {
+                65
+            } else {
+                70
+            });
         }
     }
+    timings.sort();
+    let total: std::time::Duration = timings.iter().sum();
+    println!(
+        "{} runs: min {:?}, median {:?}, mean {:?}, max {:?}",
+        timings.len(),
+        timings[0],
+        timings[timings.len() / 2],
+        total / timings.len() as u32,
+        timings[timings.len() - 1]
+    );
 }
 
-fn error(line: usize, message: &str) {
-    fn report(line: usize, location: &str, message: &str) {
-        eprintln!("Error: [line {}] Error {}: {}", line, location, message);
+fn exit_with_status(interpreter: &Interpreter) {
+    let code = interpreter.session.exit_code();
+    if code != 0 {
+        std::process::exit(code);
     }
-    report(line, "", message);
 }
@@ -0,0 +1,176 @@
+//! `lox.toml`, the manifest that makes a directory a project, such as:
+//!
+//! ```toml
+//! entry = "src/main.lox"
+//! paths = ["lib"]
+//! prelude = ["strings.lox"]
+//!
+//! [dependencies]
+//! shapes = { path = "../shapes" }
+//! ```
+//!
+//! `lox run` and `lox check` given the project's directory, or with no script inside it, run
+//! or check the project: each dependency's project first, then the prelude files, then the entry file, all
+//! sharing their globals. Prelude files are looked up in the project directory and then in
+//! each of `paths`. Relative paths are relative to the manifest.
+
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+pub const FILE_NAME: &str = "lox.toml";
+
+#[derive(Debug, PartialEq)]
+pub struct Manifest {
+    pub root: PathBuf,
+    pub entry: PathBuf,
+    pub paths: Vec<PathBuf>,
+    pub prelude: Vec<PathBuf>,
+    /// Other projects, by name, that this one builds on.
+    pub dependencies: Vec<(String, PathBuf)>,
+}
+
+impl Manifest {
+    /// Reads the manifest in `dir`, or None if there isn't one.
+    pub fn find(dir: &Path) -> Option<Result<Manifest, String>> {
+        let path = dir.join(FILE_NAME);
+        if !path.is_file() {
+            return None;
+        }
+        Some(Manifest::load(&path))
+    }
+
+    fn load(path: &Path) -> Result<Manifest, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read '{}': {}", path.display(), e))?;
+        let root = path.parent().unwrap_or(Path::new("."));
+        Manifest::parse(&source, root).map_err(|e| format!("In '{}': {}", path.display(), e))
+    }
+
+    fn parse(source: &str, root: &Path) -> Result<Manifest, String> {
+        let table = source
+            .parse::<Table>()
+            .map_err(|e| e.message().to_string())?;
+        let mut entry = None;
+        let mut manifest = Manifest {
+            root: root.to_path_buf(),
+            entry: PathBuf::new(),
+            paths: Vec::new(),
+            prelude: Vec::new(),
+            dependencies: Vec::new(),
+        };
+        for (key, value) in table {
+            match key.as_str() {
+                "entry" => entry = Some(root.join(string(&key, value)?)),
+                "paths" => manifest.paths = paths(&key, value, root)?,
+                "prelude" => manifest.prelude = paths(&key, value, Path::new(""))?,
+                "dependencies" => {
+                    let Value::Table(dependencies) = value else {
+                        return Err(String::from("'dependencies' must be a table"));
+                    };
+                    for (name, dependency) in dependencies {
+                        let path = dependency
+                            .as_table()
+                            .and_then(|dependency| dependency.get("path"))
+                            .and_then(Value::as_str)
+                            .ok_or_else(|| format!("dependency '{}' needs a path", name))?;
+                        manifest.dependencies.push((name, root.join(path)));
+                    }
+                }
+                _ => return Err(format!("unknown setting '{}'", key)),
+            }
+        }
+        manifest.entry = entry.ok_or_else(|| String::from("'entry' is missing"))?;
+        Ok(manifest)
+    }
+
+    /// The files to run, in order: dependencies' first, then the prelude, then the entry file.
+    pub fn scripts(&self) -> Result<Vec<String>, String> {
+        let mut scripts = Vec::new();
+        self.collect(&mut scripts, &mut Vec::new())?;
+        Ok(scripts)
+    }
+
+    /// Adds this project's scripts to `scripts`, after those of its dependencies. `visiting`
+    /// holds the projects that led here, to catch dependency cycles.
+    fn collect(
+        &self,
+        scripts: &mut Vec<String>,
+        visiting: &mut Vec<PathBuf>,
+    ) -> Result<(), String> {
+        visiting.push(canonical(&self.root));
+        for (name, path) in &self.dependencies {
+            if visiting.contains(&canonical(path)) {
+                return Err(format!("dependency '{}' depends on itself", name));
+            }
+            let dependency = Manifest::find(path)
+                .ok_or_else(|| format!("dependency '{}' has no {}", name, FILE_NAME))??;
+            dependency.collect(scripts, visiting)?;
+        }
+        visiting.pop();
+        for prelude in &self.prelude {
+            let path = std::iter::once(&self.root)
+                .chain(&self.paths)
+                .map(|dir| dir.join(prelude))
+                .find(|path| path.is_file())
+                .ok_or_else(|| format!("Could not find prelude file '{}'", prelude.display()))?;
+            push_once(scripts, path);
+        }
+        push_once(scripts, self.entry.clone());
+        Ok(())
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Adds `path` unless it's already there, since two dependencies may share one of their own.
+fn push_once(scripts: &mut Vec<String>, path: PathBuf) {
+    let path = path.to_string_lossy().into_owned();
+    if !scripts.contains(&path) {
+        scripts.push(path);
+    }
+}
+
+fn string(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(format!("'{}' must be a string", key)),
+    }
+}
+
+fn paths(key: &str, value: Value, root: &Path) -> Result<Vec<PathBuf>, String> {
+    let Value::Array(values) = value else {
+        return Err(format!("'{}' must be a list of paths", key));
+    };
+    values
+        .into_iter()
+        .map(|value| string(key, value).map(|path| root.join(path)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let manifest = Manifest::parse(
+            "entry = \"main.lox\"\npaths = [\"lib\"]\nprelude = [\"p.lox\"]\n\
+             [dependencies]\nshapes = { path = \"../shapes\" }",
+            Path::new("app"),
+        )
+        .unwrap();
+        assert_eq!(manifest.entry, Path::new("app/main.lox"));
+        assert_eq!(manifest.paths, vec![PathBuf::from("app/lib")]);
+        assert_eq!(manifest.prelude, vec![PathBuf::from("p.lox")]);
+        assert_eq!(
+            manifest.dependencies,
+            vec![(String::from("shapes"), PathBuf::from("app/../shapes"))]
+        );
+        assert!(Manifest::parse("paths = []", Path::new(".")).is_err());
+        assert!(Manifest::parse("entry = \"a\"\n[dependencies]\nx = 1", Path::new(".")).is_err());
+        assert!(Manifest::parse("entry = \"a\"\nname = \"b\"", Path::new(".")).is_err());
+    }
+}
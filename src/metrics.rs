@@ -0,0 +1,26 @@
+//! Counters for how much work a program made the interpreter do.
+
+use std::fmt::Display;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metrics {
+    pub statements: usize,
+    /// Calls to Lox functions and natives.
+    pub calls: usize,
+    /// Instances, lists, functions and classes the program created.
+    pub allocations: usize,
+    /// Scopes entered: blocks, function bodies and the like.
+    pub environments: usize,
+    /// The most Lox calls that were active at once.
+    pub max_depth: usize,
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "statements executed: {}", self.statements)?;
+        writeln!(f, "function calls:      {}", self.calls)?;
+        writeln!(f, "allocations:         {}", self.allocations)?;
+        writeln!(f, "environments:        {}", self.environments)?;
+        write!(f, "max call depth:      {}", self.max_depth)
+    }
+}
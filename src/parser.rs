@@ -1,13 +1,28 @@
 use crate::expr::{
-    Assignment, Binary, Call, Expr, GetExpr, Grouping, Literal, Logical, SetExpr, Ternary, Unary,
-    Variable,
+    Assignment, Binary, Call, Expr, GetExpr, Grouping, Literal, Logical, SetExpr, Super, Ternary,
+    This, Unary, Variable,
 };
-use crate::stmt::Stmt;
+use crate::scanner::Comment;
+use crate::stmt::{Signature, Stmt};
 use crate::token::{Token, TokenType};
+use std::collections::HashMap;
+
+/// How deeply expressions and statements may nest by default: well short of where parsing, or
+/// anything else that walks the tree, would run out of a main thread's 8MB stack, even in a
+/// debug build.
+pub const MAX_DEPTH: usize = 128;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    interactive: bool,
+    /// How many expressions or statements enclose the one being parsed, and the most allowed.
+    depth: usize,
+    max_depth: usize,
+    /// Errors that didn't stop the current statement from parsing.
+    errors: Vec<ParseError>,
+    /// Doc comments by the line after them, where what they document is declared.
+    docs: HashMap<usize, String>,
 }
 
 #[derive(Debug)]
@@ -18,7 +33,47 @@ pub struct ParseError {
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            interactive: false,
+            depth: 0,
+            max_depth: MAX_DEPTH,
+            errors: Vec::new(),
+            docs: HashMap::new(),
+        }
+    }
+
+    /// Sets how deeply expressions and statements may nest before it's a syntax error.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Lets the end of input stand in for a statement's closing `;`, as typed at the REPL.
+    pub fn interactive(mut self) -> Self {
+        self.interactive = true;
+        self
+    }
+
+    /// Keeps the `///` lines among the scanner's `comments` as documentation for the function,
+    /// method or class declared on the line right after them.
+    pub fn with_docs(mut self, comments: &[Comment]) -> Self {
+        for comment in comments.iter().filter(|comment| !comment.trailing) {
+            let Some(text) = comment.text.strip_prefix("///") else {
+                continue;
+            };
+            if text.starts_with('/') {
+                continue;
+            }
+            let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+            let doc = match self.docs.remove(&comment.line) {
+                Some(doc) => format!("{}\n{}", doc, text),
+                None => text.to_string(),
+            };
+            self.docs.insert(comment.line + 1, doc);
+        }
+        self
     }
 
     fn match_token(&mut self, token_types: Vec<TokenType>) -> bool {
@@ -68,46 +123,196 @@ impl Parser {
         })
     }
 
-    fn synchronize(&mut self) {
-        self.advance();
+    fn consume_semicolon(&mut self, message: &str) -> Result<(), ParseError> {
+        if self.interactive && self.is_at_end() {
+            return Ok(());
+        }
+        self.consume(TokenType::SEMICOLON, message)?;
+        Ok(())
+    }
+
+    /// Runs `parse` one level deeper, failing instead once nesting reaches the limit.
+    fn nested<T>(
+        &mut self,
+        what: &str,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth >= self.max_depth {
+            return Err(ParseError {
+                token: self.peek(),
+                message: format!("{} too deeply nested.", what),
+            });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Skips to where the next statement probably starts: past a `;` or to a keyword that
+    /// begins one, stepping over anything in braces along the way. Inside a block, the `}`
+    /// that closes it is left for the block.
+    fn synchronize(&mut self, in_block: bool) {
+        let mut depth: usize = 0;
+        let mut skipped = false;
         while !self.is_at_end() {
-            if self.previous().token_type == TokenType::SEMICOLON {
+            let token_type = self.peek().token_type;
+            if depth == 0 {
+                if in_block && token_type == TokenType::RIGHT_BRACE {
+                    return;
+                }
+                if skipped && starts_statement(token_type) {
+                    return;
+                }
+            }
+            match token_type {
+                TokenType::LEFT_BRACE => depth += 1,
+                TokenType::RIGHT_BRACE => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.advance();
+            skipped = true;
+            if depth == 0 && token_type == TokenType::SEMICOLON {
                 return;
             }
+        }
+    }
 
+    /// Skips the rest of a method that failed to parse, up to and including its body, or to
+    /// the `}` that ends the class.
+    fn skip_method(&mut self) {
+        let mut depth = 0;
+        while !self.is_at_end() {
             match self.peek().token_type {
-                TokenType::CLASS => return,
-                TokenType::FUN => return,
-                TokenType::VAR => return,
-                TokenType::FOR => return,
-                TokenType::IF => return,
-                TokenType::WHILE => return,
-                TokenType::PRINT => return,
-                TokenType::RETURN => return,
-                _ => self.current += 1,
+                TokenType::LEFT_BRACE => depth += 1,
+                TokenType::RIGHT_BRACE if depth == 0 => return,
+                TokenType::RIGHT_BRACE => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.advance();
+                        return;
+                    }
+                }
+                _ => {}
             }
+            self.advance();
         }
     }
+
+    /// Skips the rest of an argument that failed to parse. True if that leaves a `,` or `)`
+    /// to carry on from, false if the call itself looks broken.
+    fn skip_argument(&mut self) -> bool {
+        let mut depth = 0;
+        while !self.is_at_end() {
+            match self.peek().token_type {
+                TokenType::COMMA | TokenType::RIGHT_PAREN if depth == 0 => return true,
+                TokenType::SEMICOLON | TokenType::RIGHT_BRACE if depth == 0 => return false,
+                TokenType::LEFT_PAREN | TokenType::LEFT_BRACE => depth += 1,
+                TokenType::RIGHT_PAREN | TokenType::RIGHT_BRACE => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+        false
+    }
+}
+
+/// Whether a statement can start with a token of this type, so that it's a good place to pick
+/// up again after an error.
+fn starts_statement(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::CLASS
+            | TokenType::FUN
+            | TokenType::VAR
+            | TokenType::FOR
+            | TokenType::IF
+            | TokenType::WHILE
+            | TokenType::WITH
+            | TokenType::PRINT
+            | TokenType::RETURN
+    )
+}
+
+/// The operands of the comma expressions `expr` is made of, left to right, or just `expr`.
+/// Parenthesized comma expressions are left alone.
+fn comma_operands(expr: Expr) -> Vec<Expr> {
+    match expr {
+        Expr::Binary(binary) if binary.operator.token_type == TokenType::COMMA => {
+            let mut operands = comma_operands(*binary.left);
+            operands.push(*binary.right);
+            operands
+        }
+        expr => vec![expr],
+    }
+}
+
+/// `a, b = value` and `a, b = b, a` would only assign `b` as comma expressions, so a
+/// statement of either shape unpacks the values after the `=` into all of the names instead.
+fn unpack(expr: Expr) -> Stmt {
+    let mut operands = comma_operands(expr.clone());
+    let Some(assigned) = operands
+        .iter()
+        .position(|operand| matches!(operand, Expr::Assign(_)))
+    else {
+        return Stmt::Expr(expr);
+    };
+    let mut names = Vec::new();
+    for operand in &operands[..assigned] {
+        match operand {
+            Expr::Variable(variable) => names.push(variable.name.clone()),
+            _ => return Stmt::Expr(expr),
+        }
+    }
+    if names.is_empty() {
+        return Stmt::Expr(expr);
+    }
+    let mut values = operands.split_off(assigned);
+    let Expr::Assign(assign) = values.remove(0) else {
+        return Stmt::Expr(expr);
+    };
+    names.push(assign.name);
+    values.insert(0, *assign.value);
+    Stmt::Unpack(None, names, values)
 }
 
 impl Parser {
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
+    /// Parses the whole program, recovering after each error so that every syntax error is
+    /// found in one pass. Nothing is printed; the errors come back in source order.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
             let statement = self.declaration();
             match statement {
                 Ok(statement) => statements.push(statement),
                 Err(error) => {
-                    crate::error(error.token.line, error.message.as_str());
-                    self.synchronize();
-                    continue;
+                    log::trace!(
+                        "syntax error at line {}: {}",
+                        error.token.line,
+                        error.message
+                    );
+                    self.errors.push(error);
+                    self.synchronize(false);
                 }
             }
         }
-        Ok(statements)
+        log::debug!(
+            "parsed {} statements with {} errors",
+            statements.len(),
+            self.errors.len()
+        );
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
     fn declaration(&mut self) -> Result<Stmt, ParseError> {
+        self.nested("Statement", Self::nested_declaration)
+    }
+
+    fn nested_declaration(&mut self) -> Result<Stmt, ParseError> {
         if self.match_token(vec![TokenType::FUN]) {
             return self.func_declaration("function".to_string());
         }
@@ -131,17 +336,37 @@ impl Parser {
     }
 
     fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let doc = self.docs.get(&self.previous().line).cloned();
         let name = self.consume(TokenType::IDENTIFIER, "Expect class name.")?;
+        let superclass = if self.match_token(vec![TokenType::LESS]) {
+            let name = self.consume(TokenType::IDENTIFIER, "Expect superclass name.")?;
+            Some(Variable { name })
+        } else {
+            None
+        };
         self.consume(TokenType::LEFT_BRACE, "Expect '{' before class body.")?;
         let mut methods = Vec::new();
         while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
-            methods.push(self.func_declaration("method".to_string())?);
+            match self.func_declaration("method".to_string()) {
+                Ok(method) => methods.push(method),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.skip_method();
+                }
+            }
         }
         self.consume(TokenType::RIGHT_BRACE, "Expect '}' after class body.")?;
-        Ok(Stmt::Class(name, methods))
+        Ok(Stmt::Class(name, superclass, methods, doc))
     }
 
     fn func_declaration(&mut self, kind: String) -> Result<Stmt, ParseError> {
+        // Methods start with their name, functions with `fun`.
+        let start = if kind == "method" {
+            self.peek()
+        } else {
+            self.previous()
+        };
+        let doc = self.docs.get(&start.line).cloned();
         let name = self.consume(
             TokenType::IDENTIFIER,
             format!("Expect {} name.", kind).as_str(),
@@ -151,6 +376,10 @@ impl Parser {
             format!("Expect '(' after {} name.", kind).as_str(),
         )?;
         let mut parameters = Vec::new();
+        let mut signature = Signature {
+            doc,
+            ..Signature::default()
+        };
         if !self.check(TokenType::RIGHT_PAREN) {
             loop {
                 if parameters.len() >= 255 {
@@ -160,22 +389,51 @@ impl Parser {
                     });
                 }
                 parameters.push(self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?);
+                signature.parameters.push(self.annotation()?);
                 if !self.match_token(vec![TokenType::COMMA]) {
                     break;
                 }
             }
         }
         self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.")?;
+        signature.returns = self.annotation()?;
         self.consume(
             TokenType::LEFT_BRACE,
             format!("Expect '{{' before {} body.", kind).as_str(),
         )?;
         let body = self.block()?;
-        Ok(Stmt::Function(name, parameters, body))
+        Ok(Stmt::Function(name, parameters, body, signature))
+    }
+
+    /// The type name after a `:`, if there is one.
+    fn annotation(&mut self) -> Result<Option<Token>, ParseError> {
+        if !self.match_token(vec![TokenType::COLON]) {
+            return Ok(None);
+        }
+        if self.match_token(vec![TokenType::IDENTIFIER, TokenType::NIL]) {
+            return Ok(Some(self.previous()));
+        }
+        Err(ParseError {
+            token: self.peek(),
+            message: "Expect type name after ':'.".to_string(),
+        })
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
         let name = self.consume(TokenType::IDENTIFIER, "Expect variable name.")?;
+        if self.check(TokenType::COMMA) {
+            let mut names = vec![name];
+            while self.match_token(vec![TokenType::COMMA]) {
+                names.push(self.consume(TokenType::IDENTIFIER, "Expect variable name.")?);
+            }
+            self.consume(TokenType::EQUAL, "Expect '=' after variable names.")?;
+            // Like `return`, several values can be given as well as one to unpack.
+            let values = comma_operands(self.expression()?);
+            self.consume_semicolon("Expect ';' after value")?;
+            return Ok(Stmt::Unpack(Some(keyword), names, values));
+        }
+        let annotation = self.annotation()?;
 
         let initializer = if self.match_token(vec![TokenType::EQUAL]) {
             Some(self.expression()?)
@@ -183,9 +441,9 @@ impl Parser {
             None
         };
 
-        self.consume(TokenType::SEMICOLON, "Expect ';' after value")?;
+        self.consume_semicolon("Expect ';' after value")?;
 
-        Ok(Stmt::Var(name, initializer))
+        Ok(Stmt::Var(name, initializer, annotation))
     }
 
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
@@ -199,12 +457,9 @@ impl Parser {
             Some(self.expression_statement()?)
         };
 
-        let mut condition = if !self.check(TokenType::SEMICOLON) {
+        let condition = if !self.check(TokenType::SEMICOLON) {
             Some(self.expression()?)
         } else {
-            // Expr::Literal(Literal {
-            //    value: Token { token_type: TokenType::TRUE, lexeme: "true".to_string(), line: 0 }
-            // })
             None
         };
 
@@ -219,29 +474,7 @@ impl Parser {
 
         let mut body = self.statement()?;
 
-        if let Some(increment) = increment {
-            body = Stmt::Block(vec![
-                body,
-                match increment {
-                    Expr::Assign(assignment) => {
-                        Stmt::Assign(assignment.name.clone(), Expr::Assign(assignment))
-                    }
-                    _ => Stmt::Expr(increment),
-                },
-            ]);
-        }
-
-        if condition.is_none() {
-            condition = Some(Expr::Literal(Literal {
-                value: Token {
-                    token_type: TokenType::TRUE,
-                    lexeme: "true".to_string(),
-                    line: 0,
-                },
-            }));
-        }
-
-        body = Stmt::While(condition.unwrap(), Box::new(body));
+        body = Stmt::While(condition, Box::new(body), increment);
 
         if let Some(initializer) = initializer {
             body = Stmt::Block(vec![initializer, body]);
@@ -269,7 +502,7 @@ impl Parser {
         self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.")?;
         let body = self.statement()?;
 
-        Ok(Stmt::While(condition, Box::new(body)))
+        Ok(Stmt::While(Some(condition), Box::new(body), None))
     }
 
     fn statement(&mut self) -> Result<Stmt, ParseError> {
@@ -282,18 +515,52 @@ impl Parser {
         if self.match_token(vec![TokenType::LEFT_BRACE]) {
             return Ok(Stmt::Block(self.block()?));
         }
+        if self.match_token(vec![TokenType::WITH]) {
+            return self.with_statement();
+        }
         if self.match_token(vec![TokenType::BREAK]) {
-            return Ok(Stmt::Break);
+            let keyword = self.previous();
+            self.consume_semicolon("Expect ';' after 'break'.")?;
+            return Ok(Stmt::Break(keyword));
+        }
+        if self.match_token(vec![TokenType::CONTINUE]) {
+            let keyword = self.previous();
+            self.consume_semicolon("Expect ';' after 'continue'.")?;
+            return Ok(Stmt::Continue(keyword));
         }
 
         self.expression_statement()
     }
 
+    fn with_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
+        let resource = self.expression()?;
+        // `as` is only special here, so it can still name things elsewhere.
+        let as_keyword = self.peek();
+        if as_keyword.token_type != TokenType::IDENTIFIER || as_keyword.lexeme != "as" {
+            return Err(ParseError {
+                token: as_keyword,
+                message: "Expect 'as' after resource.".to_string(),
+            });
+        }
+        self.advance();
+        let name = self.consume(TokenType::IDENTIFIER, "Expect name after 'as'.")?;
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' before with body.")?;
+        let body = self.block()?;
+        Ok(Stmt::With(keyword, resource, name, body))
+    }
+
     fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut stmts = Vec::new();
 
         while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
-            stmts.push(self.declaration()?);
+            match self.declaration() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(true);
+                }
+            }
         }
 
         self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.")?;
@@ -301,31 +568,28 @@ impl Parser {
     }
 
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
-        let value = self.expression()?;
-        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
-        Ok(Stmt::Print(value))
+        // As with `return`, commas separate the values to print.
+        let values = comma_operands(self.expression()?);
+        self.consume_semicolon("Expect ';' after value.")?;
+        Ok(Stmt::Print(values))
     }
 
     fn return_statement(&mut self) -> Result<Stmt, ParseError> {
         let keyword = self.previous();
-        let mut value = None;
+        let mut values = Vec::new();
         if !self.check(TokenType::SEMICOLON) {
-            value = Some(self.expression()?);
+            // Commas separate the values rather than making one comma expression of them.
+            values = comma_operands(self.expression()?);
         }
 
-        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
-        Ok(Stmt::Return(keyword, value))
+        self.consume_semicolon("Expect ';' after return value.")?;
+        Ok(Stmt::Return(keyword, values))
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
         let value = self.expression()?;
-        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
-        match value {
-            Expr::Assign(assignment) => {
-                Ok(Stmt::Assign(assignment.name.clone(), *assignment.value))
-            }
-            _ => Ok(Stmt::Expr(value)),
-        }
+        self.consume_semicolon("Expect ';' after value.")?;
+        Ok(unpack(value))
     }
 
     fn expression(&mut self) -> Result<Expr, ParseError> {
@@ -337,6 +601,7 @@ impl Parser {
             TokenType::GREATER_EQUAL,
             TokenType::LESS,
             TokenType::LESS_EQUAL,
+            TokenType::IN,
             TokenType::MINUS,
             TokenType::PLUS,
             TokenType::SLASH,
@@ -347,42 +612,42 @@ impl Parser {
         ];
         if self.match_token(binary_operators.clone()) {
             let token = self.previous();
-            crate::error(
-                token.line,
-                &format!("Expression cannot start with {}", token.lexeme),
-            );
+            self.errors.push(ParseError {
+                message: format!("Expression cannot start with {}", token.lexeme),
+                token,
+            });
             while !self.is_at_end() && !self.match_token(binary_operators.clone()) {
                 self.advance();
             }
         }
 
-        match self.assignment() {
-            Ok(expr) => Ok(expr),
-            Err(err) => {
-                crate::error(err.token.line, err.message.as_str());
-                Err(err)
-            }
-        }
+        self.comma()
+    }
 
-        // C style comma operator, e.g. (1, 2, 3). The value of the expression is the last value.
-        // Not sure if this is working correctly.
-        // Not working correctly. The comma operator should be left associative, but this is right associative.
-        // Now it's straight up causing bugs so I'm disabling it.
-        // while self.peek().token_type == TokenType::COMMA {
-        //    self.advance();
-        //    expr = match self.expression() {
-        //        Ok(expr) => Ok(expr),
-        //        Err(err) => {
-        //            crate::error(err.token.line, err.message.as_str());
-        //            return Err(err);
-        //        }
-        //    };
-        // }
+    // C style comma operator, e.g. (1, 2, 3). Each operand is evaluated left to right and the
+    // value of the expression is the last one. It binds loosest of all, so places where commas
+    // separate things, like argument lists, parse an assignment instead of an expression.
+    fn comma(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.assignment()?;
 
-        // expr
+        while self.peek().token_type == TokenType::COMMA {
+            let operator = self.advance();
+            let right = self.assignment()?;
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        Ok(expr)
     }
 
     fn assignment(&mut self) -> Result<Expr, ParseError> {
+        self.nested("Expression", Self::nested_assignment)
+    }
+
+    fn nested_assignment(&mut self) -> Result<Expr, ParseError> {
         let expr = self.ternary()?;
 
         if self.match_token(vec![TokenType::EQUAL]) {
@@ -405,7 +670,6 @@ impl Parser {
                     return set;
                 }
                 _ => {
-                    crate::error(equals.line, "Invalid assignment target.");
                     return Err(ParseError {
                         token: equals,
                         message: "Invalid assignment target.".to_string(),
@@ -417,14 +681,18 @@ impl Parser {
         Ok(expr)
     }
 
+    // condition ? then_branch : else_branch, binding tighter than assignment and right
+    // associative. As in C, anything can go between `?` and `:`, but the else branch is
+    // another ternary, so `a ? b : c ? d : e` nests to the right and `a ? b : c = d` is an
+    // invalid assignment.
     fn ternary(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.or()?;
 
         if self.peek().token_type == TokenType::QUESTION {
             self.advance();
-            let then_branch = self.expression()?;
+            let then_branch = self.comma()?;
             self.consume(TokenType::COLON, "Expect ':' after then branch of ternary")?;
-            let else_branch = self.expression()?;
+            let else_branch = self.nested("Expression", Self::ternary)?;
             expr = Expr::Ternary(Ternary {
                 condition: Box::new(expr),
                 then_branch: Box::new(then_branch),
@@ -492,6 +760,7 @@ impl Parser {
             || self.peek().token_type == TokenType::GREATER_EQUAL
             || self.peek().token_type == TokenType::LESS
             || self.peek().token_type == TokenType::LESS_EQUAL
+            || self.peek().token_type == TokenType::IN
         {
             let operator = self.advance();
             let right = self.term()?;
@@ -544,7 +813,7 @@ impl Parser {
     fn unary(&mut self) -> Result<Expr, ParseError> {
         if self.peek().token_type == TokenType::BANG || self.peek().token_type == TokenType::MINUS {
             let operator = self.advance();
-            let right = self.unary()?;
+            let right = self.nested("Expression", Self::unary)?;
             return Ok(Expr::Unary(Unary {
                 operator,
                 right: Box::new(right),
@@ -578,13 +847,16 @@ impl Parser {
         if !self.check(TokenType::RIGHT_PAREN) {
             loop {
                 if arguments.len() >= 255 {
-                    crate::error(self.peek().line, "Can't have more than 255 arguments.");
                     return Err(ParseError {
                         token: self.peek(),
                         message: "Can't have more than 255 arguments.".to_string(),
                     });
                 }
-                arguments.push(self.expression()?);
+                match self.assignment() {
+                    Ok(argument) => arguments.push(argument),
+                    Err(error) if self.skip_argument() => self.errors.push(error),
+                    Err(error) => return Err(error),
+                }
                 if !self.match_token(vec![TokenType::COMMA]) {
                     break;
                 }
@@ -623,6 +895,19 @@ impl Parser {
             }));
         }
 
+        if self.match_token(vec![TokenType::SUPER]) {
+            let keyword = self.previous();
+            self.consume(TokenType::DOT, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::IDENTIFIER, "Expect superclass method name.")?;
+            return Ok(Expr::Super(Super { keyword, method }));
+        }
+
+        if self.match_token(vec![TokenType::THIS]) {
+            return Ok(Expr::This(This {
+                keyword: self.previous(),
+            }));
+        }
+
         if self.match_token(vec![TokenType::IDENTIFIER]) {
             return Ok(Expr::Variable(Variable {
                 name: self.previous(),
@@ -630,10 +915,12 @@ impl Parser {
         }
 
         if self.match_token(vec![TokenType::LEFT_PAREN]) {
+            let start = self.previous().start;
             let expr = self.expression()?;
-            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.")?;
+            let paren = self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.")?;
             return Ok(Expr::Grouping(Grouping {
                 expression: Box::new(expr),
+                span: start..paren.end,
             }));
         }
 
@@ -643,3 +930,180 @@ impl Parser {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str, interactive: bool) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut parser = Parser::new(Scanner::new(source.to_string()).scan_tokens());
+        if interactive {
+            parser = parser.interactive();
+        }
+        parser.parse()
+    }
+
+    #[test]
+    fn test_interactive_semicolon() {
+        assert_eq!(parse("var y = 3", true).unwrap().len(), 1);
+        assert_eq!(parse("print 1; print 2", true).unwrap().len(), 2);
+        assert!(parse("print 1 print 2", true).is_err());
+        assert!(parse("print 1", false).is_err());
+    }
+
+    #[test]
+    fn test_collects_all_errors() {
+        let errors = parse("var = 1;\nprint 1 +;\nprint 3;\n1 = 2;", false).unwrap_err();
+        let messages: Vec<_> = errors
+            .iter()
+            .map(|e| (e.token.line, e.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "Expect variable name."),
+                (2, "Expect expression."),
+                (4, "Invalid assignment target."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comma() {
+        let printed = parse("f(), b = 1, c; f(a, (b, c)); x ? y : z, w;", false)
+            .unwrap()
+            .into_iter()
+            .map(crate::stmt::print)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            printed,
+            vec![
+                "(expr (, (, (call f) (= b 1)) c))",
+                "(expr (call f a (group (, b c))))",
+                "(expr (, (x ? y : z) w))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let printed = parse(
+            "fun f() { return 1, (2, 3); } var a, b = f(); a, b = b, a; a, b = f();\n\
+             print a, (b, 1); var c, d = 1, f(); f(), a = 1;",
+            false,
+        )
+        .unwrap()
+        .into_iter()
+        .map(crate::stmt::print)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            printed,
+            vec![
+                "(fun f () (return 1 (group (, 2 3))))",
+                "(var (a b) (call f))",
+                "(= (a b) b a)",
+                "(= (a b) (call f))",
+                "(print a (group (, b 1)))",
+                "(var (c d) 1 (call f))",
+                "(expr (, (call f) (= a 1)))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ternary() {
+        let printed = parse(
+            "a = b ? c : d; a ? b : c ? d : e; a ? b ? c : d : e; a or b ? c = 1, d : e and f;",
+            false,
+        )
+        .unwrap()
+        .into_iter()
+        .map(crate::stmt::print)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            printed,
+            vec![
+                "(expr (= a (b ? c : d)))",
+                "(expr (a ? b : (c ? d : e)))",
+                "(expr (a ? (b ? c : d) : e))",
+                "(expr ((or a b) ? (, (= c 1) d) : (and e f)))",
+            ]
+        );
+        let errors = parse("a ? b : c = d;", false).unwrap_err();
+        assert_eq!(errors[0].message, "Invalid assignment target.");
+    }
+
+    #[test]
+    fn test_for_desugaring() {
+        let printed = parse("for (var i = 0; i < 3; i = i + 1) { print i; }", false)
+            .unwrap()
+            .into_iter()
+            .map(crate::stmt::print)
+            .collect::<Vec<_>>();
+        // The increment belongs to the loop rather than its body, so that every way of
+        // finishing an iteration runs it.
+        assert_eq!(
+            printed,
+            vec!["(block (var i 0) (while (< i 3) (block (print i)) (= i (+ i 1))))"]
+        );
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let sources = [
+            format!("print {}1{};", "(".repeat(1000), ")".repeat(1000)),
+            format!("print {}1;", "!".repeat(1000)),
+            format!("{}{}", "{".repeat(1000), "}".repeat(1000)),
+            format!("a{};", " = a".repeat(1000)),
+        ];
+        // Test threads get less stack than a program's main thread.
+        let errors = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || {
+                sources
+                    .iter()
+                    .map(|source| parse(source, false).unwrap_err().remove(0).message)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(
+            errors,
+            vec![
+                "Expression too deeply nested.",
+                "Expression too deeply nested.",
+                "Statement too deeply nested.",
+                "Expression too deeply nested.",
+            ]
+        );
+        let tokens = Scanner::new(String::from("print ((1));")).scan_tokens();
+        assert!(Parser::new(tokens.clone()).parse().is_ok());
+        let errors = Parser::new(tokens).max_depth(2).parse().unwrap_err();
+        assert_eq!(errors[0].message, "Expression too deeply nested.");
+    }
+
+    #[test]
+    fn test_recovery() {
+        let source = "class C {\n  m(a b) { print 1; }\n  n() { print 2 }\n}\n\
+                      f(1, var, 3);\n\
+                      fun g() {\n  { print; }\n  print 3;\n}\n\
+                      print 4 5;";
+        let errors = parse(source, false).unwrap_err();
+        let messages: Vec<_> = errors
+            .iter()
+            .map(|e| (e.token.line, e.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (2, "Expect ')' after parameters."),
+                (3, "Expect ';' after value."),
+                (5, "Expect expression."),
+                (7, "Expect expression."),
+                (10, "Expect ';' after value."),
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! How the REPL shows a value: instances with their fields and lists and maps with their
+//! contents, spread over several lines when they don't fit on one.
+
+use crate::primitive::Primitive;
+use std::rc::Rc;
+
+/// Past this many levels of nesting, contents are elided.
+const MAX_DEPTH: usize = 6;
+/// How wide a value may get on one line before it's split up.
+const WIDTH: usize = 80;
+const INDENT: usize = 2;
+
+/// `value` laid out for the REPL, with numbers rounded to `precision` decimal places if set.
+pub fn pretty(value: &Primitive, precision: Option<usize>) -> String {
+    Printer {
+        ancestors: Vec::new(),
+        precision,
+    }
+    .render(value, 0, 0)
+}
+
+struct Printer {
+    /// The containers enclosing the value being rendered, to catch one that contains itself.
+    ancestors: Vec<Primitive>,
+    precision: Option<usize>,
+}
+
+impl Printer {
+    /// Renders `value` starting `column` characters into a line indented by `indent`.
+    fn render(&mut self, value: &Primitive, indent: usize, column: usize) -> String {
+        let (open, close, entries) = match value {
+            Primitive::Instance(instance) => {
+                let mut fields = instance
+                    .fields
+                    .borrow()
+                    .iter()
+                    .map(|(name, value)| (format!("{}: ", name), value.primitive.clone()))
+                    .collect::<Vec<_>>();
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                (format!("{} {{", instance.class_name()), "}", fields)
+            }
+            Primitive::List(list) => {
+                let items = list
+                    .borrow()
+                    .iter()
+                    .map(|item| (String::new(), item.primitive.clone()))
+                    .collect();
+                (String::from("["), "]", items)
+            }
+            Primitive::Map(map) => {
+                let mut entries = map
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| (format!("\"{}\": ", key), value.primitive.clone()))
+                    .collect::<Vec<_>>();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                (String::from("{"), "}", entries)
+            }
+            value => return value.display(self.precision).to_string(),
+        };
+        if self.ancestors.iter().any(|ancestor| same(ancestor, value)) {
+            return String::from("<cycle>");
+        }
+        if entries.is_empty() {
+            return format!("{}{}", open, close);
+        }
+        if self.ancestors.len() >= MAX_DEPTH {
+            return format!("{}...{}", open, close);
+        }
+        self.ancestors.push(value.clone());
+        let items = entries
+            .into_iter()
+            .map(|(label, value)| {
+                let inner = indent + INDENT;
+                let rendered = self.render(&value, inner, inner + label.len());
+                label + &rendered
+            })
+            .collect::<Vec<_>>();
+        self.ancestors.pop();
+        // Instances read better with space inside their braces: `Point { x: 1 }`.
+        let padding = if open.ends_with(" {") { " " } else { "" };
+        let flat = format!(
+            "{}{}{}{}{}",
+            open,
+            padding,
+            items.join(", "),
+            padding,
+            close
+        );
+        if column + flat.len() <= WIDTH && !flat.contains('\n') {
+            return flat;
+        }
+        let inner = " ".repeat(indent + INDENT);
+        let mut lines = open;
+        for item in items {
+            lines += &format!("\n{}{},", inner, item);
+        }
+        format!("{}\n{}{}", lines, " ".repeat(indent), close)
+    }
+}
+
+/// Whether `a` and `b` are the same container, rather than equal ones.
+fn same(a: &Primitive, b: &Primitive) -> bool {
+    match (a, b) {
+        (Primitive::List(a), Primitive::List(b)) => Rc::ptr_eq(a, b),
+        (Primitive::Map(a), Primitive::Map(b)) => Rc::ptr_eq(a, b),
+        (Primitive::Instance(a), Primitive::Instance(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expr::Value,
+        primitive::{Class, Instance},
+        token::{Token, TokenType},
+    };
+    use std::cell::RefCell;
+
+    fn instance(class: &str, fields: Vec<(&str, Value)>) -> Instance {
+        let name = Token::new(TokenType::IDENTIFIER, class.to_string(), 0);
+        let instance = Instance::new(Class::new(name, Vec::new()));
+        for (field, value) in fields {
+            instance
+                .fields
+                .borrow_mut()
+                .insert(field.to_string(), value);
+        }
+        instance
+    }
+
+    fn value(primitive: Primitive) -> Value {
+        Value {
+            primitive,
+            token: Token::new(TokenType::IDENTIFIER, String::new(), 0),
+        }
+    }
+
+    #[test]
+    fn test_pretty() {
+        let point = instance("Point", vec![("y", 2.0.into()), ("x", 1.0.into())]);
+        assert_eq!(
+            pretty(&Primitive::Instance(point.clone()), None),
+            "Point { x: 1, y: 2 }"
+        );
+        let empty = instance("Empty", Vec::new());
+        assert_eq!(pretty(&Primitive::Instance(empty), None), "Empty {}");
+
+        let points = vec![value(Primitive::Instance(point)); 4];
+        let line = instance(
+            "Line",
+            vec![("points", points.into()), ("name", "zigzag".into())],
+        );
+        assert_eq!(
+            pretty(&Primitive::Instance(line), None),
+            "Line {\n  name: \"zigzag\",\n  points: [\n    Point { x: 1, y: 2 },\n    \
+             Point { x: 1, y: 2 },\n    Point { x: 1, y: 2 },\n    Point { x: 1, y: 2 },\n  ],\n}"
+        );
+
+        let list = Rc::new(RefCell::new(vec![Value::from(1.0)]));
+        list.borrow_mut().push(value(Primitive::List(list.clone())));
+        assert_eq!(pretty(&Primitive::List(list.clone()), None), "[1, <cycle>]");
+        list.borrow_mut().clear();
+
+        let mut nested = Value::from(1.0);
+        for _ in 0..8 {
+            nested = vec![nested].into();
+        }
+        assert_eq!(pretty(&nested.primitive, None), "[[[[[[[...]]]]]]]");
+    }
+}
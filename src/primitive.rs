@@ -1,7 +1,7 @@
 use crate::{
-    expr::{Expr, Value},
-    interpreter::{environment::Environment, InterpretError, Interpreter},
-    stmt::Stmt,
+    expr::Value,
+    interpreter::{environment::Environment, ErrorKind, InterpretError, Interpreter},
+    stmt::{Signature, Stmt},
     token::{Token, TokenType},
 };
 use std::{
@@ -18,72 +18,173 @@ pub enum Primitive {
     Nil,
     String(String),
     Callable(Callable),
+    Native(NativeFunction),
     Class(Class),
     Instance(Instance),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<HashMap<String, Value>>>),
 }
 
 pub trait LoxCallable {
-    fn call(&self, args: Vec<Value>, locals: HashMap<Expr, usize>)
-        -> Result<Value, InterpretError>;
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpretError>;
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// A class. Copies share their methods, which is what makes two of them the same class.
+#[derive(Clone, Debug)]
 pub struct Class {
     pub name: Token,
-    pub methods: Vec<Stmt>,
+    /// In the order they were declared, closed over the scope the class was declared in.
+    pub methods: Rc<Vec<Callable>>,
+    /// The class this one inherits methods from.
+    pub superclass: Option<Rc<Class>>,
+    pub doc: Option<String>,
 }
 
 impl Class {
-    pub fn new(name: Token, methods: Vec<Stmt>) -> Self {
-        Self { name, methods }
+    pub fn new(name: Token, methods: Vec<Callable>) -> Self {
+        Self {
+            name,
+            methods: Rc::new(methods),
+            superclass: None,
+            doc: None,
+        }
+    }
+
+    pub fn with_superclass(mut self, superclass: Option<Class>) -> Self {
+        self.superclass = superclass.map(Rc::new);
+        self
+    }
+
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
+    }
+
+    /// Its own methods' names and then those it inherits.
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names = self
+            .methods
+            .iter()
+            .map(|method| method.name.lexeme.clone())
+            .collect::<Vec<_>>();
+        if let Some(superclass) = &self.superclass {
+            for name in superclass.method_names() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// The method called `name`, from this class or else the nearest superclass that has one.
+    pub fn find_method(&self, name: &str) -> Option<&Callable> {
+        self.methods
+            .iter()
+            .find(|method| method.name.lexeme == name)
+            .or_else(|| self.superclass.as_ref()?.find_method(name))
+    }
+
+    /// How many arguments calling the class takes: those of its `init` method, if it has one.
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity)
     }
 }
 
 impl LoxCallable for Class {
     fn call(
         &self,
+        interpreter: &mut Interpreter,
         args: Vec<Value>,
-        locals: HashMap<Expr, usize>,
     ) -> Result<Value, InterpretError> {
+        let instance = Instance::new(self.clone());
+        if let Some(init) = self.find_method("init") {
+            init.bind(instance.clone()).call(interpreter, args)?;
+        }
         Ok(Value {
-            primitive: Primitive::Instance(Instance::new(self.clone())),
+            primitive: Primitive::Instance(instance),
             token: self.name.clone(),
         })
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.methods, &other.methods)
+    }
+}
+
+/// An instance of a class. Copies are references to the same object: they share its fields,
+/// so a field set through one is seen through all of them, and `==` compares which object
+/// they refer to.
+#[derive(Clone, Debug)]
 pub struct Instance {
     class: Class,
-    pub fields: HashMap<String, Value>,
+    pub fields: Rc<RefCell<HashMap<String, Value>>>,
 }
 
 impl Instance {
     pub fn new(class: Class) -> Self {
         Self {
             class,
-            fields: HashMap::new(),
+            fields: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
+    /// A field, or else a method bound to this instance. Fields shadow methods.
     pub fn get(&self, name: Token) -> Result<Value, InterpretError> {
-        if let Some(value) = self.fields.get(&name.lexeme) {
+        if let Some(value) = self.fields.borrow().get(&name.lexeme) {
             return Ok(value.clone());
         }
+        if let Some(method) = self.class.find_method(&name.lexeme) {
+            return Ok(Value {
+                primitive: Primitive::Callable(method.bind(self.clone())),
+                token: name,
+            });
+        }
         Err(InterpretError::new(
+            ErrorKind::PropertyError,
             format!("Undefined property '{}'.", name.lexeme),
             name,
         ))
     }
 
-    pub fn set(&mut self, name: Token, value: Value) {
-        self.fields.insert(name.lexeme, value);
+    pub fn set(&self, name: Token, value: Value) {
+        self.fields.borrow_mut().insert(name.lexeme, value);
+    }
+
+    pub fn class_name(&self) -> &str {
+        &self.class.name.lexeme
+    }
+
+    /// Field and method names, for tooling such as REPL completion.
+    pub fn member_names(&self) -> Vec<String> {
+        let mut names = self
+            .fields
+            .borrow()
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        names.extend(self.class.method_names());
+        names
     }
 }
 
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
+    }
+}
+
+/// A function is the same function only if it comes from the same declaration run in the
+/// same scope.
 impl PartialEq for Callable {
-    fn eq(&self, _other: &Self) -> bool {
-        false
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.closure, &other.closure)
     }
 }
 
@@ -100,6 +201,10 @@ pub struct Callable {
     pub params: Vec<Token>,
     pub body: Vec<Stmt>,
     pub closure: Rc<RefCell<Environment>>,
+    /// The parameter and return types it was declared with, and its documentation.
+    pub signature: Signature,
+    /// A class's `init` method, which always gives back `this`.
+    pub initializer: bool,
 }
 
 impl Callable {
@@ -109,36 +214,81 @@ impl Callable {
         body: Vec<Stmt>,
         closure: Rc<RefCell<Environment>>,
     ) -> Self {
-        let callable = Self {
+        Self {
             arity: params.len(),
-            name: name.clone(),
+            name,
             params,
             body,
             closure,
-        };
-        callable
+            signature: Signature::default(),
+            initializer: false,
+        }
+    }
+
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = signature;
+        self
+    }
+
+    pub fn with_initializer(mut self, initializer: bool) -> Self {
+        self.initializer = initializer;
+        self
+    }
+
+    /// The method with `this` set to `instance`, in a scope of its own between the method's
+    /// closure and its body.
+    pub fn bind(&self, instance: Instance) -> Callable {
+        let mut environment = Environment::new(self.closure.clone());
+        environment.define(
+            String::from("this"),
+            Value {
+                primitive: Primitive::Instance(instance),
+                token: self.name.clone(),
+            },
+        );
+        Callable {
+            closure: Rc::new(RefCell::new(environment)),
+            ..self.clone()
+        }
     }
 }
 
 impl LoxCallable for Callable {
     fn call(
         &self,
+        interpreter: &mut Interpreter,
         args: Vec<Value>,
-        locals: HashMap<Expr, usize>,
     ) -> Result<Value, InterpretError> {
-        let mut new_interpreter = Interpreter::new_with_locals(self.closure.clone(), locals);
-        new_interpreter.new_environment();
-        for (i, arg) in args.iter().enumerate() {
-            new_interpreter.define(self.params[i].lexeme.clone(), arg.clone());
+        let previous = interpreter.environment.clone();
+        interpreter.environment = Rc::new(RefCell::new(Environment::new(self.closure.clone())));
+        interpreter.metrics.environments += 1;
+        for (param, arg) in self.params.iter().zip(args) {
+            interpreter.define(param.lexeme.clone(), arg);
+        }
+        let result = interpreter.interpret_block(self.body.clone());
+        interpreter.environment = previous;
+        if self.initializer {
+            // A bare `return` is all an initializer may have, and it still gives `this`.
+            return match result {
+                Err(e) if e.value.is_none() => Err(e),
+                _ => Ok(self
+                    .closure
+                    .borrow()
+                    .get(0, "this")
+                    .unwrap_or_else(|| Value {
+                        primitive: Primitive::Nil,
+                        token: self.name.clone(),
+                    })),
+            };
         }
-        match new_interpreter.interpret_block(self.body.clone()) {
+        match result {
             Ok(_) => Ok(Value {
                 primitive: Primitive::Nil,
                 token: Token::new(TokenType::NIL, String::from("nil"), 0),
             }),
             Err(e) => {
                 if let Some(value) = e.value {
-                    Ok(value)
+                    Ok(*value)
                 } else {
                     Err(e)
                 }
@@ -147,10 +297,168 @@ impl LoxCallable for Callable {
     }
 }
 
-impl Display for Primitive {
+pub type NativeFn = dyn Fn(&mut Interpreter, Vec<Value>) -> Result<Value, InterpretError>;
+
+/// A function implemented in Rust, such as `clock` or `input`.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: usize, function: Rc<NativeFn>) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            function,
+        }
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+impl Debug for NativeFunction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpretError> {
+        (self.function)(interpreter, args)
+    }
+}
+
+impl Primitive {
+    /// Lox's `==`. Values of different types are never equal, and instances, functions and
+    /// classes are only equal to themselves. Lists and maps are equal when their elements are.
+    pub fn equals(&self, other: &Primitive) -> bool {
+        match (self, other) {
+            (Primitive::List(left), Primitive::List(right)) => {
+                Rc::ptr_eq(left, right) || {
+                    let (left, right) = (left.borrow(), right.borrow());
+                    left.len() == right.len()
+                        && left
+                            .iter()
+                            .zip(right.iter())
+                            .all(|(left, right)| left.primitive.equals(&right.primitive))
+                }
+            }
+            (Primitive::Map(left), Primitive::Map(right)) => {
+                Rc::ptr_eq(left, right) || {
+                    let (left, right) = (left.borrow(), right.borrow());
+                    left.len() == right.len()
+                        && left.iter().all(|(key, left)| {
+                            right
+                                .get(key)
+                                .is_some_and(|right| left.primitive.equals(&right.primitive))
+                        })
+                }
+            }
+            (left, right) => left == right,
+        }
+    }
+
+    /// Lox truthiness: everything but `nil` and `false` is true.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Primitive::Nil | Primitive::Boolean(false))
+    }
+
+    /// A short description of the value's type, such as `number` or `instance of Point`.
+    pub fn type_description(&self) -> String {
         match self {
-            Primitive::Number(number) => write!(f, "{}", number),
+            Primitive::Number(_) => String::from("number"),
+            Primitive::Boolean(_) => String::from("boolean"),
+            Primitive::Nil => String::from("nil"),
+            Primitive::String(_) => String::from("string"),
+            Primitive::Callable(callable) => format!("function/{}", callable.arity),
+            Primitive::Native(native) => format!("native function/{}", native.arity),
+            Primitive::Class(class) => format!("class {}", class.name.lexeme),
+            Primitive::Instance(instance) => format!("instance of {}", instance.class.name.lexeme),
+            Primitive::List(_) => String::from("list"),
+            Primitive::Map(_) => String::from("map"),
+        }
+    }
+}
+
+/// How a number reads when printed or joined to a string: the shortest decimal that parses
+/// back to the same value, without a fractional part for integers and in exponent form for
+/// very large or small magnitudes. With a precision, it's rounded to that many decimal
+/// places first (of the mantissa, in exponent form) and trailing zeros are dropped.
+pub fn format_number(number: f64, precision: Option<usize>) -> String {
+    if number.is_nan() {
+        return String::from("NaN");
+    }
+    if number.is_infinite() {
+        return String::from(if number > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        });
+    }
+    let exponent = number != 0.0 && !(1e-6..1e21).contains(&number.abs());
+    let formatted = match (precision, exponent) {
+        (None, false) => number.to_string(),
+        (None, true) => format!("{:e}", number),
+        (Some(digits), false) => trim_zeros(&format!("{:.*}", digits, number)).to_string(),
+        (Some(digits), true) => {
+            let formatted = format!("{:.*e}", digits, number);
+            let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+            format!("{}e{}", trim_zeros(mantissa), exponent)
+        }
+    };
+    // Rounding can leave `-0`, which should only show for negative zero itself.
+    match formatted.as_str() {
+        "-0" if number != 0.0 => String::from("0"),
+        _ => formatted,
+    }
+}
+
+fn trim_zeros(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+impl Primitive {
+    /// The value as `print` shows it, with numbers rounded to `precision` decimal places when
+    /// there is one, those in lists and maps included.
+    pub fn display(&self, precision: Option<usize>) -> Shown<'_> {
+        Shown {
+            primitive: self,
+            precision,
+        }
+    }
+}
+
+/// A value being displayed with a precision. See `Primitive::display`.
+pub struct Shown<'a> {
+    primitive: &'a Primitive,
+    precision: Option<usize>,
+}
+
+impl Display for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display(None))
+    }
+}
+
+impl Display for Shown<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.primitive {
+            Primitive::Number(number) => write!(f, "{}", format_number(*number, self.precision)),
             Primitive::Boolean(boolean) => write!(f, "{}", boolean),
             Primitive::Nil => write!(f, "nil"),
             Primitive::String(string) => write!(f, "\"{}\"", string),
@@ -165,8 +473,83 @@ impl Display for Primitive {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
+            Primitive::Native(native) => write!(f, "<native fn> {}", native.name),
             Primitive::Class(class) => write!(f, "{}", class.name.lexeme),
             Primitive::Instance(instance) => write!(f, "{} instance", instance.class.name.lexeme),
+            Primitive::List(list) => write!(
+                f,
+                "[{}]",
+                list.borrow()
+                    .iter()
+                    .map(|value| value.primitive.display(self.precision).to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Primitive::Map(map) => {
+                let map = map.borrow();
+                let mut keys = map.keys().collect::<Vec<&String>>();
+                keys.sort();
+                write!(
+                    f,
+                    "{{{}}}",
+                    keys.into_iter()
+                        .map(|key| {
+                            let value = map[key].primitive.display(self.precision);
+                            format!("\"{}\": {}", key, value)
+                        })
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        let cases = [
+            (1.0, "1"),
+            (-2.5, "-2.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e21, "1e21"),
+            (123456789012345680000.0, "123456789012345680000"),
+            (1e-7, "1e-7"),
+            (-0.0, "-0"),
+            (f64::NAN, "NaN"),
+            (f64::NEG_INFINITY, "-Infinity"),
+        ];
+        for (number, expected) in cases {
+            assert_eq!(format_number(number, None), expected);
         }
+        let cases = [
+            (2.0 / 3.0, "0.67"),
+            (2.5, "2.5"),
+            (-0.001, "0"),
+            (1.23456e30, "1.23e30"),
+        ];
+        for (number, expected) in cases {
+            assert_eq!(format_number(number, Some(2)), expected);
+        }
+    }
+
+    #[test]
+    fn test_equals() {
+        let list = |items: &[f64]| {
+            let items = items.iter().map(|&n| Value::from(n)).collect::<Vec<_>>();
+            Primitive::List(Rc::new(RefCell::new(items)))
+        };
+        assert!(list(&[1.0, 2.0]).equals(&list(&[1.0, 2.0])));
+        assert!(!list(&[1.0, 2.0]).equals(&list(&[2.0, 1.0])));
+        assert!(!list(&[1.0]).equals(&Primitive::Number(1.0)));
+        let map = |value: f64| {
+            let entries = HashMap::from([(String::from("k"), Value::from(value))]);
+            Primitive::Map(Rc::new(RefCell::new(entries)))
+        };
+        assert!(map(1.0).equals(&map(1.0)));
+        assert!(!map(1.0).equals(&map(2.0)));
     }
 }
@@ -0,0 +1,98 @@
+//! Hot reloading: running an edited script again by redefining only the functions and classes
+//! that changed, so the globals the program built up survive the edit.
+
+use crate::{
+    compile,
+    diagnostic::with_source,
+    execute,
+    interpreter::Interpreter,
+    stmt::{self, Stmt},
+    Status,
+};
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct HotReload {
+    /// Each top-level function and class by name, printed without source positions as it
+    /// was last run.
+    declarations: HashMap<String, String>,
+    /// Whether the whole program has been run once.
+    started: bool,
+}
+
+impl HotReload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs all of `source` the first time. After that only the function and class
+    /// declarations that are new or different from last time run, into the existing globals;
+    /// the rest of the program is left alone.
+    pub fn run(&mut self, source: String, interpreter: &mut Interpreter) -> Status {
+        with_source(&source.clone(), || {
+            let Some(stmts) = compile(source, interpreter, false) else {
+                return Status::CompileError;
+            };
+            let declarations = stmts
+                .iter()
+                .filter_map(declaration)
+                .collect::<HashMap<_, _>>();
+            let stmts = if self.started {
+                stmts
+                    .into_iter()
+                    .filter(|stmt| {
+                        declaration(stmt).is_some_and(|(name, printed)| {
+                            self.declarations.get(&name) != Some(&printed)
+                        })
+                    })
+                    .collect()
+            } else {
+                stmts
+            };
+            self.started = true;
+            self.declarations = declarations;
+            execute(Some(stmts), interpreter, false)
+        })
+    }
+}
+
+fn declaration(stmt: &Stmt) -> Option<(String, String)> {
+    match stmt {
+        Stmt::Function(name, ..) | Stmt::Class(name, ..) => {
+            Some((name.lexeme.clone(), stmt::print(stmt.clone())))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::environment::Environment;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn test_reload_keeps_globals() {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(stdout.clone())
+            .with_stderr(stderr.clone());
+        let mut reload = HotReload::new();
+        let source = "var count = 0;\nfun step() { count = count + 1; }\nfun show() { print count; }\nstep();";
+        assert_eq!(reload.run(source.to_string(), &mut interpreter), Status::Ok);
+        // Moving `show` down a line isn't a change, so only `step` is redefined.
+        let edited = "var count = 0;\nfun step() { count = count + 10; }\n\nfun show() { print count; }\nstep();";
+        assert_eq!(reload.run(edited.to_string(), &mut interpreter), Status::Ok);
+        interpreter.call_function("step", &[]).unwrap();
+        interpreter.call_function("show", &[]).unwrap();
+        assert_eq!(String::from_utf8(stdout.borrow().clone()).unwrap(), "11\n");
+        assert_eq!(
+            reload.run(String::from("fun step( {"), &mut interpreter),
+            Status::CompileError
+        );
+        assert!(String::from_utf8(stderr.borrow().clone())
+            .unwrap()
+            .starts_with("error: Expect parameter name."));
+    }
+}
@@ -0,0 +1,560 @@
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+    rc::Rc,
+    sync::atomic::Ordering,
+    time::Instant,
+};
+
+use crate::config::Config;
+#[cfg(not(target_arch = "wasm32"))]
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::{CmdKind, Highlighter},
+    hint::Hinter,
+    history::FileHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+use slow_lox::{highlight, interpreter::Interpreter, primitive::Primitive, scanner, Status};
+
+#[cfg(not(target_arch = "wasm32"))]
+type LineEditor = Editor<Completions, FileHistory>;
+
+/// Where REPL lines come from: a line editor on a terminal, or the interpreter's stdin when
+/// input is piped or the editor can't start. The editor turns on bracketed paste, so a pasted
+/// block comes back from a single read, newlines and all, and runs as one entry.
+enum Input {
+    #[cfg(not(target_arch = "wasm32"))]
+    Editor(Box<LineEditor>),
+    Plain(Rc<RefCell<dyn BufRead>>),
+}
+
+enum Line {
+    Text(String),
+    /// Ctrl-C: drop whatever has been typed so far.
+    Interrupted,
+    Eof,
+}
+
+impl Input {
+    fn new(interpreter: &Interpreter) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if std::io::stdin().is_terminal() {
+            if let Ok(mut editor) = LineEditor::new() {
+                editor.set_helper(Some(Completions::default()));
+                if let Some(path) = history_path() {
+                    // There's no history yet on first use.
+                    let _ = editor.load_history(&path);
+                }
+                return Input::Editor(Box::new(editor));
+            }
+        }
+        Input::Plain(interpreter.stdin.clone())
+    }
+
+    fn read_line(&mut self, prompt: &str, interpreter: &Interpreter) -> Line {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Input::Editor(editor) => {
+                if let Some(completions) = editor.helper_mut() {
+                    *completions = Completions::from(interpreter);
+                }
+                match editor.readline(prompt) {
+                    Ok(line) => Line::Text(line),
+                    Err(ReadlineError::Interrupted) => Line::Interrupted,
+                    Err(_) => Line::Eof,
+                }
+            }
+            Input::Plain(stdin) => {
+                let stdout = interpreter.stdout.clone();
+                write!(stdout.borrow_mut(), "{}", prompt).unwrap();
+                stdout.borrow_mut().flush().unwrap();
+                let mut line = String::new();
+                match stdin.borrow_mut().read_line(&mut line) {
+                    Ok(0) | Err(_) => Line::Eof,
+                    Ok(_) => Line::Text(line),
+                }
+            }
+        }
+    }
+
+    fn add_history(&mut self, _entry: &str) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Input::Editor(editor) = self {
+            let _ = editor.add_history_entry(_entry);
+        }
+    }
+
+    fn save_history(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (Input::Editor(editor), Some(path)) = (self, history_path()) {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = editor.save_history(&path);
+        }
+    }
+}
+
+/// Tab-completion candidates, refreshed from the interpreter before every prompt. Also colors
+/// the line as it's typed.
+#[derive(Default)]
+struct Completions {
+    globals: Vec<String>,
+    /// Field and method names of global classes and instances, for completing after a dot.
+    members: HashMap<String, Vec<String>>,
+    /// Whether to pick out the bracket pair at the cursor; off once the line is entered.
+    brackets: Cell<bool>,
+}
+
+impl Completions {
+    fn from(interpreter: &Interpreter) -> Self {
+        let mut completions = Completions::default();
+        for (name, value) in interpreter.environment.borrow().globals() {
+            let members = match &value.primitive {
+                Primitive::Instance(instance) => instance.member_names(),
+                Primitive::Class(class) => class.method_names(),
+                _ => Vec::new(),
+            };
+            if !members.is_empty() {
+                completions.members.insert(name.clone(), members);
+            }
+            completions.globals.push(name);
+        }
+        completions
+    }
+
+    /// Returns where the word under the cursor starts and the names that could finish it.
+    fn candidates(&self, before: &str) -> (usize, Vec<String>) {
+        let is_name = |c: char| c.is_alphanumeric() || c == '_';
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !is_name(*c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let prefix = &before[start..];
+        let mut names = match before[..start].strip_suffix('.') {
+            Some(object) => {
+                let object_start = object
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| !is_name(*c))
+                    .map_or(0, |(i, c)| i + c.len_utf8());
+                self.members
+                    .get(&object[object_start..])
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            None => scanner::keywords()
+                .map(String::from)
+                .chain(self.globals.iter().cloned())
+                .collect(),
+        };
+        names.retain(|name| name.starts_with(prefix));
+        names.sort();
+        names.dedup();
+        (start, names)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Highlighter for Completions {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if !slow_lox::diagnostic::color() {
+            return Cow::Borrowed(line);
+        }
+        let brackets = self
+            .brackets
+            .get()
+            .then(|| highlight::matching_bracket(line, pos))
+            .flatten();
+        Cow::Owned(highlight::ansi_with_brackets(line, brackets))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        // Any keystroke can change the colors, so the whole line is redrawn each time.
+        self.brackets.set(kind != CmdKind::ForcedRefresh);
+        slow_lox::diagnostic::color()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Validator for Completions {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Helper for Completions {}
+
+fn history_path() -> Option<PathBuf> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return dirs::data_dir().map(|dir| dir.join("slow_lox").join("history"));
+    #[cfg(target_arch = "wasm32")]
+    None
+}
+
+const COMMANDS: &[(&str, &str)] = &[
+    (":load <file>", "Run a file in the current session"),
+    (":time <code>", "Run code and report how long it took"),
+    (":type <expr>", "Show the type of an expression's value"),
+    (
+        ":paste",
+        "Read lines until :end, then run them as one entry",
+    ),
+    (
+        ":save <file>",
+        "Write the entries that ran cleanly to a script",
+    ),
+    (":env", "List the globals defined so far"),
+    (":show", "Print the entries that ran cleanly"),
+    (":reset", "Forget everything defined in this session"),
+    (":help", "Show this list"),
+];
+
+/// Entries that ran cleanly, in order, so `:save` can turn a session into a script.
+#[derive(Default)]
+struct Transcript {
+    entries: Vec<String>,
+}
+
+impl Transcript {
+    fn record(&mut self, source: &str, status: Status) {
+        let source = source.trim();
+        if status != Status::Ok || source.is_empty() {
+            return;
+        }
+        // REPL entries may leave off the final `;`, but the saved script has to parse strictly.
+        if source.ends_with(';') || source.ends_with('}') {
+            self.entries.push(source.to_string());
+        } else {
+            self.entries.push(format!("{};", source));
+        }
+    }
+
+    fn script(&self) -> String {
+        let mut script = self.entries.join("\n");
+        script.push('\n');
+        script
+    }
+
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.script())
+    }
+}
+
+fn meta_command(line: &str, interpreter: &mut Interpreter, transcript: &mut Transcript) {
+    let stdout = interpreter.stdout.clone();
+    let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+    let argument = argument.trim();
+    match command {
+        ":load" if argument.is_empty() => {
+            writeln!(stdout.borrow_mut(), "Usage: :load <file>").unwrap();
+        }
+        ":load" => match std::fs::read_to_string(argument) {
+            Ok(source) => {
+                let status = slow_lox::run(source.clone(), interpreter);
+                transcript.record(&source, status);
+            }
+            Err(e) => {
+                writeln!(stdout.borrow_mut(), "Could not read '{}': {}", argument, e).unwrap()
+            }
+        },
+        ":time" => {
+            let start = Instant::now();
+            let status = slow_lox::run_interactive(argument.to_string(), interpreter);
+            transcript.record(argument, status);
+            writeln!(stdout.borrow_mut(), "Elapsed: {:?}", start.elapsed()).unwrap();
+        }
+        ":type" => {
+            if let Some(value) = slow_lox::evaluate(argument.to_string(), interpreter) {
+                writeln!(
+                    stdout.borrow_mut(),
+                    "{}",
+                    value.primitive.type_description()
+                )
+                .unwrap();
+            }
+        }
+        ":env" => {
+            let mut globals = interpreter
+                .environment
+                .borrow()
+                .globals()
+                .into_iter()
+                .collect::<Vec<_>>();
+            globals.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, value) in globals {
+                // Natives are always there; listing them would bury what the user defined.
+                if !matches!(value.primitive, Primitive::Native(_)) {
+                    let value = value.primitive.display(interpreter.precision());
+                    writeln!(stdout.borrow_mut(), "{} = {}", name, value).unwrap();
+                }
+            }
+        }
+        ":save" if argument.is_empty() => {
+            writeln!(stdout.borrow_mut(), "Usage: :save <file>").unwrap();
+        }
+        ":save" => {
+            if let Err(e) = transcript.save(argument) {
+                writeln!(stdout.borrow_mut(), "Could not write '{}': {}", argument, e).unwrap();
+            }
+        }
+        ":show" if !transcript.entries.is_empty() => {
+            let script = transcript.script();
+            let script = if slow_lox::diagnostic::color() {
+                slow_lox::highlight::ansi(&script)
+            } else {
+                script
+            };
+            write!(stdout.borrow_mut(), "{}", script).unwrap();
+        }
+        ":show" => {}
+        ":reset" => {
+            interpreter.reset();
+            *transcript = Transcript::default();
+        }
+        ":help" => {
+            for (usage, description) in COMMANDS {
+                writeln!(stdout.borrow_mut(), "{:<14} {}", usage, description).unwrap();
+            }
+            writeln!(stdout.borrow_mut(), "{:<14} Quit", "q").unwrap();
+        }
+        _ => writeln!(
+            stdout.borrow_mut(),
+            "Unknown command '{}'. Type :help for a list.",
+            command
+        )
+        .unwrap(),
+    }
+}
+
+/// Collects lines verbatim until `:end` or end of input. Returns `None` if cancelled with Ctrl-C.
+fn read_paste(input: &mut Input, interpreter: &Interpreter) -> Option<String> {
+    let stdout = interpreter.stdout.clone();
+    writeln!(
+        stdout.borrow_mut(),
+        "Paste mode; finish with :end on its own line."
+    )
+    .unwrap();
+    let mut pasted = String::new();
+    loop {
+        match input.read_line("", interpreter) {
+            Line::Text(line) if line.trim() == ":end" => break,
+            Line::Text(line) => {
+                pasted.push_str(line.trim_end_matches(['\r', '\n']));
+                pasted.push('\n');
+            }
+            Line::Interrupted => return None,
+            Line::Eof => break,
+        }
+    }
+    Some(pasted)
+}
+
+/// Runs an interactive session, first replaying a script saved with `:save` if one is given.
+pub fn run(interpreter: &mut Interpreter, config: &Config, replay: Option<&str>) {
+    let stdout = interpreter.stdout.clone();
+    let mut transcript = Transcript::default();
+    for path in &config.preload {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                slow_lox::run(source, interpreter);
+            }
+            Err(e) => eprintln!("Could not preload '{}': {}", path, e),
+        }
+    }
+    if let Some(path) = replay {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                let status = slow_lox::run(source.clone(), interpreter);
+                transcript.record(&source, status);
+            }
+            Err(e) => {
+                eprintln!("Could not read '{}': {}", path, e);
+                std::process::exit(66);
+            }
+        }
+    }
+    writeln!(stdout.borrow_mut(), "Welcome to the Lox REPL!").unwrap();
+    writeln!(stdout.borrow_mut(), "Press q to quit.").unwrap();
+    let mut input = Input::new(interpreter);
+    // Ctrl-C at the prompt is handled by the line editor; while code runs, it interrupts the
+    // evaluation and leaves the session as it was.
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let interrupted = interpreter.interrupt_flag();
+        let _ = ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst));
+    }
+    let mut entry = String::new();
+    loop {
+        let prompt = if entry.is_empty() {
+            &config.prompt
+        } else {
+            &config.continuation_prompt
+        };
+        let line = match input.read_line(prompt, interpreter) {
+            Line::Text(line) => line,
+            Line::Interrupted => {
+                entry.clear();
+                continue;
+            }
+            Line::Eof => break,
+        };
+        let line = line.trim();
+        if entry.is_empty() && line.to_lowercase() == "q" {
+            break;
+        }
+        if entry.is_empty() && line == ":paste" {
+            if let Some(pasted) = read_paste(&mut input, interpreter) {
+                input.add_history(&pasted);
+                let status = slow_lox::run_interactive(pasted.clone(), interpreter);
+                transcript.record(&pasted, status);
+            }
+            continue;
+        }
+        // Meta-commands never reach the scanner, so they can't clash with Lox syntax.
+        if entry.is_empty() && line.starts_with(':') {
+            input.add_history(line);
+            meta_command(line, interpreter, &mut transcript);
+            continue;
+        }
+        // A blank continuation line submits the entry as it stands, errors and all.
+        let forced = !entry.is_empty() && line.is_empty();
+        if !entry.is_empty() {
+            entry.push('\n');
+        }
+        entry.push_str(line);
+        if !forced && slow_lox::is_incomplete(&entry) {
+            continue;
+        }
+        let entry = std::mem::take(&mut entry);
+        if !entry.trim().is_empty() {
+            input.add_history(&entry);
+        }
+        // Errors are already reported; the session carries on regardless.
+        let status = slow_lox::run_interactive(entry.clone(), interpreter);
+        transcript.record(&entry, status);
+    }
+    input.save_history();
+    writeln!(stdout.borrow_mut(), "Bye!").unwrap();
+}
+
+/// The prompt `breakpoint()` opens. Entries run in the scope the breakpoint was hit in, and
+/// `:continue` or end of input resumes the program.
+pub fn breakpoint(interpreter: &mut Interpreter) {
+    let mut input = Input::new(interpreter);
+    debug(&mut input, interpreter);
+}
+
+fn debug(input: &mut Input, interpreter: &mut Interpreter) {
+    let stdout = interpreter.stdout.clone();
+    writeln!(
+        stdout.borrow_mut(),
+        "Paused at breakpoint. :continue resumes, :locals lists variables in scope."
+    )
+    .unwrap();
+    loop {
+        let line = match input.read_line("debug> ", interpreter) {
+            Line::Text(line) => line,
+            Line::Interrupted => continue,
+            Line::Eof => break,
+        };
+        match line.trim() {
+            "" => {}
+            ":continue" | ":c" => break,
+            ":locals" => {
+                let locals = interpreter.environment.borrow().locals();
+                for (name, value) in locals {
+                    let value = value.primitive.display(interpreter.precision());
+                    writeln!(stdout.borrow_mut(), "{} = {}", name, value).unwrap();
+                }
+            }
+            source => {
+                interpreter.with_dynamic_scope(|interpreter| {
+                    slow_lox::run_interactive(source.to_string(), interpreter)
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slow_lox::interpreter::environment::Environment;
+
+    #[test]
+    fn test_completions() {
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())));
+        slow_lox::run(
+            String::from(
+                "class Point { norm() {} scale(by) {} } var point = Point(); var printer;",
+            ),
+            &mut interpreter,
+        );
+        let completions = Completions::from(&interpreter);
+        assert_eq!(
+            completions.candidates("pri"),
+            (0, vec![String::from("print"), String::from("printer")])
+        );
+        assert_eq!(
+            completions.candidates("print point."),
+            (12, vec![String::from("norm"), String::from("scale")])
+        );
+        assert_eq!(
+            completions.candidates("Point.n"),
+            (6, vec![String::from("norm")])
+        );
+    }
+
+    #[test]
+    fn test_transcript() {
+        let mut transcript = Transcript::default();
+        transcript.record("var a = 1", Status::Ok);
+        transcript.record("print b;", Status::RuntimeError);
+        transcript.record("fun f() {}\n", Status::Ok);
+        assert_eq!(transcript.entries, vec!["var a = 1;", "fun f() {}"]);
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let stdin = Rc::new(RefCell::new(std::io::Cursor::new(
+            ":locals\nn = n * 10;\n:continue\nnever\n",
+        )));
+        let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stdout(stdout.clone());
+        interpreter.define_native("breakpoint", 0, move |interpreter, _| {
+            debug(&mut Input::Plain(stdin.clone()), interpreter);
+            Ok(().into())
+        });
+        slow_lox::run(
+            String::from("fun f(n) { breakpoint(); print n; } f(4);"),
+            &mut interpreter,
+        );
+        let output = String::from_utf8(stdout.borrow().clone()).unwrap();
+        assert!(output.ends_with("debug> n = 4\ndebug> debug> 40\n"));
+    }
+}
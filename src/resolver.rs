@@ -1,65 +1,279 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
 
 use crate::{
-    expr::Expr,
-    interpreter::{InterpretError, Interpreter},
+    expr::{self, Expr, Variable},
+    fold,
+    interpreter::{ErrorKind, InterpretError, Interpreter},
+    lint::Rule,
     stmt::Stmt,
-    token::Token,
+    token::{Token, TokenType},
 };
 
 #[derive(Clone)]
 enum FunctionType {
     None,
     Function,
+    Method,
+    /// A class's `init` method.
+    Initializer,
+}
+
+#[derive(Clone, Copy)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// What declared a local, for warning about it.
+#[derive(Clone, Copy)]
+enum Declaration {
+    Variable,
+    Function,
+    Class,
+    Parameter,
+    /// The name a `with` statement gives its resource.
+    Resource,
+}
+
+impl Declaration {
+    fn name(self) -> &'static str {
+        match self {
+            Declaration::Variable => "variable",
+            Declaration::Function => "function",
+            Declaration::Class => "class",
+            Declaration::Parameter => "parameter",
+            Declaration::Resource => "resource",
+        }
+    }
+
+    /// How the local is described when it's never used, or None if that's fine: a callback
+    /// often has no use for some of its parameters, and a resource is used when it's closed.
+    fn unused(self) -> Option<&'static str> {
+        match self {
+            Declaration::Variable => Some("Local variable"),
+            Declaration::Function => Some("Function"),
+            Declaration::Class => Some("Class"),
+            Declaration::Parameter | Declaration::Resource => None,
+        }
+    }
+}
+
+struct Local {
+    token: Token,
+    declaration: Declaration,
+    defined: bool,
+    used: bool,
+    /// Index into the resolver's known functions, for a `fun` declaration.
+    function: Option<usize>,
+}
+
+/// A local `fun` declaration and the calls made to it by name. The calls are checked against
+/// its arity once the whole program has been resolved, unless the name gets reassigned.
+struct KnownFunction {
+    arity: usize,
+    calls: Vec<NamedCall>,
+    reassigned: bool,
+}
+
+/// A call to a function by name: its closing paren, how many arguments it passes and the
+/// byte range of the whole call.
+struct NamedCall {
+    paren: Token,
+    arguments: usize,
+    span: Range<usize>,
+}
+
+/// A scope the resolver has been through and what was declared in it, for `--dump-symbols`.
+struct ScopeRecord {
+    /// What opened the scope, e.g. `fun f`.
+    label: String,
+    /// How many scopes enclose it, not counting the globals.
+    depth: usize,
+    declared: Vec<(Token, Declaration)>,
 }
 
 pub struct Resolver<'a> {
-    stacks: Vec<HashMap<String, bool>>,
+    stacks: Vec<HashMap<String, Local>>,
+    /// Every scope so far in the order they were opened, and which of them are still open.
+    scopes: Vec<ScopeRecord>,
+    open_scopes: Vec<usize>,
+    /// Names that weren't found in any scope, so are looked up in the globals at runtime.
+    globals: Vec<Token>,
     interpreter: &'a mut Interpreter,
     current_function: FunctionType,
+    current_class: ClassType,
+    /// How many loops enclose the code being resolved, within the current function.
+    loop_depth: usize,
+    functions: Vec<KnownFunction>,
+    /// The arity of each global declared with `fun`. Globals can be called before they're
+    /// declared, so calls to them are kept by name until the end.
+    global_functions: HashMap<String, usize>,
+    global_calls: Vec<(String, NamedCall)>,
+    /// Globals that are assigned or declared more than once, so calls may reach anything.
+    reassigned_globals: HashSet<String>,
 }
 
 impl<'a> Resolver<'a> {
     pub fn new(interpreter: &'a mut Interpreter) -> Self {
         Self {
             stacks: Vec::new(),
+            scopes: Vec::new(),
+            open_scopes: Vec::new(),
+            globals: Vec::new(),
             interpreter,
             current_function: FunctionType::None,
+            current_class: ClassType::None,
+            loop_depth: 0,
+            functions: Vec::new(),
+            global_functions: HashMap::new(),
+            global_calls: Vec::new(),
+            reassigned_globals: HashSet::new(),
         }
     }
 
-    fn begin_scope(&mut self) {
+    fn begin_scope(&mut self, label: String) {
+        self.open_scopes.push(self.scopes.len());
+        self.scopes.push(ScopeRecord {
+            label,
+            depth: self.stacks.len(),
+            declared: Vec::new(),
+        });
         self.stacks.push(HashMap::new());
     }
 
+    /// Leaves the innermost scope, warning about what it declared but never read. Names that
+    /// start with `_` are expected to go unused.
     fn end_scope(&mut self) {
-        self.stacks.pop();
+        let Some(scope) = self.stacks.pop() else {
+            return;
+        };
+        self.open_scopes.pop();
+        let mut unused = scope
+            .into_values()
+            .filter(|local| !local.used && !local.token.lexeme.starts_with('_'))
+            .filter_map(|local| Some((local.declaration.unused()?, local.token)))
+            .collect::<Vec<_>>();
+        unused.sort_by_key(|(_, token)| (token.line, token.column));
+        for (what, token) in unused {
+            self.warning(
+                Rule::UnusedLocal,
+                &token,
+                &format!("{} '{}' is never used.", what, token.lexeme),
+            );
+        }
     }
 
-    fn declare(&mut self, name: Token) -> Result<(), InterpretError> {
+    fn declare(&mut self, name: Token, declaration: Declaration) -> Result<(), InterpretError> {
+        let shadowed = self
+            .stacks
+            .iter()
+            .rev()
+            .skip(1)
+            .find_map(|scope| scope.get(&name.lexeme))
+            .map(|local| local.token.line);
+        if let Some(line) = shadowed {
+            let message = format!(
+                "'{}' shadows the declaration on line {}.",
+                name.lexeme, line
+            );
+            self.warning(Rule::Shadowing, &name, &message);
+        }
+        if self.stacks.is_empty() && self.global_functions.contains_key(&name.lexeme) {
+            self.reassigned_globals.insert(name.lexeme.clone());
+        }
+        if let Some(&open) = self.open_scopes.last() {
+            self.scopes[open].declared.push((name.clone(), declaration));
+        }
         if let Some(scope) = self.stacks.last_mut() {
             if scope.contains_key(&name.lexeme) {
                 return Err(InterpretError::new(
+                    ErrorKind::ResolveError,
                     String::from("Variable with this name already declared in this scope."),
                     name,
                 ));
             }
-            scope.insert(name.lexeme.clone(), false);
+            scope.insert(
+                name.lexeme.clone(),
+                Local {
+                    token: name,
+                    declaration,
+                    defined: false,
+                    used: false,
+                    function: None,
+                },
+            );
         }
         Ok(())
     }
 
-    fn define(&mut self, name: Token) -> Result<(), InterpretError> {
+    fn warning(&mut self, rule: Rule, token: &Token, message: &str) {
+        self.interpreter
+            .session
+            .warning(rule, &token.into(), message);
+    }
+
+    /// Opens a scope holding just `this` or `super`, for a class's methods to close over.
+    fn keyword_scope(&mut self, label: String, keyword: Token) {
+        self.begin_scope(label);
         if let Some(scope) = self.stacks.last_mut() {
-            scope.insert(name.lexeme.clone(), true);
+            scope.insert(
+                keyword.lexeme.clone(),
+                Local {
+                    token: keyword,
+                    declaration: Declaration::Variable,
+                    defined: true,
+                    used: true,
+                    function: None,
+                },
+            );
+        }
+    }
+
+    fn define(&mut self, name: Token) -> Result<(), InterpretError> {
+        if let Some(local) = self
+            .stacks
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
         }
         Ok(())
     }
 }
 
 impl<'a> Resolver<'a> {
+    /// Resolves a whole program, then checks calls made by name to functions it declares.
     pub fn resolve(&mut self, stmts: Vec<Stmt>) -> Result<(), InterpretError> {
+        self.resolve_stmts(stmts)?;
+        log::debug!(
+            "resolved {} scopes, {} calls left to check",
+            self.scopes.len(),
+            self.functions
+                .iter()
+                .map(|function| function.calls.len())
+                .sum::<usize>()
+                + self.global_calls.len()
+        );
+        self.check_arities()
+    }
+
+    fn resolve_stmts(&mut self, stmts: Vec<Stmt>) -> Result<(), InterpretError> {
+        let mut returned = None;
         for stmt in stmts {
+            if let Some(keyword) = returned.take() {
+                self.warning(
+                    Rule::UnreachableCode,
+                    &keyword,
+                    "Code after 'return' is unreachable.",
+                );
+            }
+            if let Stmt::Return(keyword, _) = &stmt {
+                returned = Some(keyword.clone());
+            }
             self.resolve_stmt(stmt)?;
         }
         Ok(())
@@ -67,59 +281,158 @@ impl<'a> Resolver<'a> {
 
     fn resolve_stmt(&mut self, stmt: Stmt) -> Result<(), InterpretError> {
         match stmt {
-            Stmt::Function(token, tokens, stmts) => {
-                self.declare(token.clone())?;
+            Stmt::Function(token, tokens, stmts, _) => {
+                self.declare(token.clone(), Declaration::Function)?;
                 self.define(token.clone())?;
-                self.resolve_function(tokens, stmts, FunctionType::Function)?;
+                self.declare_function(&token, tokens.len());
+                self.resolve_function(&token, tokens, stmts, FunctionType::Function)?;
             }
             Stmt::Expr(expr) => {
                 self.resolve_expr(expr)?;
             }
             Stmt::If(condition, then_branch, else_branch) => {
+                self.check_condition(&condition, true);
                 self.resolve_expr(condition)?;
                 self.resolve_stmt(*then_branch)?;
                 if let Some(else_stmt) = else_branch {
                     self.resolve_stmt(*else_stmt)?;
                 }
             }
-            Stmt::Print(expr) => {
-                self.resolve_expr(expr)?;
+            Stmt::Print(values) => {
+                for value in values {
+                    self.resolve_expr(value)?;
+                }
             }
-            Stmt::Return(token, expr) => {
+            Stmt::Return(token, values) => {
                 if let FunctionType::None = self.current_function {
                     return Err(InterpretError::new(
+                        ErrorKind::ResolveError,
                         String::from("Cannot return from top-level code."),
                         token,
                     ));
                 }
-                if let Some(expr) = expr {
-                    self.resolve_expr(expr)?;
+                if !values.is_empty() {
+                    if let FunctionType::Initializer = self.current_function {
+                        return Err(InterpretError::new(
+                            ErrorKind::ResolveError,
+                            String::from("Can't return a value from an initializer."),
+                            token,
+                        ));
+                    }
+                }
+                for value in values {
+                    self.resolve_expr(value)?;
                 }
             }
-            Stmt::While(condition, body) => {
-                self.resolve_expr(condition)?;
+            Stmt::While(condition, body, increment) => {
+                // `while (true)` is how infinite loops are written, so only warn about loops
+                // that never run.
+                if let Some(condition) = condition {
+                    self.check_condition(&condition, false);
+                    self.resolve_expr(condition)?;
+                }
+                self.loop_depth += 1;
                 self.resolve_stmt(*body)?;
+                self.loop_depth -= 1;
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment)?;
+                }
             }
             Stmt::Block(stmts) => {
-                self.begin_scope();
-                self.resolve(stmts)?;
+                self.begin_scope(String::from("block"));
+                self.resolve_stmts(stmts)?;
                 self.end_scope();
             }
-            Stmt::Var(name, expr) => {
-                self.declare(name.clone())?;
+            Stmt::With(_, resource, name, body) => {
+                self.resolve_expr(resource)?;
+                self.begin_scope(String::from("with"));
+                self.declare(name.clone(), Declaration::Resource)?;
+                self.define(name)?;
+                self.resolve_stmts(body)?;
+                self.end_scope();
+            }
+            Stmt::Var(name, expr, _) => {
+                self.declare(name.clone(), Declaration::Variable)?;
                 if let Some(expr) = expr {
                     self.resolve_expr(expr)?;
                 }
                 self.define(name)?;
             }
-            Stmt::Assign(_, expr) => {
-                self.resolve_expr(expr)?;
+            Stmt::Unpack(Some(_), names, values) => {
+                for name in &names {
+                    self.declare(name.clone(), Declaration::Variable)?;
+                }
+                for value in values {
+                    self.resolve_expr(value)?;
+                }
+                for name in names {
+                    self.define(name)?;
+                }
+            }
+            Stmt::Unpack(None, names, values) => {
+                for value in values {
+                    self.resolve_expr(value)?;
+                }
+                for name in names {
+                    self.reassign(&name);
+                    // Keyed like a read, but assigning doesn't count as using it.
+                    self.resolve_local(
+                        Expr::Variable(Variable { name: name.clone() }),
+                        name,
+                        false,
+                    );
+                }
             }
-            Stmt::Class(token, methods) => {
-                self.declare(token.clone())?;
+            Stmt::Class(token, superclass, methods, _) => {
+                self.declare(token.clone(), Declaration::Class)?;
                 self.define(token.clone())?;
+                let class_type = match &superclass {
+                    Some(superclass) if superclass.name.lexeme == token.lexeme => {
+                        return Err(InterpretError::new(
+                            ErrorKind::ResolveError,
+                            String::from("A class can't inherit from itself."),
+                            superclass.name.clone(),
+                        ));
+                    }
+                    Some(_) => ClassType::Subclass,
+                    None => ClassType::Class,
+                };
+                let enclosing_class = std::mem::replace(&mut self.current_class, class_type);
+                // Methods close over a scope holding `super`, if there is one, inside one
+                // holding `this`.
+                if let Some(superclass) = superclass.clone() {
+                    self.resolve_var_expr(Expr::Variable(superclass))?;
+                    let keyword = Token::new(TokenType::SUPER, String::from("super"), token.line);
+                    self.keyword_scope(format!("super of {}", token.lexeme), keyword);
+                }
+                let keyword = Token::new(TokenType::THIS, String::from("this"), token.line);
+                self.keyword_scope(format!("class {}", token.lexeme), keyword);
+                for method in methods {
+                    if let Stmt::Function(name, params, body, _) = method {
+                        let function_type = if name.lexeme == "init" {
+                            FunctionType::Initializer
+                        } else {
+                            FunctionType::Method
+                        };
+                        self.resolve_function(&name, params, body, function_type)?;
+                    }
+                }
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.current_class = enclosing_class;
+            }
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => {
+                if self.loop_depth == 0 {
+                    let message = format!("Must be inside a loop to use '{}'.", keyword.lexeme);
+                    return Err(InterpretError::new(
+                        ErrorKind::ResolveError,
+                        message,
+                        keyword,
+                    ));
+                }
             }
-            Stmt::Break => {}
         }
         Ok(())
     }
@@ -127,6 +440,21 @@ impl<'a> Resolver<'a> {
     fn resolve_expr(&mut self, expr: Expr) -> Result<(), InterpretError> {
         match expr {
             Expr::Call(call) => {
+                if let Expr::Variable(variable) = call.callee.as_ref() {
+                    let named = NamedCall {
+                        paren: call.paren.clone(),
+                        arguments: call.arguments.len(),
+                        span: expr::span(&call.callee).start..call.paren.end,
+                    };
+                    match self.local_function(&variable.name) {
+                        Some(Some(function)) => self.functions[function].calls.push(named),
+                        Some(None) => {}
+                        None => {
+                            let name = variable.name.lexeme.clone();
+                            self.global_calls.push((name, named));
+                        }
+                    }
+                }
                 self.resolve_expr(*call.callee)?;
                 for arg in call.arguments {
                     self.resolve_expr(arg)?;
@@ -134,9 +462,12 @@ impl<'a> Resolver<'a> {
             }
             Expr::Assign(assign) => {
                 self.resolve_expr(*assign.value.clone())?;
-                self.resolve_local(*assign.value, assign.name);
+                let name = assign.name.clone();
+                self.reassign(&name);
+                self.resolve_local(Expr::Assign(assign), name, false);
             }
             Expr::Binary(binary) => {
+                self.check_mixed_equality(&binary.operator, &binary.left, &binary.right);
                 self.resolve_expr(*binary.left)?;
                 self.resolve_expr(*binary.right)?;
             }
@@ -152,17 +483,10 @@ impl<'a> Resolver<'a> {
                 self.resolve_expr(*unary.right)?;
             }
             Expr::Variable(var) => {
-                if let Some(scope) = self.stacks.last_mut() {
-                    if scope.get(&var.name.lexeme) == Some(&false) {
-                        crate::error(
-                            var.name.line,
-                            "Cannot read local variable in its own initializer.",
-                        );
-                    }
-                }
                 self.resolve_var_expr(Expr::Variable(var))?;
             }
             Expr::Ternary(ternary) => {
+                self.check_condition(&ternary.condition, true);
                 self.resolve_expr(*ternary.condition)?;
                 self.resolve_expr(*ternary.then_branch)?;
                 self.resolve_expr(*ternary.else_branch)?;
@@ -174,53 +498,525 @@ impl<'a> Resolver<'a> {
                 self.resolve_expr(*set.expr)?;
                 self.resolve_expr(*set.value)?;
             }
+            Expr::Super(super_expr) => {
+                let message = match self.current_class {
+                    ClassType::None => "Can't use 'super' outside of a class.",
+                    ClassType::Class => "Can't use 'super' in a class with no superclass.",
+                    ClassType::Subclass => "",
+                };
+                if !message.is_empty() {
+                    return Err(InterpretError::new(
+                        ErrorKind::ResolveError,
+                        String::from(message),
+                        super_expr.keyword,
+                    ));
+                }
+                let keyword = super_expr.keyword.clone();
+                self.resolve_local(Expr::Super(super_expr), keyword, true);
+            }
+            Expr::This(this) => {
+                if let ClassType::None = self.current_class {
+                    return Err(InterpretError::new(
+                        ErrorKind::ResolveError,
+                        String::from("Can't use 'this' outside of a class."),
+                        this.keyword,
+                    ));
+                }
+                let keyword = this.keyword.clone();
+                self.resolve_local(Expr::This(this), keyword, true);
+            }
         }
         Ok(())
     }
 
+    /// Records the `fun` declaration just made for `name`, so calls to it can be checked.
+    fn declare_function(&mut self, name: &Token, arity: usize) {
+        let Some(scope) = self.stacks.last_mut() else {
+            self.global_functions
+                .entry(name.lexeme.clone())
+                .or_insert(arity);
+            return;
+        };
+        if let Some(local) = scope.get_mut(&name.lexeme) {
+            local.function = Some(self.functions.len());
+            self.functions.push(KnownFunction {
+                arity,
+                calls: Vec::new(),
+                reassigned: false,
+            });
+        }
+    }
+
+    /// What `name` refers to if it's a local: `Some(Some(_))` for a known function. None
+    /// means it's a global.
+    fn local_function(&self, name: &Token) -> Option<Option<usize>> {
+        self.stacks
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))
+            .map(|local| local.function)
+    }
+
+    fn reassign(&mut self, name: &Token) {
+        match self.local_function(name) {
+            Some(Some(function)) => self.functions[function].reassigned = true,
+            Some(None) => {}
+            None => {
+                self.reassigned_globals.insert(name.lexeme.clone());
+            }
+        }
+    }
+
+    /// Fails on the first call, in source order, with the wrong number of arguments for the
+    /// function it names.
+    fn check_arities(&self) -> Result<(), InterpretError> {
+        let locals = self
+            .functions
+            .iter()
+            .filter(|function| !function.reassigned)
+            .flat_map(|function| function.calls.iter().map(|call| (call, function.arity)));
+        let globals = self
+            .global_calls
+            .iter()
+            .filter(|(name, _)| !self.reassigned_globals.contains(name))
+            .filter_map(|(name, call)| Some((call, *self.global_functions.get(name)?)));
+        let mismatch = locals
+            .chain(globals)
+            .filter(|(call, arity)| call.arguments != *arity)
+            .min_by_key(|(call, _)| (call.paren.line, call.paren.column));
+        match mismatch {
+            Some((call, arity)) => Err(InterpretError::new(
+                ErrorKind::ResolveError,
+                format!("Expected {} arguments but got {}.", arity, call.arguments),
+                call.paren.clone(),
+            )
+            .within(call.span.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Warns about a condition that always comes out the same way. One that's always true is
+    /// only reported if `when_true` is set.
+    fn check_condition(&mut self, condition: &Expr, when_true: bool) {
+        let token = expr::first_token(condition);
+        match fold::constant(condition).map(|value| value.is_truthy()) {
+            Some(true) if when_true => {
+                let token = token.clone();
+                self.warning(Rule::ConstantCondition, &token, "Condition is always true.");
+            }
+            Some(false) => {
+                let token = token.clone();
+                self.warning(
+                    Rule::ConstantCondition,
+                    &token,
+                    "Condition is always false.",
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Warns about `==` or `!=` between two literals that can never be equal.
+    fn check_mixed_equality(&mut self, operator: &Token, left: &Expr, right: &Expr) {
+        let always = match operator.token_type {
+            TokenType::EQUAL_EQUAL => "false",
+            TokenType::BANG_EQUAL => "true",
+            _ => return,
+        };
+        let (Some(left), Some(right)) = (literal_type(left), literal_type(right)) else {
+            return;
+        };
+        if left != right {
+            self.warning(
+                Rule::MixedEquality,
+                operator,
+                &format!(
+                    "Comparing a {} with a {} using '{}' is always {}.",
+                    left, right, operator.lexeme, always
+                ),
+            );
+        }
+    }
+
     fn resolve_var_expr(&mut self, expr: Expr) -> Result<(), InterpretError> {
         let expr_clone = expr.clone();
         if let Expr::Variable(var) = expr {
             if let Some(scope) = self.stacks.last_mut() {
-                if scope.get(&var.name.lexeme) == Some(&false) {
-                    crate::error(
-                        var.name.line,
-                        "Cannot read local variable in its own initializer.",
-                    );
+                if scope
+                    .get(&var.name.lexeme)
+                    .is_some_and(|local| !local.defined)
+                {
+                    return Err(InterpretError::new(
+                        ErrorKind::ResolveError,
+                        String::from("Cannot read local variable in its own initializer."),
+                        var.name,
+                    ));
                 }
             }
-            self.resolve_local(expr_clone, var.name);
+            self.resolve_local(expr_clone, var.name, true);
         }
         Ok(())
     }
 
     fn resolve_function(
         &mut self,
+        name: &Token,
         params: Vec<Token>,
         stmts: Vec<Stmt>,
         function_type: FunctionType,
     ) -> Result<(), InterpretError> {
         let enclosing_function = self.current_function.clone();
         self.current_function = function_type;
+        // A loop around a function declaration doesn't make `break` valid in its body.
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
 
-        self.begin_scope();
+        self.begin_scope(format!("fun {}", name.lexeme));
         for param in params {
-            self.declare(param.clone())?;
+            self.declare(param.clone(), Declaration::Parameter)?;
             self.define(param.clone())?;
         }
-        self.resolve(stmts)?;
+        self.resolve_stmts(stmts)?;
         self.end_scope();
 
         self.current_function = enclosing_function;
+        self.loop_depth = enclosing_loops;
         Ok(())
     }
 
-    fn resolve_local(&mut self, expr: Expr, name: Token) {
-        for (i, scope) in self.stacks.iter().enumerate().rev() {
-            if scope.contains_key(&name.lexeme) {
-                self.interpreter.resolve(expr, i);
+    /// Records how far out `name` was declared, under `expr`, marking the local used if `read`.
+    fn resolve_local(&mut self, expr: Expr, name: Token, read: bool) {
+        for (distance, scope) in self.stacks.iter_mut().rev().enumerate() {
+            if let Some(local) = scope.get_mut(&name.lexeme) {
+                if read {
+                    local.used = true;
+                }
+                log::trace!(
+                    "{}:{} {} resolved at depth {}",
+                    name.line,
+                    name.column,
+                    name.lexeme,
+                    distance
+                );
+                self.interpreter.resolve(expr, distance);
                 return;
             }
         }
+        log::trace!("{}:{} {} is global", name.line, name.column, name.lexeme);
+        self.globals.push(name);
+    }
+
+    /// Every scope the resolver went through with what it declared, then where each local
+    /// reference resolved to and which names were left to the globals.
+    pub fn symbols(&self) -> String {
+        let mut out = String::from("scopes:\n");
+        for scope in &self.scopes {
+            let indent = "  ".repeat(scope.depth + 1);
+            out.push_str(&format!("{}{}\n", indent, scope.label));
+            for (token, declaration) in &scope.declared {
+                out.push_str(&format!(
+                    "{}  {} {} (line {})\n",
+                    indent,
+                    declaration.name(),
+                    token.lexeme,
+                    token.line
+                ));
+            }
+        }
+        let mut locals = self
+            .interpreter
+            .locals
+            .iter()
+            .filter_map(|(expr, depth)| match expr {
+                Expr::Variable(variable) => Some((&variable.name, "", depth)),
+                Expr::Assign(assignment) => Some((&assignment.name, " =", depth)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        locals.sort_by_key(|(token, _, _)| (token.line, token.column));
+        out.push_str("locals:\n");
+        for (token, assigned, depth) in locals {
+            out.push_str(&format!(
+                "  {}:{} {}{} -> depth {}\n",
+                token.line, token.column, token.lexeme, assigned, depth
+            ));
+        }
+        out.push_str("globals:\n");
+        for token in &self.globals {
+            out.push_str(&format!(
+                "  {}:{} {}\n",
+                token.line, token.column, token.lexeme
+            ));
+        }
+        out
+    }
+}
+
+/// The type of a literal, or None if `expr` isn't one.
+fn literal_type(expr: &Expr) -> Option<&'static str> {
+    let Expr::Literal(literal) = expr else {
+        return None;
+    };
+    match literal.value.token_type {
+        TokenType::NUMBER => Some("number"),
+        TokenType::STRING => Some("string"),
+        TokenType::TRUE | TokenType::FALSE => Some("boolean"),
+        TokenType::NIL => Some("nil"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::environment::Environment, parser::Parser, scanner::Scanner};
+    use std::{cell::RefCell, rc::Rc};
+
+    fn interpreter() -> Interpreter {
+        Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+            .with_stderr(Rc::new(RefCell::new(std::io::sink())))
+    }
+
+    /// Every variable the resolver placed, in source order, with how many scopes out it is.
+    fn resolved(source: &str) -> Vec<(String, usize)> {
+        let mut interpreter = interpreter();
+        let stmts = Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        Resolver::new(&mut interpreter).resolve(stmts).unwrap();
+        let mut locals = interpreter
+            .locals
+            .iter()
+            .map(|(expr, distance)| match expr {
+                Expr::Variable(variable) => (variable.name.clone(), *distance),
+                Expr::Assign(assign) => (assign.name.clone(), *distance),
+                expr => panic!("resolved something that isn't a variable: {:?}", expr),
+            })
+            .collect::<Vec<_>>();
+        locals.sort_by_key(|(name, _)| (name.line, name.column));
+        locals
+            .into_iter()
+            .map(|(name, distance)| (name.lexeme, distance))
+            .collect()
+    }
+
+    fn names(names: &[(&str, usize)]) -> Vec<(String, usize)> {
+        names
+            .iter()
+            .map(|(name, distance)| (name.to_string(), *distance))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_expression() {
+        assert_eq!(
+            resolved("{ var o; var v; o.x = v; print o.x; o.f(v); print v ? !v : (v) or v + v; }"),
+            names(&[
+                ("o", 0),
+                ("v", 0),
+                ("o", 0),
+                ("o", 0),
+                ("v", 0),
+                ("v", 0),
+                ("v", 0),
+                ("v", 0),
+                ("v", 0),
+                ("v", 0),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_distances() {
+        assert_eq!(
+            resolved("{ var a; { fun f(b) { print a; print b; print f; } } }"),
+            names(&[("a", 2), ("b", 0), ("f", 1)])
+        );
+        assert_eq!(resolved("var g; print g; { print g; }"), names(&[]));
+    }
+
+    #[test]
+    fn test_method_bodies() {
+        assert_eq!(
+            resolved("class C { m(p) { var l = p; return l; } }"),
+            names(&[("p", 0), ("l", 0)])
+        );
+    }
+
+    /// The warnings reported while resolving `source`, each followed by its location line.
+    fn warnings(source: &str) -> Vec<String> {
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = interpreter().with_stderr(stderr.clone());
+        let stmts = Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        Resolver::new(&mut interpreter).resolve(stmts).unwrap();
+        let stderr = String::from_utf8(stderr.borrow().clone()).unwrap();
+        stderr
+            .lines()
+            .filter(|line| line.starts_with("warning") || line.contains("-->"))
+            .map(String::from)
+            .collect()
+    }
+
+    fn resolve_error(source: &str) -> Option<InterpretError> {
+        let stmts = Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        Resolver::new(&mut interpreter()).resolve(stmts).err()
+    }
+
+    #[test]
+    fn test_top_level_return() {
+        for source in ["print 1;\nreturn 5;", "if (true) {\n  return;\n}"] {
+            let error = resolve_error(source).unwrap();
+            assert_eq!(error.kind, ErrorKind::ResolveError);
+            assert_eq!(error.message, "Cannot return from top-level code.");
+            assert_eq!(error.token.line, 2);
+        }
+        assert!(resolve_error("fun f() { return 1; } class C { m() { return; } }").is_none());
+    }
+
+    #[test]
+    fn test_initializer_return() {
+        let error = resolve_error("class C {\n  init() {\n    return 1;\n  }\n}").unwrap();
+        assert_eq!(error.message, "Can't return a value from an initializer.");
+        assert_eq!(error.token.line, 3);
+        assert!(resolve_error("class C { init() { return; } m() { return 1; } }").is_none());
+        assert!(resolve_error("fun init() { return 1; }").is_none());
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        for source in [
+            "break;",
+            "if (true) {\n  break;\n}",
+            "while (true) {\n  fun f() { break; }\n}",
+        ] {
+            let error = resolve_error(source).unwrap();
+            assert_eq!(error.kind, ErrorKind::ResolveError);
+            assert_eq!(error.message, "Must be inside a loop to use 'break'.");
+        }
+        assert!(resolve_error("while (true) { if (true) break; }").is_none());
+        assert!(resolve_error("for (;;) { { break; } }").is_none());
+
+        let error = resolve_error("fun f() {\n  continue;\n}").unwrap();
+        assert_eq!(error.message, "Must be inside a loop to use 'continue'.");
+        assert!(resolve_error("while (true) { continue; }").is_none());
+    }
+
+    #[test]
+    fn test_this_outside_class() {
+        for source in ["print this;", "fun f() { return this; }"] {
+            let error = resolve_error(source).unwrap();
+            assert_eq!(error.message, "Can't use 'this' outside of a class.");
+        }
+        assert!(resolve_error("class A { m() { fun f() { return this; } return f; } }").is_none());
+    }
+
+    #[test]
+    fn test_super_errors() {
+        for (source, message) in [
+            ("class A < A {}", "A class can't inherit from itself."),
+            ("print super.m;", "Can't use 'super' outside of a class."),
+            (
+                "class A { m() { super.m(); } }",
+                "Can't use 'super' in a class with no superclass.",
+            ),
+        ] {
+            assert_eq!(resolve_error(source).unwrap().message, message);
+        }
+        assert!(resolve_error("class A {} class B < A { m() { super.m(); } }").is_none());
+    }
+
+    #[test]
+    fn test_unused_locals() {
+        assert_eq!(
+            warnings("var global;\n{\n  var a = 1;\n  var b;\n  b = 2;\n  var _c;\n  fun f(unused) {}\n  fun g() { return a; }\n  print g();\n}"),
+            vec![
+                "warning[unused-local]: Local variable 'b' is never used.",
+                " --> line 4",
+                "warning[unused-local]: Function 'f' is never used.",
+                " --> line 7",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shadowing() {
+        // The locals are never used either; only the shadowing warnings matter here.
+        let shadowing =
+            warnings("var g;\n{\n  var a;\n  var g;\n  {\n    var a;\n  }\n  fun f(a) {}\n}")
+                .into_iter()
+                .filter(|line| line.starts_with("warning[shadowing]"))
+                .collect::<Vec<_>>();
+        assert_eq!(
+            shadowing,
+            vec![
+                "warning[shadowing]: 'a' shadows the declaration on line 3.",
+                "warning[shadowing]: 'a' shadows the declaration on line 3.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constant_conditions() {
+        assert_eq!(
+            warnings("if (true) print 1;\nwhile (1 > 2) print 2;\nwhile (true) {}\nfor (;;) {}\nprint nil ? 1 : 2;\nvar x; if (x) print x;"),
+            vec![
+                "warning[constant-condition]: Condition is always true.",
+                " --> line 1",
+                "warning[constant-condition]: Condition is always false.",
+                " --> line 2",
+                "warning[constant-condition]: Condition is always false.",
+                " --> line 5",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arity() {
+        let error = resolve_error("fun f(a, b) {}\nprint 1;\nf(1);").unwrap();
+        assert_eq!(error.kind, ErrorKind::ResolveError);
+        assert_eq!(error.message, "Expected 2 arguments but got 1.");
+        assert_eq!(error.token.line, 3);
+        assert!(resolve_error("{ fun g() { f(1, 2, 3); } fun f(a) {} }").is_none());
+        assert!(resolve_error("fun h() { return f(); } fun f(a) {}").is_some());
+        assert!(resolve_error("{ fun f(a) {} f(1); { var f; f(); } }").is_none());
+        assert!(resolve_error("fun f(a) {} f = clock; f();").is_none());
+        assert!(resolve_error("fun f(a) {} fun g() { f = clock; } f();").is_none());
+        assert!(resolve_error("fun f(a) {} fun f() {} f();").is_none());
+    }
+
+    #[test]
+    fn test_assignment() {
+        assert_eq!(resolved("{ var a; print a = 1; }"), names(&[("a", 0)]));
+        let stdout = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = interpreter().with_stdout(stdout.clone());
+        let source = "{ var a = 1; fun set() { print a = 2; } set(); print a; }";
+        assert_eq!(
+            crate::run(source.to_string(), &mut interpreter),
+            crate::Status::Ok
+        );
+        assert_eq!(
+            String::from_utf8(stdout.borrow().clone()).unwrap(),
+            "2\n2\n"
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut interpreter = interpreter();
+        let stmts =
+            Parser::new(Scanner::new(String::from("fun f(a) { { print a + g; } }")).scan_tokens())
+                .parse()
+                .unwrap();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve(stmts).unwrap();
+        assert_eq!(
+            resolver.symbols(),
+            "scopes:\n  fun f\n    parameter a (line 1)\n    block\n\
+             locals:\n  1:20 a -> depth 1\n\
+             globals:\n  1:24 g\n"
+        );
     }
 }
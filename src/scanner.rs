@@ -1,4 +1,4 @@
-use crate::error;
+use crate::diagnostic::Location;
 use crate::token::{Token, TokenType};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
@@ -7,7 +7,9 @@ lazy_static! {
     static ref KEYWORDS: HashMap<&'static str, TokenType> = {
         let mut map = HashMap::new();
         map.insert("and", TokenType::AND);
+        map.insert("break", TokenType::BREAK);
         map.insert("class", TokenType::CLASS);
+        map.insert("continue", TokenType::CONTINUE);
         map.insert("else", TokenType::ELSE);
         map.insert("false", TokenType::FALSE);
         map.insert("for", TokenType::FOR);
@@ -22,10 +24,34 @@ lazy_static! {
         map.insert("true", TokenType::TRUE);
         map.insert("var", TokenType::VAR);
         map.insert("while", TokenType::WHILE);
+        map.insert("with", TokenType::WITH);
+        map.insert("in", TokenType::IN);
         map
     };
 }
 
+/// All reserved words, in no particular order.
+pub fn keywords() -> impl Iterator<Item = &'static str> {
+    KEYWORDS.keys().copied()
+}
+
+/// One line per token, as `--dump-tokens` prints them: its `line:column`, its byte span, its
+/// type and its lexeme.
+pub fn dump_tokens(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            format!(
+                "{:>8} {:>9} {:<14} {}\n",
+                format!("{}:{}", token.line, token.column),
+                format!("{}..{}", token.start, token.end),
+                format!("{:?}", token.token_type),
+                token.lexeme
+            )
+        })
+        .collect()
+}
+
 fn match_keyword(identifier: &str) -> TokenType {
     match KEYWORDS.get(identifier) {
         Some(token_type) => *token_type,
@@ -33,38 +59,125 @@ fn match_keyword(identifier: &str) -> TokenType {
     }
 }
 
+#[derive(Debug)]
+pub struct ScanError {
+    pub location: Location,
+    pub message: String,
+}
+
+/// A comment, kept aside so tools like the formatter can put it back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The line the comment starts on.
+    pub line: usize,
+    /// The whole comment, including the `//` or `/* */`.
+    pub text: String,
+    /// Whether code comes before it on the same line.
+    pub trailing: bool,
+}
+
 pub struct Scanner {
     source: String,
+    /// The source's characters, which is what `start` and `current` count, and the byte
+    /// offset each starts at followed by the source's length.
+    chars: Vec<char>,
+    offsets: Vec<usize>,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    /// Where the current line starts, to work out columns.
+    line_start: usize,
+    errors: Vec<ScanError>,
+    unterminated: bool,
+    comments: Vec<Comment>,
+    /// Whether comments also go in the token stream.
+    comment_tokens: bool,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Scanner {
+        let (offsets, chars) = source.char_indices().unzip();
+        let mut offsets: Vec<usize> = offsets;
+        offsets.push(source.len());
         Scanner {
+            chars,
+            offsets,
             source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            errors: Vec::new(),
+            unterminated: false,
+            comments: Vec::new(),
+            comment_tokens: false,
         }
     }
 
+    /// Emits each comment as a COMMENT token holding its whole text, for tools that need to
+    /// see where comments are. The parser can't handle them.
+    pub fn with_comments(mut self) -> Self {
+        self.comment_tokens = true;
+        self
+    }
+
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
             self.scan_token();
         }
 
-        self.tokens
-            .push(Token::new(TokenType::EOF, String::new(), self.line));
+        self.start = self.current;
+        let end = self.source.len();
+        let eof = Token::at(TokenType::EOF, String::new(), self.line, self.column());
+        self.tokens.push(eof.with_span(end, end));
+        log::debug!(
+            "scanned {} tokens with {} errors",
+            self.tokens.len(),
+            self.errors.len()
+        );
         self.tokens.clone()
     }
 
+    /// The lexical errors found while scanning, in source order.
+    pub fn errors(&self) -> &[ScanError] {
+        &self.errors
+    }
+
+    /// The comments skipped while scanning, in source order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Whether the source ended inside a string or block comment.
+    pub fn unterminated(&self) -> bool {
+        self.unterminated
+    }
+
+    /// Column of the current token, or 0 if it began on an earlier line (a multi-line string
+    /// is reported on the line where it ends).
+    fn column(&self) -> usize {
+        self.start
+            .checked_sub(self.line_start)
+            .map_or(0, |column| column + 1)
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    fn error(&mut self, message: &str) {
+        self.errors.push(ScanError {
+            location: Location::new(self.line, self.column(), String::new()),
+            message: message.to_string(),
+        });
+    }
+
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
+        self.current >= self.chars.len()
     }
 
     fn scan_token(&mut self) {
@@ -110,57 +223,82 @@ impl Scanner {
                 }
             }
             '/' => {
+                let (line, column) = (self.line, self.column());
                 if self.match_char('*') {
-                    while !(self.is_at_end() || self.peek() == '*' && self.peak_next() == '/') {
-                        if self.peek() == '\n' {
-                            self.line += 1;
+                    // Block comments nest, so code that has some can still be commented out.
+                    let mut depth = 1;
+                    while depth > 0 && !self.is_at_end() {
+                        if self.peek() == '/' && self.peak_next() == '*' {
+                            self.current += 2;
+                            depth += 1;
+                        } else if self.peek() == '*' && self.peak_next() == '/' {
+                            self.current += 2;
+                            depth -= 1;
+                        } else if self.advance() == '\n' {
+                            self.new_line();
                         }
-                        self.advance();
                     }
-                    if self.is_at_end() {
-                        error(self.line, "Unterminated block comment");
-                        return;
+                    if depth > 0 {
+                        self.error("Unterminated block comment");
+                        self.unterminated = true;
                     } else {
-                        self.advance();
-                    }
-                    if self.is_at_end() {
-                        error(self.line, "Unterminated block comment");
-                    } else {
-                        self.advance();
+                        self.comment(line, column);
                     }
                 } else if self.match_char('/') {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.comment(line, column);
                 } else {
                     self.make_token(TokenType::SLASH, String::from("/"));
                 }
             }
             ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            '\n' => self.new_line(),
             '"' => self.string(),
             '0'..='9' => self.number(),
             '_' | 'a'..='z' | 'A'..='Z' => self.identifier(),
-            _ => error(self.line, "Unexpected character."),
+            _ => self.error("Unexpected character."),
+        }
+    }
+
+    fn comment(&mut self, line: usize, column: usize) {
+        let text = self.text(self.start, self.current);
+        let trailing = self
+            .tokens
+            .iter()
+            .rev()
+            .find(|token| token.token_type != TokenType::COMMENT)
+            .is_some_and(|token| token.line == line);
+        if self.comment_tokens {
+            let token = Token::at(TokenType::COMMENT, text.clone(), line, column);
+            self.tokens
+                .push(token.with_span(self.offset(self.start), self.offset(self.current)));
         }
+        self.comments.push(Comment {
+            line,
+            text,
+            trailing,
+        });
     }
 
     fn string(&mut self) {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
             self.advance();
+            if self.previous() == '\n' {
+                self.new_line();
+            }
         }
 
         if self.is_at_end() {
-            error(self.line, "Unterminated string");
+            self.error("Unterminated string");
+            self.unterminated = true;
             return;
         }
 
         self.advance();
 
-        let value = self.source[self.start + 1..self.current - 1].to_string();
+        let value = self.text(self.start + 1, self.current - 1);
 
         self.make_token(TokenType::STRING, value);
     }
@@ -178,9 +316,8 @@ impl Scanner {
             }
         }
 
-        let value = self.source[self.start..self.current]
-            .parse::<f64>()
-            .unwrap();
+        // Digits with at most one '.' between them always parse.
+        let value = self.text(self.start, self.current).parse::<f64>().unwrap();
 
         self.make_token(TokenType::NUMBER, value.to_string());
     }
@@ -190,32 +327,43 @@ impl Scanner {
             self.advance();
         }
 
-        let str = &self.source[self.start..self.current];
+        let str = self.text(self.start, self.current);
+
+        self.make_token(match_keyword(&str), str);
+    }
+
+    /// The byte offset of a character index.
+    fn offset(&self, index: usize) -> usize {
+        self.offsets[index]
+    }
 
-        self.make_token(match_keyword(str), String::from(str));
+    /// The source between two character indices.
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[self.offset(start)..self.offset(end)].to_string()
     }
 
     fn peak_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current + 1).unwrap()
+        self.chars.get(self.current + 1).copied().unwrap_or('\0')
+    }
+
+    fn previous(&self) -> char {
+        self.chars[self.current - 1]
     }
 
     fn advance(&mut self) -> char {
         self.current += 1;
-        self.source.chars().nth(self.current - 1).unwrap()
+        self.chars[self.current - 1]
     }
 
     fn make_token(&mut self, token_type: TokenType, literal: String) {
-        self.tokens.push(Token::new(token_type, literal, self.line));
+        let column = self.column();
+        let (start, end) = (self.offset(self.start), self.offset(self.current));
+        self.tokens
+            .push(Token::at(token_type, literal, self.line, column).with_span(start, end));
     }
 
     fn match_char(&mut self, char: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        if self.source.chars().nth(self.current) != Some(char) {
+        if self.is_at_end() || self.peek() != char {
             return false;
         }
 
@@ -224,10 +372,7 @@ impl Scanner {
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current).unwrap()
+        self.chars.get(self.current).copied().unwrap_or('\0')
     }
 }
 
@@ -235,11 +380,29 @@ impl Scanner {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_dump_tokens() {
+        let tokens = Scanner::new("var x = 1;\nprint \"hi\";".to_string()).scan_tokens();
+        assert_eq!(
+            dump_tokens(&tokens),
+            "     1:1      0..3 VAR            var\n\
+             \x20    1:5      4..5 IDENTIFIER     x\n\
+             \x20    1:7      6..7 EQUAL          =\n\
+             \x20    1:9      8..9 NUMBER         1\n\
+             \x20   1:10     9..10 SEMICOLON      ;\n\
+             \x20    2:1    11..16 PRINT          print\n\
+             \x20    2:7    17..21 STRING         hi\n\
+             \x20   2:11    21..22 SEMICOLON      ;\n\
+             \x20   2:12    22..22 EOF            \n"
+        );
+    }
+
     #[test]
     fn test_block_comments() {
         let mut scanner = Scanner::new("/* This is a block comment */".to_string());
         let tokens = scanner.scan_tokens();
-        assert_eq!(Token::new(TokenType::EOF, String::new(), 1), tokens[0]);
+        let eof = Token::at(TokenType::EOF, String::new(), 1, 30).with_span(29, 29);
+        assert_eq!(eof, tokens[0]);
     }
 
     #[test]
@@ -247,6 +410,96 @@ mod tests {
         let mut scanner =
             Scanner::new("/* This is a block comment with // slashes in it */".to_string());
         let tokens = scanner.scan_tokens();
-        assert_eq!(Token::new(TokenType::EOF, String::new(), 1), tokens[0]);
+        let eof = Token::at(TokenType::EOF, String::new(), 1, 52).with_span(51, 51);
+        assert_eq!(eof, tokens[0]);
+    }
+
+    #[test]
+    fn test_nested_block_comments() {
+        let mut scanner = Scanner::new(String::from(
+            "/* outer /* inner */ still\n in comment */ print 1; /* /* */",
+        ));
+        let tokens = scanner.scan_tokens();
+        let types = tokens
+            .iter()
+            .map(|token| token.token_type)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::PRINT,
+                TokenType::NUMBER,
+                TokenType::SEMICOLON,
+                TokenType::EOF
+            ]
+        );
+        assert_eq!(tokens[0].line, 2);
+        assert_eq!(
+            scanner.comments()[0].text,
+            "/* outer /* inner */ still\n in comment */"
+        );
+        assert!(scanner.unterminated());
+    }
+
+    #[test]
+    fn test_comment_tokens() {
+        let source = "// note\nprint 1; /* a\n b */ print 2;";
+        let tokens = Scanner::new(source.to_string())
+            .with_comments()
+            .scan_tokens();
+        let comments = tokens
+            .iter()
+            .filter(|token| token.token_type == TokenType::COMMENT)
+            .map(|token| (token.lexeme.as_str(), token.line, token.column))
+            .collect::<Vec<_>>();
+        assert_eq!(comments, vec![("// note", 1, 1), ("/* a\n b */", 2, 10)]);
+        assert_eq!(
+            Scanner::new(source.to_string()).scan_tokens().len(),
+            tokens.len() - 2
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let source = "print \"héllo\" + aü; // ok";
+        let tokens = Scanner::new(source.to_string())
+            .with_comments()
+            .scan_tokens();
+        let spans = tokens
+            .iter()
+            .map(|token| &source[token.start..token.end])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec!["print", "\"héllo\"", "+", "aü", ";", "// ok", ""]
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut scanner = Scanner::new(
+            "var a = 1;
+  print a;"
+                .to_string(),
+        );
+        let columns = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| (token.line, token.column))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                (1, 1),
+                (1, 5),
+                (1, 7),
+                (1, 9),
+                (1, 10),
+                (2, 3),
+                (2, 9),
+                (2, 10),
+                (2, 11)
+            ]
+        );
     }
 }
@@ -0,0 +1,207 @@
+//! Where diagnostics go. A session writes every error and warning it's given and keeps count,
+//! so the driver can work out an exit code without any global state.
+
+use crate::diagnostic::{report, report_json, Location, Severity};
+use crate::interpreter::{ErrorKind, InterpretError};
+use crate::lint::{Level, Lints, Rule};
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+/// How many frames of a stack trace to print before eliding the rest.
+const MAX_TRACE: usize = 20;
+
+pub struct Session {
+    output: Rc<RefCell<dyn Write>>,
+    errors: usize,
+    runtime_errors: usize,
+    warnings: usize,
+    /// Report warnings as errors, so that they fail compilation.
+    pub deny_warnings: bool,
+    /// Which warnings are reported, and how.
+    pub lints: Lints,
+    /// Write each diagnostic as a line of JSON instead of for people to read.
+    pub json: bool,
+    /// The script being compiled, named in JSON diagnostics.
+    pub file: Option<String>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new(Rc::new(RefCell::new(std::io::stderr())))
+    }
+}
+
+impl Session {
+    pub fn new(output: Rc<RefCell<dyn Write>>) -> Self {
+        Self {
+            output,
+            errors: 0,
+            runtime_errors: 0,
+            warnings: 0,
+            deny_warnings: false,
+            lints: Lints::default(),
+            json: false,
+            file: None,
+        }
+    }
+
+    /// Reports a compile error: a lexical, syntax or resolution problem.
+    pub fn error(&mut self, location: &Location, message: &str) {
+        self.errors += 1;
+        self.emit(Severity::Error, None, location, message);
+    }
+
+    /// Reports something suspicious that doesn't stop the program from running, at the level
+    /// set for `rule`. Denied rules, or all of them when warnings are denied, are errors.
+    pub fn warning(&mut self, rule: Rule, location: &Location, message: &str) {
+        let severity = match self.lints.level(rule) {
+            Level::Allow => return,
+            Level::Deny => Severity::Error,
+            Level::Warn if self.deny_warnings => Severity::Error,
+            Level::Warn => Severity::Warning,
+        };
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        self.emit(severity, Some(rule.name()), location, message);
+    }
+
+    /// Reports an error from the resolver or interpreter along with the calls it unwound
+    /// through.
+    pub fn report_error(&mut self, error: &InterpretError) {
+        if error.kind == ErrorKind::ResolveError {
+            self.errors += 1;
+        } else {
+            self.runtime_errors += 1;
+        }
+        self.emit(
+            Severity::Error,
+            Some(error.kind.code()),
+            &Location::from(error.token.as_ref()).with_span(error.span.clone()),
+            &error.message,
+        );
+        if self.json {
+            return;
+        }
+        let mut output = self.output.borrow_mut();
+        if error.trace.is_empty() {
+            return;
+        }
+        let _ = writeln!(
+            output,
+            "  in {} at line {}",
+            error.trace[0].function, error.token.line
+        );
+        for (i, frame) in error.trace.iter().enumerate().take(MAX_TRACE) {
+            let caller = error
+                .trace
+                .get(i + 1)
+                .map_or("script", |caller| caller.function.as_str());
+            let _ = writeln!(output, "  called from {} at line {}", caller, frame.line);
+        }
+        if error.trace.len() > MAX_TRACE {
+            let _ = writeln!(output, "  ... and {} more", error.trace.len() - MAX_TRACE);
+        }
+    }
+
+    fn emit(&self, severity: Severity, code: Option<&str>, location: &Location, message: &str) {
+        let output = &mut *self.output.borrow_mut();
+        if self.json {
+            report_json(
+                output,
+                self.file.as_deref(),
+                severity,
+                code,
+                location,
+                message,
+            );
+        } else {
+            report(output, severity, code, location, message);
+        }
+    }
+
+    /// How many compile errors have been reported so far.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.warnings
+    }
+
+    pub fn had_error(&self) -> bool {
+        self.errors > 0
+    }
+
+    pub fn had_runtime_error(&self) -> bool {
+        self.runtime_errors > 0
+    }
+
+    /// 65 if anything failed to compile, else 70 if anything failed at runtime, else 0.
+    pub fn exit_code(&self) -> i32 {
+        if self.had_error() {
+            65
+        } else if self.had_runtime_error() {
+            70
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::{Token, TokenType};
+
+    #[test]
+    fn test_exit_code() {
+        let mut session = Session::new(Rc::new(RefCell::new(std::io::sink())));
+        session.warning(
+            Rule::UnreachableCode,
+            &Location::new(1, 0, String::new()),
+            "Suspicious.",
+        );
+        assert_eq!(session.exit_code(), 0);
+        let token = Token::new(TokenType::IDENTIFIER, String::from("x"), 1);
+        session.report_error(&InterpretError::new(
+            ErrorKind::NameError,
+            String::from("Undefined variable 'x'."),
+            token,
+        ));
+        assert_eq!(session.exit_code(), 70);
+        session.error(&Location::new(1, 0, String::new()), "Unexpected character.");
+        assert_eq!(session.exit_code(), 65);
+    }
+
+    #[test]
+    fn test_deny_warnings() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut session = Session::new(output.clone());
+        session.deny_warnings = true;
+        session.warning(
+            Rule::UnreachableCode,
+            &Location::new(1, 0, String::new()),
+            "Suspicious.",
+        );
+        assert_eq!(session.warnings(), 0);
+        assert_eq!(session.exit_code(), 65);
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "error[unreachable-code]: Suspicious.\n --> line 1\n"
+        );
+    }
+
+    #[test]
+    fn test_lint_levels() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut session = Session::new(output.clone());
+        session.lints.set(Rule::UnreachableCode, Level::Allow);
+        session.lints.set(Rule::MixedEquality, Level::Deny);
+        let location = Location::new(1, 0, String::new());
+        session.warning(Rule::UnreachableCode, &location, "Unreachable.");
+        assert!(output.borrow().is_empty());
+        session.warning(Rule::MixedEquality, &location, "Mixed.");
+        assert_eq!(session.errors(), 1);
+    }
+}
@@ -1,16 +1,404 @@
-use crate::{expr::Expr, token::Token};
+use crate::{
+    expr::{self, json_string, Expr, Variable},
+    token::Token,
+};
+use std::ops::Range;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     Expr(Expr),
-    Print(Expr),
-    Var(Token, Option<Expr>),
-    Assign(Token, Expr),
+    /// The values to print, separated by spaces; there's at least one.
+    Print(Vec<Expr>),
+    /// A declaration with its initializer and type annotation, if any.
+    Var(Token, Option<Expr>, Option<Token>),
     Block(Vec<Stmt>),
     If(Expr, Box<Stmt>, Option<Box<Stmt>>),
-    While(Expr, Box<Stmt>),
-    Break,
-    Function(Token, Vec<Token>, Vec<Stmt>),
-    Class(Token, Vec<Stmt>),
-    Return(Token, Option<Expr>),
+    /// A loop, with the increment of a `for` loop if it came from one. The increment runs after
+    /// the body on every iteration. A `for` loop without a condition has none, and runs until
+    /// it's broken out of.
+    While(Option<Expr>, Box<Stmt>, Option<Expr>),
+    Break(Token),
+    /// Skips the rest of the loop's body, though not its increment.
+    Continue(Token),
+    Function(Token, Vec<Token>, Vec<Stmt>, Signature),
+    /// A class with the class it inherits from, its methods and the `///` comments above it,
+    /// if any.
+    Class(Token, Option<Variable>, Vec<Stmt>, Option<String>),
+    /// `return;`, `return value;`, or `return a, b;` to give back several values at once.
+    Return(Token, Vec<Expr>),
+    /// `var a, b = value;` with its `var` keyword, or `a, b = value;` without: each name takes
+    /// one of the values a function gave back with `return a, b;`, in order. With several
+    /// values, as in `a, b = b, a;`, each name takes one of those instead.
+    Unpack(Option<Token>, Vec<Token>, Vec<Expr>),
+    /// `with resource as name { body }`: the body runs with `name` bound to the resource, whose
+    /// `close()` is called when it finishes, however it finishes.
+    With(Token, Expr, Token, Vec<Stmt>),
+}
+
+/// The type annotations on a function's parameters and return value. Each is the name of a
+/// type, and None where there isn't one. The `///` comments above the function come along as
+/// its documentation.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Signature {
+    pub parameters: Vec<Option<Token>>,
+    pub returns: Option<Token>,
+    pub doc: Option<String>,
+}
+
+impl Signature {
+    pub fn parameter(&self, index: usize) -> Option<&Token> {
+        self.parameters.get(index).and_then(Option::as_ref)
+    }
+}
+
+/// How a function is called, as it was declared: `name(a: number, b): string`.
+pub fn signature(name: &Token, params: &[Token], signature: &Signature) -> String {
+    let params = params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| annotated(&param.lexeme, signature.parameter(i)))
+        .collect::<Vec<String>>()
+        .join(", ");
+    annotated(
+        &format!("{}({})", name.lexeme, params),
+        signature.returns.as_ref(),
+    )
+}
+
+/// `name` followed by `: type` if it's annotated.
+pub fn annotated(name: &str, annotation: Option<&Token>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}: {}", name, annotation.lexeme),
+        None => name.to_string(),
+    }
+}
+
+/// The line a statement starts on, roughly: statements that begin with a keyword the parser
+/// doesn't keep report the line of their first expression instead. Blocks have none.
+pub fn line(stmt: &Stmt) -> Option<usize> {
+    match stmt {
+        Stmt::Expr(expr) => Some(expr::line(expr)),
+        Stmt::Print(values) => values.first().map(expr::line),
+        Stmt::If(condition, _, _) | Stmt::While(Some(condition), _, _) => {
+            Some(expr::line(condition))
+        }
+        Stmt::While(None, body, _) => line(body),
+        Stmt::Var(name, _, _)
+        | Stmt::Function(name, _, _, _)
+        | Stmt::Class(name, ..)
+        | Stmt::Return(name, _)
+        | Stmt::With(name, ..)
+        | Stmt::Break(name)
+        | Stmt::Continue(name) => Some(name.line),
+        Stmt::Unpack(_, names, _) => Some(names[0].line),
+        Stmt::Block(_) => None,
+    }
+}
+
+/// The byte range a statement covers, roughly, in the same way as `line`: keywords, braces and
+/// semicolons the parser doesn't keep are left out. Empty blocks have none.
+pub fn span(stmt: &Stmt) -> Option<Range<usize>> {
+    let token = |token: &Token| token.start..token.end;
+    let through = |start: Range<usize>, end: Option<Range<usize>>| match end {
+        Some(end) => start.start..end.end,
+        None => start,
+    };
+    match stmt {
+        Stmt::Expr(expr) => Some(expr::span(expr)),
+        Stmt::Print(values) => Some(through(
+            expr::span(values.first()?),
+            values.last().map(expr::span),
+        )),
+        Stmt::Var(name, initializer, annotation) => Some(through(
+            token(name),
+            initializer
+                .as_ref()
+                .map(expr::span)
+                .or(annotation.as_ref().map(token)),
+        )),
+        Stmt::Block(stmts) => Some(through(span(stmts.first()?)?, stmts.last().and_then(span))),
+        Stmt::If(condition, then_branch, else_branch) => Some(through(
+            expr::span(condition),
+            else_branch
+                .as_deref()
+                .and_then(span)
+                .or_else(|| span(then_branch)),
+        )),
+        Stmt::While(Some(condition), body, _) => Some(through(expr::span(condition), span(body))),
+        Stmt::While(None, body, _) => span(body),
+        Stmt::Break(keyword) | Stmt::Continue(keyword) => Some(token(keyword)),
+        Stmt::Function(name, _, body, _) | Stmt::Class(name, _, body, _) => {
+            Some(through(token(name), body.last().and_then(span)))
+        }
+        Stmt::Return(keyword, values) => {
+            Some(through(token(keyword), values.last().map(expr::span)))
+        }
+        Stmt::Unpack(keyword, names, values) => Some(through(
+            token(keyword.as_ref().unwrap_or(&names[0])),
+            values.last().map(expr::span),
+        )),
+        Stmt::With(keyword, _, name, body) => Some(through(
+            token(keyword),
+            body.last().and_then(span).or(Some(token(name))),
+        )),
+    }
+}
+
+pub fn print(stmt: Stmt) -> String {
+    match stmt {
+        Stmt::Expr(expr) => format!("(expr {})", expr::print(expr)),
+        Stmt::Print(values) => format!(
+            "(print{})",
+            values
+                .into_iter()
+                .map(|value| format!(" {}", expr::print(value)))
+                .collect::<String>()
+        ),
+        Stmt::Var(name, None, annotation) => {
+            format!("(var {})", annotated(&name.lexeme, annotation.as_ref()))
+        }
+        Stmt::Var(name, Some(initializer), annotation) => format!(
+            "(var {} {})",
+            annotated(&name.lexeme, annotation.as_ref()),
+            expr::print(initializer)
+        ),
+        Stmt::Block(stmts) => format!("(block{})", print_all(stmts)),
+        Stmt::If(condition, then_branch, else_branch) => match else_branch {
+            Some(else_branch) => format!(
+                "(if {} {} {})",
+                expr::print(condition),
+                print(*then_branch),
+                print(*else_branch)
+            ),
+            None => format!("(if {} {})", expr::print(condition), print(*then_branch)),
+        },
+        Stmt::While(condition, body, None) => {
+            format!("(while {} {})", print_condition(condition), print(*body))
+        }
+        Stmt::While(condition, body, Some(increment)) => format!(
+            "(while {} {} {})",
+            print_condition(condition),
+            print(*body),
+            expr::print(increment)
+        ),
+        Stmt::Break(_) => String::from("(break)"),
+        Stmt::Continue(_) => String::from("(continue)"),
+        Stmt::Function(name, params, body, signature) => format!(
+            "(fun {} ({}){}{})",
+            name.lexeme,
+            params
+                .iter()
+                .enumerate()
+                .map(|(i, param)| annotated(&param.lexeme, signature.parameter(i)))
+                .collect::<Vec<String>>()
+                .join(" "),
+            annotated("", signature.returns.as_ref()),
+            print_all(body)
+        ),
+        Stmt::Class(name, superclass, methods, _) => format!(
+            "(class {}{}{})",
+            name.lexeme,
+            superclass.map_or(String::new(), |superclass| format!(
+                " < {}",
+                superclass.name.lexeme
+            )),
+            print_all(methods)
+        ),
+        Stmt::Return(_, values) => format!(
+            "(return{})",
+            values
+                .into_iter()
+                .map(|value| format!(" {}", expr::print(value)))
+                .collect::<String>()
+        ),
+        Stmt::With(_, resource, name, body) => format!(
+            "(with {} {}{})",
+            expr::print(resource),
+            name.lexeme,
+            print_all(body)
+        ),
+        Stmt::Unpack(keyword, names, values) => format!(
+            "({} ({}) {})",
+            if keyword.is_some() { "var" } else { "=" },
+            names
+                .iter()
+                .map(|name| name.lexeme.clone())
+                .collect::<Vec<String>>()
+                .join(" "),
+            values
+                .into_iter()
+                .map(expr::print)
+                .collect::<Vec<String>>()
+                .join(" ")
+        ),
+    }
+}
+
+/// A loop's condition, with a missing one shown as the `true` it acts like.
+fn print_condition(condition: Option<Expr>) -> String {
+    condition.map_or(String::from("true"), expr::print)
+}
+
+fn print_all(stmts: Vec<Stmt>) -> String {
+    stmts
+        .into_iter()
+        .map(|stmt| format!(" {}", print(stmt)))
+        .collect()
+}
+
+pub fn to_json(stmt: Stmt) -> String {
+    match stmt {
+        Stmt::Expr(expr) => format!(
+            r#"{{"type":"Expression","expression":{}}}"#,
+            expr::to_json(expr)
+        ),
+        Stmt::Print(values) => format!(
+            r#"{{"type":"Print","values":[{}]}}"#,
+            values
+                .into_iter()
+                .map(expr::to_json)
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+        Stmt::Var(name, initializer, annotation) => format!(
+            r#"{{"type":"Var","name":{},"initializer":{},"annotation":{}}}"#,
+            json_string(&name.lexeme),
+            initializer.map_or(String::from("null"), expr::to_json),
+            json_annotation(annotation.as_ref())
+        ),
+        Stmt::Block(stmts) => format!(r#"{{"type":"Block","statements":{}}}"#, to_json_all(stmts)),
+        Stmt::If(condition, then_branch, else_branch) => format!(
+            r#"{{"type":"If","condition":{},"then":{},"else":{}}}"#,
+            expr::to_json(condition),
+            to_json(*then_branch),
+            else_branch.map_or(String::from("null"), |stmt| to_json(*stmt))
+        ),
+        Stmt::While(condition, body, increment) => format!(
+            r#"{{"type":"While","condition":{},"body":{},"increment":{}}}"#,
+            condition.map_or(String::from("null"), expr::to_json),
+            to_json(*body),
+            increment.map_or(String::from("null"), expr::to_json)
+        ),
+        Stmt::Break(_) => String::from(r#"{"type":"Break"}"#),
+        Stmt::Continue(_) => String::from(r#"{"type":"Continue"}"#),
+        Stmt::Function(name, params, body, signature) => format!(
+            r#"{{"type":"Function","name":{},"params":[{}],"paramTypes":[{}],"returnType":{},"doc":{},"body":{}}}"#,
+            json_string(&name.lexeme),
+            params
+                .iter()
+                .map(|param| json_string(&param.lexeme))
+                .collect::<Vec<String>>()
+                .join(","),
+            (0..params.len())
+                .map(|i| json_annotation(signature.parameter(i)))
+                .collect::<Vec<String>>()
+                .join(","),
+            json_annotation(signature.returns.as_ref()),
+            json_doc(signature.doc.as_deref()),
+            to_json_all(body)
+        ),
+        Stmt::Class(name, superclass, methods, doc) => format!(
+            r#"{{"type":"Class","name":{},"superclass":{},"doc":{},"methods":{}}}"#,
+            json_string(&name.lexeme),
+            superclass.map_or(String::from("null"), |superclass| json_string(
+                &superclass.name.lexeme
+            )),
+            json_doc(doc.as_deref()),
+            to_json_all(methods)
+        ),
+        Stmt::Return(_, values) => format!(
+            r#"{{"type":"Return","values":[{}]}}"#,
+            values
+                .into_iter()
+                .map(expr::to_json)
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+        Stmt::With(_, resource, name, body) => format!(
+            r#"{{"type":"With","resource":{},"name":{},"body":{}}}"#,
+            expr::to_json(resource),
+            json_string(&name.lexeme),
+            to_json_all(body)
+        ),
+        Stmt::Unpack(keyword, names, values) => format!(
+            r#"{{"type":"Unpack","declaration":{},"names":[{}],"values":[{}]}}"#,
+            keyword.is_some(),
+            names
+                .iter()
+                .map(|name| json_string(&name.lexeme))
+                .collect::<Vec<String>>()
+                .join(","),
+            values
+                .into_iter()
+                .map(expr::to_json)
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+    }
+}
+
+fn json_doc(doc: Option<&str>) -> String {
+    doc.map_or(String::from("null"), json_string)
+}
+
+fn json_annotation(annotation: Option<&Token>) -> String {
+    annotation.map_or(String::from("null"), |annotation| {
+        json_string(&annotation.lexeme)
+    })
+}
+
+pub fn to_json_all(stmts: Vec<Stmt>) -> String {
+    format!(
+        "[{}]",
+        stmts
+            .into_iter()
+            .map(to_json)
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_print() {
+        let stmts = parse("fun add(a, b) { return a + b; } if (x) print \"hi\"; else y = 1;");
+        let printed = stmts.into_iter().map(print).collect::<Vec<String>>();
+        assert_eq!(
+            printed,
+            vec![
+                "(fun add (a b) (return (+ a b)))",
+                "(if x (print hi) (expr (= y 1)))"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = to_json_all(parse("var s = \"a\\b\";"));
+        assert_eq!(
+            json,
+            r#"[{"type":"Var","name":"s","initializer":{"type":"Literal","kind":"STRING","value":"a\\b"},"annotation":null}]"#
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let stmts = parse("fun f(a: number) { return (a, -a) ? a.b = 1 : g(a); } for (;;) break;");
+        let json = serde_json::to_string(&stmts).unwrap();
+        assert!(json.starts_with(r#"[{"Function":[{"token_type":"IDENTIFIER","lexeme":"f""#));
+        assert_eq!(serde_json::from_str::<Vec<Stmt>>(&json).unwrap(), stmts);
+    }
 }
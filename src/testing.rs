@@ -0,0 +1,258 @@
+//! `slow_lox test`: runs every `.lox` file under a directory and checks what it printed
+//! against the expectations written in its comments, as in the Crafting Interpreters suite:
+//!
+//! ```lox
+//! print 1 + 2; // expect: 3
+//! print nope;  // expect runtime error: Undefined variable 'nope'.
+//! ```
+//!
+//! Given a reference implementation such as `java -jar jlox.jar`, each file is also run through
+//! it and anything it printed or exited with differently counts as a failure too.
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    rc::Rc,
+};
+
+use slow_lox::{coverage::Coverage, interpreter::Interpreter, Status};
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+
+#[derive(Debug, Default, PartialEq)]
+struct Expectations {
+    output: Vec<String>,
+    runtime_error: Option<String>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Expectations::default();
+        for line in source.lines() {
+            if let Some((_, message)) = line.split_once(EXPECT_RUNTIME_ERROR) {
+                expectations.runtime_error = Some(message.trim_end().to_string());
+            } else if let Some((_, output)) = line.split_once(EXPECT) {
+                expectations.output.push(output.trim_end().to_string());
+            }
+        }
+        expectations
+    }
+
+    /// Everything about a run that didn't match, or nothing if it passed.
+    fn check(&self, status: Status, output: &str, diagnostics: &str) -> Vec<String> {
+        let mut failures = Vec::new();
+        let actual = output.lines().collect::<Vec<_>>();
+        for (i, expected) in self.output.iter().enumerate() {
+            match actual.get(i) {
+                Some(line) if line == expected => {}
+                Some(line) => {
+                    failures.push(format!("expected output '{}' but got '{}'", expected, line))
+                }
+                None => failures.push(format!("missing expected output '{}'", expected)),
+            }
+        }
+        for line in actual.iter().skip(self.output.len()) {
+            failures.push(format!("unexpected output '{}'", line));
+        }
+        let error = first_error(diagnostics);
+        match (&self.runtime_error, status) {
+            (Some(expected), Status::RuntimeError) if error == Some(expected.as_str()) => {}
+            (Some(expected), _) => failures.push(format!(
+                "expected runtime error '{}' but got {}",
+                expected,
+                error.map_or(String::from("none"), |error| format!("'{}'", error))
+            )),
+            (None, Status::Ok) => {}
+            (None, _) => failures.push(format!("unexpected error '{}'", error.unwrap_or_default())),
+        }
+        failures
+    }
+}
+
+/// The message of the first error, without its label and code. Warnings are skipped.
+fn first_error(diagnostics: &str) -> Option<&str> {
+    let line = diagnostics.lines().find(|line| line.starts_with("error"))?;
+    line.split_once(": ").map(|(_, message)| message)
+}
+
+/// Runs `file` through the `reference` command, given as a program and its arguments split on
+/// whitespace, and returns what it printed and its exit code.
+fn run_reference(reference: &str, file: &Path) -> Result<(String, i32), String> {
+    let mut words = reference.split_whitespace();
+    let program = words.next().ok_or("the reference command is empty")?;
+    let output = Command::new(program)
+        .args(words)
+        .arg(file)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("could not run '{}': {}", reference, e))?;
+    let code = output.status.code().unwrap_or(-1);
+    Ok((String::from_utf8_lossy(&output.stdout).into_owned(), code))
+}
+
+/// How a run differed from the reference's run of the same file: the first line of output
+/// that didn't match, and the exit code.
+fn diverges(ours: (&str, i32), reference: (&str, i32)) -> Vec<String> {
+    let mut divergences = Vec::new();
+    let (output, code) = ours;
+    let (expected, expected_code) = reference;
+    let lines = output.lines().map(Some).chain(std::iter::repeat(None));
+    let expected_lines = expected.lines().map(Some).chain(std::iter::repeat(None));
+    let first = lines
+        .zip(expected_lines)
+        .take_while(|pair| *pair != (None, None))
+        .enumerate()
+        .find(|(_, (line, expected))| line != expected);
+    if let Some((i, (line, expected))) = first {
+        let quote =
+            |line: Option<&str>| line.map_or(String::from("nothing"), |line| format!("'{}'", line));
+        divergences.push(format!(
+            "line {} of output is {} but the reference printed {}",
+            i + 1,
+            quote(line),
+            quote(expected)
+        ));
+    }
+    if code != expected_code {
+        divergences.push(format!(
+            "exited with {} but the reference exited with {}",
+            code, expected_code
+        ));
+    }
+    divergences
+}
+
+/// Runs every test under `dir` in an interpreter of its own, prints the failures and a
+/// summary, and returns whether they all passed. With `coverage`, an lcov report for all the
+/// tests is written there; with `reference`, each test is compared against that command too.
+pub fn run(
+    dir: &Path,
+    coverage: Option<&Path>,
+    reference: Option<&str>,
+    new_interpreter: impl Fn() -> Interpreter,
+) -> bool {
+    let mut files = Vec::new();
+    if let Err(e) = collect(dir, &mut files) {
+        eprintln!("Could not read '{}': {}", dir.display(), e);
+        std::process::exit(66);
+    }
+    files.sort();
+    // Expected errors are compared as text, so keep escape codes out of them.
+    slow_lox::diagnostic::set_color(false);
+    let mut failed = 0;
+    let mut report = String::new();
+    // Lines hit and coverable lines, across all the files.
+    let mut covered = (0, 0);
+    for file in &files {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                println!("FAIL {}\n  could not read it: {}", file.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let diagnostics = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = new_interpreter()
+            .with_stdout(output.clone())
+            .with_stderr(diagnostics.clone());
+        if coverage.is_some() {
+            interpreter.coverage = Some(Coverage::default());
+        }
+        let status = slow_lox::run(source.clone(), &mut interpreter);
+        if let Some(lines) = &interpreter.coverage {
+            report.push_str(&lines.lcov(&file.to_string_lossy()));
+            let (hit, total) = lines.summary();
+            covered = (covered.0 + hit, covered.1 + total);
+        }
+        let output = String::from_utf8_lossy(&output.borrow()).into_owned();
+        let diagnostics = String::from_utf8_lossy(&diagnostics.borrow()).into_owned();
+        let mut failures = Expectations::parse(&source).check(status, &output, &diagnostics);
+        if let Some(reference) = reference {
+            let code = interpreter.session.exit_code();
+            match run_reference(reference, file) {
+                Ok((expected, expected_code)) => {
+                    failures.extend(diverges((&output, code), (&expected, expected_code)))
+                }
+                Err(e) => failures.push(e),
+            }
+        }
+        if !failures.is_empty() {
+            failed += 1;
+            println!("FAIL {}", file.display());
+            for failure in failures {
+                println!("  {}", failure);
+            }
+        }
+    }
+    println!(
+        "{} passed, {} failed, {} total",
+        files.len() - failed,
+        failed,
+        files.len()
+    );
+    if let Some(path) = coverage {
+        if let Err(e) = std::fs::write(path, report) {
+            eprintln!("Could not write '{}': {}", path.display(), e);
+            std::process::exit(73);
+        }
+        println!("{} of {} lines covered", covered.0, covered.1);
+    }
+    failed == 0
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expectations() {
+        let expectations = Expectations::parse(
+            "print 1; // expect: 1\nprint \"a\"; // expect: a\nprint x; // expect runtime error: Undefined variable 'x'.",
+        );
+        assert_eq!(expectations.output, vec!["1", "a"]);
+        let diagnostics = "error[E102]: Undefined variable 'x'.\n --> line 3\n";
+        assert!(expectations
+            .check(Status::RuntimeError, "1\na\n", diagnostics)
+            .is_empty());
+        assert_eq!(
+            expectations.check(Status::RuntimeError, "1\nb\nc\n", diagnostics),
+            vec!["expected output 'a' but got 'b'", "unexpected output 'c'"]
+        );
+        assert_eq!(
+            expectations.check(Status::Ok, "1\na\n", ""),
+            vec!["expected runtime error 'Undefined variable 'x'.' but got none"]
+        );
+    }
+
+    #[test]
+    fn test_diverges() {
+        assert!(diverges(("1\n2\n", 0), ("1\n2\n", 0)).is_empty());
+        assert_eq!(
+            diverges(("1\n\"a\"\n", 70), ("1\na\n", 0)),
+            vec![
+                "line 2 of output is '\"a\"' but the reference printed 'a'",
+                "exited with 70 but the reference exited with 0"
+            ]
+        );
+        assert_eq!(
+            diverges(("1\n", 0), ("1\n2\n", 0)),
+            vec!["line 2 of output is nothing but the reference printed '2'"]
+        );
+    }
+}
@@ -1,22 +1,43 @@
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: usize,
+    /// 1-based; 0 for tokens made up by the interpreter rather than scanned.
+    pub column: usize,
+    /// Byte offsets of the token in the source, including any quotes, with `end` exclusive.
+    /// Both 0 for tokens that weren't scanned.
+    pub start: usize,
+    pub end: usize,
 }
 
 impl Token {
     pub fn new(token_type: TokenType, lexeme: String, line: usize) -> Token {
+        Token::at(token_type, lexeme, line, 0)
+    }
+
+    pub fn at(token_type: TokenType, lexeme: String, line: usize, column: usize) -> Token {
         Token {
             token_type,
             lexeme,
             line,
+            column,
+            start: 0,
+            end: 0,
         }
     }
+
+    pub fn with_span(mut self, start: usize, end: usize) -> Token {
+        self.start = start;
+        self.end = end;
+        self
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Copy, Eq, Hash)]
-#[allow(non_camel_case_types)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub enum TokenType {
     // Single-character tokens.
     LEFT_PAREN,
@@ -66,6 +87,12 @@ pub enum TokenType {
     VAR,
     WHILE,
     BREAK,
+    CONTINUE,
+    WITH,
+    IN,
+
+    // Only scanned for tools that ask for them; the parser never sees these.
+    COMMENT,
 
     EOF,
 }
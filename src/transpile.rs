@@ -0,0 +1,513 @@
+//! Transpiling to JavaScript: readable code that runs a program's statements in a browser or
+//! Node, with a small runtime that keeps Lox's truthiness, arithmetic and printing.
+
+use crate::{
+    expr::{json_string, Expr},
+    stmt::Stmt,
+    token::{Token, TokenType},
+};
+
+const INDENT: &str = "    ";
+
+/// The helpers transpiled code calls. Their names start with `$`, which a Lox identifier
+/// can't, so they never clash with the program's own.
+const RUNTIME: &str = r#""use strict";
+
+class $Error extends Error {}
+
+const $truthy = (value) => value != null && value !== false;
+
+function $str(value) {
+    if (value == null) return "nil";
+    if (typeof value === "number") {
+        if (Object.is(value, -0)) return "-0";
+        return String(value).replace("e+", "e");
+    }
+    if (typeof value === "function") {
+        if (value.$class) return value.name;
+        // Lox's names are what's in the source, without the `$` reserved words get.
+        const params = /\(([^)]*)\)/.exec(String(value))[1].replaceAll("$", "");
+        return `<fn> ${value.name.replace("$", "")}(${params})`;
+    }
+    if (Array.isArray(value)) return `[${value.map($str).join(", ")}]`;
+    if (typeof value === "object") return `${value.constructor.name} instance`;
+    return String(value);
+}
+
+const $print = (...values) => console.log(values.map($str).join(" "));
+
+function $numbers(a, b) {
+    if (typeof a !== "number" || typeof b !== "number") throw new $Error("Operands must be numbers.");
+}
+
+function $add(a, b) {
+    if (typeof a === "number" && typeof b === "number") return a + b;
+    const text = (value) => typeof value === "string" || typeof value === "number";
+    if ((typeof a === "string" || typeof b === "string") && text(a) && text(b)) {
+        return $str(a) + $str(b);
+    }
+    throw new $Error("Operands must be two numbers or two strings.");
+}
+
+const $sub = (a, b) => ($numbers(a, b), a - b);
+const $mul = (a, b) => ($numbers(a, b), a * b);
+const $div = (a, b) => ($numbers(a, b), a / b);
+const $lt = (a, b) => ($numbers(a, b), a < b);
+const $le = (a, b) => ($numbers(a, b), a <= b);
+const $gt = (a, b) => ($numbers(a, b), a > b);
+const $ge = (a, b) => ($numbers(a, b), a >= b);
+const $eq = (a, b) => (a ?? null) === (b ?? null) || (Number.isNaN(a) && Number.isNaN(b));
+function $in(item, container) {
+    if (Array.isArray(container)) return container.some((element) => $eq(element, item));
+    if (typeof container === "string" && typeof item === "string") return container.includes(item);
+    throw new $Error("Can only look for a string in a string or a value in a list.");
+}
+
+function $neg(value) {
+    if (typeof value !== "number") throw new $Error("Operand must be a number.");
+    return -value;
+}
+
+const $and = (left, right) => ($truthy(left) ? right() : left);
+const $or = (left, right) => ($truthy(left) ? left : right());
+
+function $call(callee, ...args) {
+    if (typeof callee !== "function") throw new $Error("Can only call functions and classes.");
+    const init = callee.$class ? callee.prototype.init : undefined;
+    const arity = callee.$class ? (init ? init.length : 0) : callee.length;
+    if (args.length !== arity) {
+        throw new $Error(`Expected ${arity} arguments but got ${args.length}.`);
+    }
+    if (!callee.$class) return callee(...args);
+    const instance = new callee();
+    if (init) init.apply(instance, args);
+    return instance;
+}
+
+function $unpack(value, count) {
+    const values = Array.isArray(value) ? value : [value];
+    if (values.length !== count) {
+        throw new $Error(`Expected ${count} values to unpack but got ${values.length}.`);
+    }
+    return values;
+}
+
+const $instance = (value) => typeof value === "object" && value !== null && !Array.isArray(value);
+
+function $get(object, name) {
+    if (!$instance(object)) throw new $Error("Only instances have properties.");
+    if (Object.hasOwn(object, name)) return object[name];
+    for (let proto = Object.getPrototypeOf(object); proto !== Object.prototype; proto = Object.getPrototypeOf(proto)) {
+        if (name !== "constructor" && Object.hasOwn(proto, name)) return proto[name].bind(object);
+    }
+    throw new $Error(`Undefined property '${name}'.`);
+}
+
+function $set(object, name, value) {
+    if (!$instance(object)) throw new $Error("Only instances have fields.");
+    return (object[name] = value);
+}
+
+var clock = () => Date.now() / 1000;
+var random = () => Math.random();
+// JavaScript keeps no Lox call stack to look at.
+var callerInfo = () => [];
+var toFixed = (number, digits) => formatNumber(number, digits, "");
+var formatNumber = (number, digits, separator) => {
+    if (!Number.isFinite(number)) return $str(number);
+    const [whole, fraction] = Math.abs(number).toFixed(digits).split(".");
+    const grouped = whole.replace(/\B(?=(\d{3})+$)/g, separator);
+    const zero = /^[0.]*$/.test(whole + (fraction ?? ""));
+    return (number < 0 && !zero ? "-" : "") + grouped + (fraction === undefined ? "" : "." + fraction);
+};
+var str = (value) => (typeof value === "string" ? value : $str(value));
+var join = (list, separator) => list.map(str).join(separator);
+
+function StringBuilder() {
+    const parts = [];
+    return Object.assign(Object.create(StringBuilder.prototype), {
+        append: (value) => (parts.push(str(value)), null),
+        toString: () => parts.join(""),
+    });
+}
+var args = () => [];
+"#;
+
+/// Names that are fine in Lox but reserved in JavaScript, or that would change what a class
+/// or object means. They get a trailing `$`.
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "case",
+    "catch",
+    "const",
+    "constructor",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "finally",
+    "function",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "static",
+    "switch",
+    "throw",
+    "try",
+    "typeof",
+    "undefined",
+    "void",
+    "with",
+    "yield",
+    "__proto__",
+];
+
+/// `stmts` as a JavaScript program, runtime included.
+pub fn javascript(stmts: &[Stmt]) -> String {
+    let mut out = String::from(RUNTIME);
+    out.push('\n');
+    let mut writer = Writer {
+        out: &mut out,
+        depth: 0,
+        in_method: false,
+        in_initializer: false,
+    };
+    for stmt in stmts {
+        writer.stmt(stmt);
+    }
+    out
+}
+
+fn name(token: &Token) -> String {
+    if RESERVED.contains(&token.lexeme.as_str()) {
+        format!("{}$", token.lexeme)
+    } else {
+        token.lexeme.clone()
+    }
+}
+
+struct Writer<'a> {
+    out: &'a mut String,
+    depth: usize,
+    /// Inside a method, where functions are written as arrow functions to keep its `this`.
+    in_method: bool,
+    /// Directly inside an `init` method, which gives back `this`.
+    in_initializer: bool,
+}
+
+impl Writer<'_> {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr) => {
+                let text = match expr {
+                    // Assignments don't need the parentheses they get inside other expressions.
+                    Expr::Assign(assign) => {
+                        format!("{} = {}", name(&assign.name), self::expr(&assign.value))
+                    }
+                    expr => self::expr(expr),
+                };
+                self.line(&format!("{};", text));
+            }
+            Stmt::Print(values) => {
+                let values = values.iter().map(self::expr).collect::<Vec<_>>();
+                self.line(&format!("$print({});", values.join(", ")));
+            }
+            Stmt::Var(token, initializer, _) => {
+                // Globals can be declared again; locals can't, and need block scope.
+                let keyword = if self.depth == 0 { "var" } else { "let" };
+                let value = initializer
+                    .as_ref()
+                    .map_or(String::from("null"), self::expr);
+                self.line(&format!("{} {} = {};", keyword, name(token), value));
+            }
+            Stmt::Block(stmts) => {
+                self.line("{");
+                self.body(stmts);
+                self.line("}");
+            }
+            Stmt::With(_, resource, name, body) => {
+                self.line("{");
+                self.depth += 1;
+                let name = self::name(name);
+                self.line(&format!("let {} = {};", name, self::expr(resource)));
+                self.line("try {");
+                self.body(body);
+                self.line("} finally {");
+                self.depth += 1;
+                self.line(&format!("$call($get({}, \"close\"));", name));
+                self.depth -= 1;
+                self.line("}");
+                self.depth -= 1;
+                self.line("}");
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.line(&format!("if ($truthy({})) {{", self::expr(condition)));
+                self.nested(then_branch);
+                let mut else_branch = else_branch.as_deref();
+                while let Some(branch) = else_branch {
+                    match branch {
+                        Stmt::If(condition, then_branch, next) => {
+                            self.line(&format!(
+                                "}} else if ($truthy({})) {{",
+                                self::expr(condition)
+                            ));
+                            self.nested(then_branch);
+                            else_branch = next.as_deref();
+                        }
+                        branch => {
+                            self.line("} else {");
+                            self.nested(branch);
+                            else_branch = None;
+                        }
+                    }
+                }
+                self.line("}");
+            }
+            Stmt::While(condition, body, increment) => {
+                let condition = match condition {
+                    Some(condition) => format!("$truthy({})", self::expr(condition)),
+                    None => String::from("true"),
+                };
+                match increment {
+                    Some(increment) => self.line(&format!(
+                        "for (; {}; {}) {{",
+                        condition,
+                        self::expr(increment)
+                    )),
+                    None => self.line(&format!("while ({}) {{", condition)),
+                }
+                self.nested(body);
+                self.line("}");
+            }
+            Stmt::Break(_) => self.line("break;"),
+            Stmt::Continue(_) => self.line("continue;"),
+            Stmt::Function(token, params, body, _) if self.in_method => {
+                self.line(&format!(
+                    "const {} = ({}) => {{",
+                    name(token),
+                    parameters(params)
+                ));
+                self.function_body(body);
+                self.line("};");
+            }
+            Stmt::Function(token, params, body, _) => {
+                self.line(&format!(
+                    "function {}({}) {{",
+                    name(token),
+                    parameters(params)
+                ));
+                self.function_body(body);
+                self.line("}");
+            }
+            Stmt::Class(token, superclass, methods, _) => {
+                match superclass {
+                    Some(superclass) => self.line(&format!(
+                        "class {} extends {} {{",
+                        name(token),
+                        name(&superclass.name)
+                    )),
+                    None => self.line(&format!("class {} {{", name(token))),
+                }
+                self.depth += 1;
+                self.line("static $class = true;");
+                let in_method = std::mem::replace(&mut self.in_method, true);
+                for method in methods {
+                    if let Stmt::Function(token, params, body, _) = method {
+                        self.line(&format!("{}({}) {{", name(token), parameters(params)));
+                        self.in_initializer = token.lexeme == "init";
+                        self.body(body);
+                        if self.in_initializer {
+                            self.depth += 1;
+                            self.line("return this;");
+                            self.depth -= 1;
+                        }
+                        self.in_initializer = false;
+                        self.line("}");
+                    }
+                }
+                self.in_method = in_method;
+                self.depth -= 1;
+                self.line("}");
+            }
+            Stmt::Return(_, values) => match values.as_slice() {
+                [] if self.in_initializer => self.line("return this;"),
+                [] => self.line("return;"),
+                [value] => self.line(&format!("return {};", self::expr(value))),
+                values => {
+                    let values = values.iter().map(self::expr).collect::<Vec<_>>();
+                    self.line(&format!("return [{}];", values.join(", ")));
+                }
+            },
+            Stmt::Unpack(keyword, names, values) => {
+                let keyword = match keyword {
+                    Some(_) if self.depth == 0 => "var ",
+                    Some(_) => "let ",
+                    None => "",
+                };
+                let value = match values.as_slice() {
+                    [value] => self::expr(value),
+                    values => {
+                        let values = values.iter().map(self::expr).collect::<Vec<_>>();
+                        format!("[{}]", values.join(", "))
+                    }
+                };
+                self.line(&format!(
+                    "{}[{}] = $unpack({}, {});",
+                    keyword,
+                    parameters(names),
+                    value,
+                    names.len()
+                ));
+            }
+        }
+    }
+
+    /// A function's body, which returns what it says even inside an initializer.
+    fn function_body(&mut self, stmts: &[Stmt]) {
+        let in_initializer = std::mem::take(&mut self.in_initializer);
+        self.body(stmts);
+        self.in_initializer = in_initializer;
+    }
+
+    fn body(&mut self, stmts: &[Stmt]) {
+        self.depth += 1;
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.depth -= 1;
+    }
+
+    /// The statements of a branch or loop body, which go inside braces the caller writes.
+    fn nested(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(stmts) => self.body(stmts),
+            stmt => self.body(std::slice::from_ref(stmt)),
+        }
+    }
+}
+
+fn parameters(params: &[Token]) -> String {
+    params.iter().map(name).collect::<Vec<_>>().join(", ")
+}
+
+/// `expr` in JavaScript. Anything that isn't a call or a single token is parenthesized, so the
+/// result can go anywhere.
+fn expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Literal(literal) => match literal.value.token_type {
+            TokenType::STRING => json_string(&literal.value.lexeme),
+            TokenType::NIL => String::from("null"),
+            _ => literal.value.lexeme.clone(),
+        },
+        Expr::Grouping(grouping) => self::expr(&grouping.expression),
+        Expr::Variable(variable) => name(&variable.name),
+        Expr::Assign(assign) => format!("({} = {})", name(&assign.name), self::expr(&assign.value)),
+        Expr::Unary(unary) => match unary.operator.token_type {
+            TokenType::MINUS => format!("$neg({})", self::expr(&unary.right)),
+            _ => format!("!$truthy({})", self::expr(&unary.right)),
+        },
+        Expr::Binary(binary) => {
+            let left = self::expr(&binary.left);
+            let right = self::expr(&binary.right);
+            let helper = match binary.operator.token_type {
+                TokenType::COMMA => return format!("({}, {})", left, right),
+                TokenType::BANG_EQUAL => return format!("!$eq({}, {})", left, right),
+                TokenType::EQUAL_EQUAL => "$eq",
+                TokenType::PLUS => "$add",
+                TokenType::MINUS => "$sub",
+                TokenType::STAR => "$mul",
+                TokenType::SLASH => "$div",
+                TokenType::LESS => "$lt",
+                TokenType::LESS_EQUAL => "$le",
+                TokenType::GREATER => "$gt",
+                TokenType::IN => "$in",
+                _ => "$ge",
+            };
+            format!("{}({}, {})", helper, left, right)
+        }
+        Expr::Logical(logical) => {
+            let helper = match logical.operator.token_type {
+                TokenType::AND => "$and",
+                _ => "$or",
+            };
+            format!(
+                "{}({}, () => {})",
+                helper,
+                self::expr(&logical.left),
+                self::expr(&logical.right)
+            )
+        }
+        Expr::Ternary(ternary) => format!(
+            "($truthy({}) ? {} : {})",
+            self::expr(&ternary.condition),
+            self::expr(&ternary.then_branch),
+            self::expr(&ternary.else_branch)
+        ),
+        Expr::Call(call) => {
+            let mut args = vec![self::expr(&call.callee)];
+            args.extend(call.arguments.iter().map(self::expr));
+            format!("$call({})", args.join(", "))
+        }
+        Expr::Get(get) => format!(
+            "$get({}, {})",
+            self::expr(&get.expr),
+            json_string(&get.name.lexeme)
+        ),
+        Expr::Set(set) => format!(
+            "$set({}, {}, {})",
+            self::expr(&set.expr),
+            json_string(&set.name.lexeme),
+            self::expr(&set.value)
+        ),
+        Expr::This(_) => String::from("this"),
+        Expr::Super(super_expr) => format!("super.{}.bind(this)", name(&super_expr.method)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn transpile(source: &str) -> String {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        javascript(&stmts)
+            .strip_prefix(RUNTIME)
+            .unwrap()
+            .trim_start()
+            .to_string()
+    }
+
+    #[test]
+    fn test_javascript() {
+        let source = "fun count(new) {\n  for (var i = 0; i < new; i = i + 1) {\n    if (i == 1 and !done) print \"one\"; else print str(i);\n  }\n}\nclass A { go() { return count(3); } }\nvar a = A();\na.x = a.go();\n";
+        assert_eq!(
+            transpile(source),
+            "function count(new$) {\n    {\n        let i = 0;\n        for (; $truthy($lt(i, new$)); (i = $add(i, 1))) {\n            if ($truthy($and($eq(i, 1), () => !$truthy(done)))) {\n                $print(\"one\");\n            } else {\n                $print($call(str, i));\n            }\n        }\n    }\n}\nclass A {\n    static $class = true;\n    go() {\n        return $call(count, 3);\n    }\n}\nvar a = $call(A);\n$set(a, \"x\", $call($get(a, \"go\")));\n"
+        );
+    }
+}
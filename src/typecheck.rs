@@ -0,0 +1,671 @@
+//! Optional static types: checks values against the `: type` annotations on variables,
+//! parameters and return values before anything runs. Code without annotations stays dynamic,
+//! except that locals which are never reassigned take the type of their initializer and
+//! functions the type they return, so operations that can't work on those get a warning.
+
+use crate::{
+    expr::{self, Expr},
+    lint::Rule,
+    stmt::{Signature, Stmt},
+    token::{Token, TokenType},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    /// Not annotated, or not known until runtime.
+    Any,
+    Number,
+    String,
+    Boolean,
+    Nil,
+    Function,
+}
+
+impl Type {
+    pub const NAMES: [(&'static str, Type); 6] = [
+        ("any", Type::Any),
+        ("number", Type::Number),
+        ("string", Type::String),
+        ("bool", Type::Boolean),
+        ("nil", Type::Nil),
+        ("function", Type::Function),
+    ];
+
+    pub fn name(self) -> &'static str {
+        Type::NAMES
+            .iter()
+            .find(|(_, ty)| *ty == self)
+            .map_or("any", |(name, _)| name)
+    }
+
+    /// Whether a value of type `other` can go where this type is expected.
+    pub fn accepts(self, other: Type) -> bool {
+        self == Type::Any || other == Type::Any || self == other
+    }
+
+    /// The type both `self` and `other` fit, which is only known if they're the same.
+    fn join(self, other: Type) -> Type {
+        if self == other {
+            self
+        } else {
+            Type::Any
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct TypeError {
+    pub token: Token,
+    pub message: String,
+    /// The rule for a problem found through inferred types, which is only a warning.
+    pub rule: Option<Rule>,
+}
+
+/// Checks `stmts` against their annotations and inferred types, returning every problem in
+/// source order.
+pub fn check(stmts: &[Stmt]) -> Vec<TypeError> {
+    let mut assigned = HashSet::new();
+    for stmt in stmts {
+        assigned_in_stmt(stmt, &mut assigned);
+    }
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        assigned,
+        function: None,
+        errors: Vec::new(),
+    };
+    // Global functions can be called from code above them. Bad annotations get reported when
+    // the declarations are checked in order.
+    for stmt in stmts {
+        if let Stmt::Function(name, params, _, signature) = stmt {
+            let function = checker.function_type(params.len(), signature);
+            checker.bind_function(name, function);
+        }
+    }
+    checker.errors.clear();
+    checker.stmts(stmts);
+    checker.errors
+}
+
+/// The parameter and return types of a function declared with `fun`.
+#[derive(Clone)]
+struct FunctionType {
+    parameters: Vec<Type>,
+    returns: Type,
+}
+
+#[derive(Clone)]
+struct Binding {
+    ty: Type,
+    function: Option<FunctionType>,
+}
+
+/// What the function being checked returns: the annotated type, or what its `return`s give
+/// so far when it has none.
+struct Returns {
+    annotation: Option<Type>,
+    returned: Option<Type>,
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Every name assigned to anywhere, whose type can't be inferred from its declaration.
+    assigned: HashSet<String>,
+    /// None at the top level.
+    function: Option<Returns>,
+    errors: Vec<TypeError>,
+}
+
+impl Checker {
+    fn error(&mut self, token: &Token, message: String) {
+        self.errors.push(TypeError {
+            token: token.clone(),
+            message,
+            rule: None,
+        });
+    }
+
+    fn warning(&mut self, token: &Token, message: String) {
+        self.errors.push(TypeError {
+            token: token.clone(),
+            message,
+            rule: Some(Rule::TypeMismatch),
+        });
+    }
+
+    /// Reports `actual` if it can't go where `expected` is wanted, at the start of `expr`.
+    fn expect(&mut self, expected: Type, actual: Type, expr: &Expr) {
+        if !expected.accepts(actual) {
+            let message = format!("Expected {} but got {}.", expected, actual);
+            self.error(expr::first_token(expr), message);
+        }
+    }
+
+    /// The type an annotation names; unknown names are reported and treated as `any`.
+    fn annotation(&mut self, annotation: Option<&Token>) -> Type {
+        let Some(annotation) = annotation else {
+            return Type::Any;
+        };
+        match Type::NAMES
+            .iter()
+            .find(|(name, _)| *name == annotation.lexeme)
+        {
+            Some((_, ty)) => *ty,
+            None => {
+                let message = format!("Unknown type '{}'.", annotation.lexeme);
+                self.error(annotation, message);
+                Type::Any
+            }
+        }
+    }
+
+    fn function_type(&mut self, arity: usize, signature: &Signature) -> FunctionType {
+        FunctionType {
+            parameters: (0..arity)
+                .map(|i| self.annotation(signature.parameter(i)))
+                .collect(),
+            returns: self.annotation(signature.returns.as_ref()),
+        }
+    }
+
+    fn bind(&mut self, name: &Token, ty: Type, function: Option<FunctionType>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), Binding { ty, function });
+        }
+    }
+
+    /// Binds a `fun` declaration, which is only known to stay a function if it's never
+    /// assigned to.
+    fn bind_function(&mut self, name: &Token, function: FunctionType) {
+        if self.assigned.contains(&name.lexeme) {
+            self.bind(name, Type::Any, None);
+        } else {
+            self.bind(name, Type::Function, Some(function));
+        }
+    }
+
+    fn lookup(&self, name: &Token) -> Option<&Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr) => {
+                self.expr(expr);
+            }
+            Stmt::Print(values) => {
+                for value in values {
+                    self.expr(value);
+                }
+            }
+            Stmt::Var(name, initializer, annotation) => {
+                let ty = self.annotation(annotation.as_ref());
+                let actual = match initializer {
+                    Some(initializer) => {
+                        let actual = self.expr(initializer);
+                        self.expect(ty, actual, initializer);
+                        actual
+                    }
+                    None => Type::Nil,
+                };
+                // Globals can be changed by the next REPL entry, so only locals are inferred.
+                let inferred = annotation.is_none()
+                    && self.scopes.len() > 1
+                    && !self.assigned.contains(&name.lexeme);
+                self.bind(name, if inferred { actual } else { ty }, None);
+            }
+            Stmt::Block(stmts) => {
+                self.scopes.push(HashMap::new());
+                self.stmts(stmts);
+                self.scopes.pop();
+            }
+            Stmt::With(_, resource, name, body) => {
+                let ty = self.expr(resource);
+                self.scopes.push(HashMap::new());
+                self.bind(name, ty, None);
+                self.stmts(body);
+                self.scopes.pop();
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::While(condition, body, increment) => {
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                self.stmt(body);
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+            Stmt::Function(name, params, body, signature) => {
+                let mut function = self.function_type(params.len(), signature);
+                // Calls from its own body see it returning `any`.
+                self.bind_function(name, function.clone());
+                function.returns = self.function(params, body, signature, function.clone());
+                self.bind_function(name, function);
+            }
+            Stmt::Class(_, _, methods, _) => {
+                for method in methods {
+                    if let Stmt::Function(_, params, body, signature) = method {
+                        let function = self.function_type(params.len(), signature);
+                        self.function(params, body, signature, function);
+                    }
+                }
+            }
+            Stmt::Unpack(keyword, names, values) => {
+                for value in values {
+                    self.expr(value);
+                }
+                // Which value each name gets isn't known until runtime.
+                if keyword.is_some() {
+                    for name in names {
+                        self.bind(name, Type::Any, None);
+                    }
+                }
+            }
+            Stmt::Return(keyword, values) => {
+                let actual = match values.as_slice() {
+                    [] => Type::Nil,
+                    [value] => self.expr(value),
+                    values => {
+                        for value in values {
+                            self.expr(value);
+                        }
+                        Type::Any
+                    }
+                };
+                let Some(returns) = &mut self.function else {
+                    return;
+                };
+                let Some(expected) = returns.annotation else {
+                    returns.returned = Some(match returns.returned {
+                        Some(returned) => returned.join(actual),
+                        None => actual,
+                    });
+                    return;
+                };
+                match values.as_slice() {
+                    [value] => self.expect(expected, actual, value),
+                    [] if !expected.accepts(Type::Nil) => {
+                        let message = format!("Expected {} but got nil.", expected);
+                        self.error(keyword, message);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Checks a function body, returning its return type: the annotated one, or else the one
+    /// every way out of the body agrees on.
+    fn function(
+        &mut self,
+        params: &[Token],
+        body: &[Stmt],
+        signature: &Signature,
+        function: FunctionType,
+    ) -> Type {
+        let scope = params
+            .iter()
+            .zip(function.parameters)
+            .map(|(param, ty)| {
+                let binding = Binding { ty, function: None };
+                (param.lexeme.clone(), binding)
+            })
+            .collect();
+        self.scopes.push(scope);
+        let annotation = signature.returns.as_ref().map(|_| function.returns);
+        let enclosing = self.function.replace(Returns {
+            annotation,
+            returned: None,
+        });
+        self.stmts(body);
+        let returns = std::mem::replace(&mut self.function, enclosing);
+        self.scopes.pop();
+        let returns = returns.expect("the function's own returns were just pushed");
+        if let Some(annotation) = returns.annotation {
+            return annotation;
+        }
+        // Running off the end returns nil, which only can't happen after a final `return`.
+        match (returns.returned, body.last()) {
+            (Some(returned), Some(Stmt::Return(..))) => returned,
+            (Some(returned), _) => returned.join(Type::Nil),
+            (None, _) => Type::Nil,
+        }
+    }
+
+    fn assign(&mut self, name: &Token, value: &Expr) -> Type {
+        let actual = self.expr(value);
+        if let Some(expected) = self.lookup(name).map(|binding| binding.ty) {
+            self.expect(expected, actual, value);
+        }
+        actual
+    }
+
+    /// The type `expr` always has, checking any calls and assignments inside it.
+    fn expr(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal(literal) => match literal.value.token_type {
+                TokenType::NUMBER => Type::Number,
+                TokenType::STRING => Type::String,
+                TokenType::TRUE | TokenType::FALSE => Type::Boolean,
+                TokenType::NIL => Type::Nil,
+                _ => Type::Any,
+            },
+            Expr::Grouping(grouping) => self.expr(&grouping.expression),
+            Expr::Unary(unary) => {
+                let right = self.expr(&unary.right);
+                match unary.operator.token_type {
+                    TokenType::BANG => Type::Boolean,
+                    TokenType::MINUS => {
+                        self.expect_number(&unary.operator, right);
+                        Type::Number
+                    }
+                    _ => Type::Any,
+                }
+            }
+            Expr::Binary(binary) => {
+                let left = self.expr(&binary.left);
+                let right = self.expr(&binary.right);
+                let operator = &binary.operator;
+                match operator.token_type {
+                    TokenType::COMMA => right,
+                    TokenType::PLUS => self.add(operator, left, right),
+                    TokenType::MINUS | TokenType::STAR | TokenType::SLASH => {
+                        self.expect_number(operator, left);
+                        self.expect_number(operator, right);
+                        Type::Number
+                    }
+                    TokenType::GREATER
+                    | TokenType::GREATER_EQUAL
+                    | TokenType::LESS
+                    | TokenType::LESS_EQUAL => {
+                        self.expect_number(operator, left);
+                        self.expect_number(operator, right);
+                        Type::Boolean
+                    }
+                    TokenType::EQUAL_EQUAL | TokenType::BANG_EQUAL | TokenType::IN => Type::Boolean,
+                    _ => Type::Any,
+                }
+            }
+            Expr::Logical(logical) => {
+                let left = self.expr(&logical.left);
+                left.join(self.expr(&logical.right))
+            }
+            Expr::Ternary(ternary) => {
+                self.expr(&ternary.condition);
+                let then_branch = self.expr(&ternary.then_branch);
+                then_branch.join(self.expr(&ternary.else_branch))
+            }
+            Expr::Variable(variable) => self
+                .lookup(&variable.name)
+                .map_or(Type::Any, |binding| binding.ty),
+            Expr::Assign(assignment) => self.assign(&assignment.name, &assignment.value),
+            Expr::Call(call) => {
+                let callee = self.expr(&call.callee);
+                if matches!(
+                    callee,
+                    Type::Number | Type::String | Type::Boolean | Type::Nil
+                ) {
+                    let message = format!("Can't call a {}.", callee);
+                    self.warning(expr::first_token(&call.callee), message);
+                }
+                let function = match call.callee.as_ref() {
+                    Expr::Variable(variable) => self
+                        .lookup(&variable.name)
+                        .and_then(|binding| binding.function.clone()),
+                    _ => None,
+                };
+                for (i, argument) in call.arguments.iter().enumerate() {
+                    let actual = self.expr(argument);
+                    if let Some(expected) = function
+                        .as_ref()
+                        .and_then(|function| function.parameters.get(i))
+                    {
+                        self.expect(*expected, actual, argument);
+                    }
+                }
+                function.map_or(Type::Any, |function| function.returns)
+            }
+            Expr::Get(get) => {
+                self.expr(&get.expr);
+                Type::Any
+            }
+            Expr::Set(set) => {
+                self.expr(&set.expr);
+                self.expr(&set.value)
+            }
+            Expr::This(_) | Expr::Super(_) => Type::Any,
+        }
+    }
+
+    /// The type of `left + right`, which works on two numbers, or on a string and a string or
+    /// number.
+    fn add(&mut self, operator: &Token, left: Type, right: Type) -> Type {
+        let addable = |ty| matches!(ty, Type::Any | Type::Number | Type::String);
+        if !addable(left) || !addable(right) {
+            let message = format!("Can't add a {} and a {}.", left, right);
+            self.warning(operator, message);
+            return Type::Any;
+        }
+        match (left, right) {
+            (Type::Number, Type::Number) => Type::Number,
+            (Type::String, _) | (_, Type::String) => Type::String,
+            _ => Type::Any,
+        }
+    }
+
+    fn expect_number(&mut self, operator: &Token, operand: Type) {
+        if !Type::Number.accepts(operand) {
+            let message = format!("Can't use '{}' on a {}.", operator.lexeme, operand);
+            self.warning(operator, message);
+        }
+    }
+}
+
+/// Adds the name of every variable `stmt` assigns to.
+fn assigned_in_stmt(stmt: &Stmt, assigned: &mut HashSet<String>) {
+    match stmt {
+        Stmt::Expr(expr) => assigned_in_expr(expr, assigned),
+        Stmt::Print(values) => {
+            for value in values {
+                assigned_in_expr(value, assigned);
+            }
+        }
+        Stmt::Var(_, initializer, _) => {
+            if let Some(initializer) = initializer {
+                assigned_in_expr(initializer, assigned);
+            }
+        }
+        Stmt::Return(_, values) => {
+            for value in values {
+                assigned_in_expr(value, assigned);
+            }
+        }
+        Stmt::Unpack(keyword, names, values) => {
+            if keyword.is_none() {
+                assigned.extend(names.iter().map(|name| name.lexeme.clone()));
+            }
+            for value in values {
+                assigned_in_expr(value, assigned);
+            }
+        }
+        Stmt::Block(stmts) | Stmt::Function(_, _, stmts, _) | Stmt::Class(_, _, stmts, _) => {
+            for stmt in stmts {
+                assigned_in_stmt(stmt, assigned);
+            }
+        }
+        Stmt::With(_, resource, _, body) => {
+            assigned_in_expr(resource, assigned);
+            for stmt in body {
+                assigned_in_stmt(stmt, assigned);
+            }
+        }
+        Stmt::If(condition, then_branch, else_branch) => {
+            assigned_in_expr(condition, assigned);
+            assigned_in_stmt(then_branch, assigned);
+            if let Some(else_branch) = else_branch {
+                assigned_in_stmt(else_branch, assigned);
+            }
+        }
+        Stmt::While(condition, body, increment) => {
+            if let Some(condition) = condition {
+                assigned_in_expr(condition, assigned);
+            }
+            assigned_in_stmt(body, assigned);
+            if let Some(increment) = increment {
+                assigned_in_expr(increment, assigned);
+            }
+        }
+        Stmt::Break(_) | Stmt::Continue(_) => {}
+    }
+}
+
+fn assigned_in_expr(expr: &Expr, assigned: &mut HashSet<String>) {
+    match expr {
+        Expr::Assign(assignment) => {
+            assigned.insert(assignment.name.lexeme.clone());
+            assigned_in_expr(&assignment.value, assigned);
+        }
+        Expr::Binary(binary) => {
+            assigned_in_expr(&binary.left, assigned);
+            assigned_in_expr(&binary.right, assigned);
+        }
+        Expr::Logical(logical) => {
+            assigned_in_expr(&logical.left, assigned);
+            assigned_in_expr(&logical.right, assigned);
+        }
+        Expr::Grouping(grouping) => assigned_in_expr(&grouping.expression, assigned),
+        Expr::Unary(unary) => assigned_in_expr(&unary.right, assigned),
+        Expr::Ternary(ternary) => {
+            assigned_in_expr(&ternary.condition, assigned);
+            assigned_in_expr(&ternary.then_branch, assigned);
+            assigned_in_expr(&ternary.else_branch, assigned);
+        }
+        Expr::Call(call) => {
+            assigned_in_expr(&call.callee, assigned);
+            for argument in &call.arguments {
+                assigned_in_expr(argument, assigned);
+            }
+        }
+        Expr::Get(get) => assigned_in_expr(&get.expr, assigned),
+        Expr::Set(set) => {
+            assigned_in_expr(&set.expr, assigned);
+            assigned_in_expr(&set.value, assigned);
+        }
+        Expr::Literal(_) | Expr::Variable(_) | Expr::This(_) | Expr::Super(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn errors(source: &str) -> Vec<String> {
+        let stmts = Parser::new(Scanner::new(source.to_string()).scan_tokens())
+            .parse()
+            .unwrap();
+        check(&stmts)
+            .into_iter()
+            .map(|e| match e.rule {
+                Some(rule) => format!("{}: {} [{}]", e.token.lexeme, e.message, rule),
+                None => format!("{}: {}", e.token.lexeme, e.message),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_annotations() {
+        let source = "
+            var x: number = \"one\";
+            var y: string = 1 < 2 ? \"a\" : \"b\";
+            fun add(a: number, b: number): number { return a + b; }
+            var s: string = add(1, \"2\");
+            fun greet(name): string { if (name) return; return \"hi \" + name; }
+            { var n: nil = nil; n = 1 + 2; }
+            var z: widget;
+        ";
+        assert_eq!(
+            errors(source),
+            vec![
+                "one: Expected number but got string.",
+                "2: Expected number but got string.",
+                "add: Expected string but got number.",
+                "return: Expected string but got nil.",
+                "1: Expected nil but got number.",
+                "widget: Unknown type 'widget'.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unannotated_code_is_dynamic() {
+        let source = "
+            var x = 1; x = \"one\";
+            fun f(a) { return a; }
+            var n: number = f(\"s\");
+            fun later(): bool { return g(1); }
+            fun g(a: number): bool { return a > 0; }
+        ";
+        assert!(errors(source).is_empty());
+    }
+
+    #[test]
+    fn test_inference() {
+        let source = "
+            fun two() { return 2; }
+            fun maybe(a) { if (a) return 1; }
+            {
+                var n = two();
+                var b = n > 1;
+                var s = \"x\" + n;
+                n();
+                print b + 1;
+                print 1 - s;
+                var m = maybe(true);
+                m();
+                var later = 1;
+                later = nil;
+                later();
+                var text: number = s;
+            }
+            var g = 1;
+            g();
+        ";
+        assert_eq!(
+            errors(source),
+            vec![
+                "n: Can't call a number. [type-mismatch]",
+                "+: Can't add a bool and a number. [type-mismatch]",
+                "-: Can't use '-' on a string. [type-mismatch]",
+                "s: Expected number but got string.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::interpreter::{environment::Environment, Interpreter};
+use std::{cell::RefCell, io::Write, rc::Rc};
+use wasm_bindgen::prelude::*;
+
+/// What a playground needs to show after running a program.
+#[wasm_bindgen]
+pub struct RunResult {
+    output: String,
+    diagnostics: String,
+}
+
+#[wasm_bindgen]
+impl RunResult {
+    #[wasm_bindgen(getter)]
+    pub fn output(&self) -> String {
+        self.output.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn diagnostics(&self) -> String {
+        self.diagnostics.clone()
+    }
+}
+
+/// Runs `source` in a fresh interpreter, capturing everything it prints and every error.
+#[wasm_bindgen]
+pub fn run(source: &str) -> RunResult {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let diagnostics = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new(Rc::new(RefCell::new(Environment::global())))
+        .with_stdout(output.clone())
+        .with_stderr(diagnostics.clone());
+
+    crate::run(source.to_string(), &mut interpreter);
+
+    let _ = output.borrow_mut().flush();
+    let output = String::from_utf8_lossy(&output.borrow()).into_owned();
+    let diagnostics = String::from_utf8_lossy(&diagnostics.borrow()).into_owned();
+    RunResult {
+        output,
+        diagnostics,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_captures_output_and_diagnostics() {
+        let result = run("print 1 + 2; print nope;");
+        assert_eq!(result.output(), "3\n");
+        assert_eq!(
+            result.diagnostics(),
+            "error[E102]: Undefined variable 'nope'.\n  --> line 1:20\n  |\n1 | print 1 + 2; print nope;\n  |                    ^^^^\n"
+        );
+
+        let result = run("print (;");
+        assert_eq!(result.output(), "");
+        assert!(result.diagnostics().contains("Expect expression."));
+    }
+}